@@ -0,0 +1,25 @@
+CREATE TABLE IF NOT EXISTS console_triggers (
+	pk INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+	id BLOB NOT NULL UNIQUE,
+	server_id BLOB NOT NULL,
+	name TEXT NOT NULL,
+	pattern TEXT NOT NULL,
+	stream TEXT NOT NULL,
+	cooldown INTEGER NOT NULL,
+	action TEXT NOT NULL,
+	enabled INTEGER NOT NULL,
+	created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS console_triggers_server_id ON console_triggers (server_id);
+
+CREATE TABLE IF NOT EXISTS server_events (
+	pk INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+	id BLOB NOT NULL UNIQUE,
+	server_id BLOB NOT NULL,
+	trigger_id BLOB,
+	message TEXT NOT NULL,
+	created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS server_events_server_id ON server_events (server_id, created_at);
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS ssh_keys (
+	pk INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+	id BLOB NOT NULL UNIQUE,
+	user_id BLOB NOT NULL,
+	name TEXT NOT NULL,
+	public_key TEXT NOT NULL,
+	fingerprint TEXT NOT NULL,
+	created_at INTEGER NOT NULL,
+	UNIQUE (user_id, fingerprint),
+	FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
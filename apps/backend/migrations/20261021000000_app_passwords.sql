@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS app_passwords (
+	pk INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+	id BLOB NOT NULL UNIQUE,
+	user_id BLOB NOT NULL,
+	name TEXT NOT NULL,
+	password_hash TEXT NOT NULL UNIQUE,
+	created_at INTEGER NOT NULL,
+	FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
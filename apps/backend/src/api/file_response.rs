@@ -0,0 +1,219 @@
+use crate::models::file_manager::types::FSFileEntry;
+use axum::http::{header, HeaderMap, HeaderValue};
+use std::time::{Duration, SystemTime};
+
+/// Number of leading bytes inspected when detecting a file's content type
+pub const CONTENT_SNIFF_LENGTH: usize = 512;
+
+/// Result of evaluating a `Range` header against a file
+#[derive(Debug, PartialEq, Eq)]
+pub enum ByteRange {
+	/// Serve the whole file
+	Full,
+	/// Serve the inclusive byte range `start..=end`
+	Partial { start: u64, end: u64 },
+	/// The requested range lies outside of the file
+	Unsatisfiable,
+}
+
+/// Format an entity tag for use in a header
+pub fn etag_header(etag: &str) -> String {
+	format!("\"{etag}\"")
+}
+
+/// Format a Unix timestamp as an HTTP date
+pub fn http_date(timestamp: i64) -> Option<String> {
+	let secs = u64::try_from(timestamp).ok()?;
+	Some(httpdate::fmt_http_date(
+		SystemTime::UNIX_EPOCH + Duration::from_secs(secs),
+	))
+}
+
+/// Check whether an `If-Match` or `If-None-Match` header value matches an entity tag. Weak tags
+/// only match with the weak comparison of `If-None-Match`, as writes need the exact content.
+fn etag_matches(header_value: &str, etag: &str, weak: bool) -> bool {
+	header_value.split(',').map(str::trim).any(|tag| {
+		if tag == "*" {
+			return true;
+		}
+
+		let (is_weak, tag) = match tag.strip_prefix("W/") {
+			Some(tag) => (true, tag),
+			None => (false, tag),
+		};
+
+		(weak || !is_weak) && tag.trim_matches('"') == etag
+	})
+}
+
+/// Parse an HTTP date header into a Unix timestamp
+fn header_timestamp(headers: &HeaderMap, name: header::HeaderName) -> Option<i64> {
+	let value = headers.get(name)?.to_str().ok()?;
+	let time = httpdate::parse_http_date(value).ok()?;
+	let secs = time.duration_since(SystemTime::UNIX_EPOCH).ok()?.as_secs();
+
+	i64::try_from(secs).ok()
+}
+
+fn header_str(headers: &HeaderMap, name: header::HeaderName) -> Option<&str> {
+	headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+/// Evaluate `If-None-Match` and `If-Modified-Since`. Returns true if the client's copy is
+/// current and a `304 Not Modified` should be sent.
+pub fn is_not_modified(headers: &HeaderMap, file: &FSFileEntry) -> bool {
+	if let Some(if_none_match) = header_str(headers, header::IF_NONE_MATCH) {
+		return etag_matches(if_none_match, &file.etag, true);
+	}
+
+	match (
+		header_timestamp(headers, header::IF_MODIFIED_SINCE),
+		file.modified,
+	) {
+		(Some(since), Some(modified)) => modified <= since,
+		_ => false,
+	}
+}
+
+/// Evaluate `If-Match` and `If-Unmodified-Since` before modifying a file. `file` is `None`
+/// if nothing exists at the target path. Returns false if a `412 Precondition Failed` should be
+/// sent.
+pub fn write_preconditions_pass(headers: &HeaderMap, file: Option<&FSFileEntry>) -> bool {
+	if let Some(if_match) = header_str(headers, header::IF_MATCH) {
+		return file.is_some_and(|file| etag_matches(if_match, &file.etag, false));
+	}
+
+	match (
+		header_timestamp(headers, header::IF_UNMODIFIED_SINCE),
+		file.and_then(|file| file.modified),
+	) {
+		(Some(since), Some(modified)) => modified <= since,
+		_ => true,
+	}
+}
+
+/// Evaluate the `Range` and `If-Range` headers for a file. Only single byte ranges are
+/// supported; other range requests are answered with the full file.
+pub fn requested_range(headers: &HeaderMap, file: &FSFileEntry) -> ByteRange {
+	let Some(range) = header_str(headers, header::RANGE) else {
+		return ByteRange::Full;
+	};
+
+	if let Some(if_range) = header_str(headers, header::IF_RANGE) {
+		let date_matches = file
+			.modified
+			.and_then(http_date)
+			.is_some_and(|date| date == if_range);
+
+		if !date_matches && if_range.trim_matches('"') != file.etag {
+			return ByteRange::Full;
+		}
+	}
+
+	parse_range(range, file.size)
+}
+
+fn parse_range(range: &str, size: u64) -> ByteRange {
+	let Some(spec) = range.trim().strip_prefix("bytes=") else {
+		return ByteRange::Full;
+	};
+
+	if spec.contains(',') {
+		return ByteRange::Full;
+	}
+
+	let Some((start, end)) = spec.trim().split_once('-') else {
+		return ByteRange::Full;
+	};
+
+	if start.is_empty() {
+		// Suffix range, e.g. "bytes=-500" for the last 500 bytes
+		return match end.parse::<u64>() {
+			Ok(0) => ByteRange::Unsatisfiable,
+			Ok(_) if size == 0 => ByteRange::Unsatisfiable,
+			Ok(suffix) => ByteRange::Partial {
+				start: size.saturating_sub(suffix),
+				end: size - 1,
+			},
+			Err(_) => ByteRange::Full,
+		};
+	}
+
+	let Ok(start) = start.parse::<u64>() else {
+		return ByteRange::Full;
+	};
+
+	if start >= size {
+		return ByteRange::Unsatisfiable;
+	}
+
+	let end = if end.is_empty() {
+		size - 1
+	} else {
+		match end.parse::<u64>() {
+			Ok(end) if end >= start => end.min(size - 1),
+			_ => return ByteRange::Full,
+		}
+	};
+
+	ByteRange::Partial { start, end }
+}
+
+/// Detect a file's content type from its extension, falling back to its leading bytes
+pub fn detect_content_type(name: &str, head: &[u8]) -> String {
+	if let Some(mime) = mime_guess::from_path(name).first() {
+		return mime.essence_str().to_string();
+	}
+
+	if let Some(kind) = infer::get(head) {
+		return kind.mime_type().to_string();
+	}
+
+	// Text files are common in server directories (logs, configs) and often lack a known extension
+	let is_text = match std::str::from_utf8(head) {
+		Ok(_) => true,
+		// A multi-byte character may be cut off at the end of the sniffed bytes
+		Err(err) => err.error_len().is_none(),
+	};
+
+	if is_text {
+		"text/plain; charset=utf-8".to_string()
+	} else {
+		"application/octet-stream".to_string()
+	}
+}
+
+/// Whether browsers run scripts in files of a content type when they are opened directly
+fn is_active_content(content_type: &str) -> bool {
+	let essence = content_type.split(';').next().unwrap_or_default().trim();
+
+	matches!(
+		essence,
+		"text/html" | "application/xhtml+xml" | "image/svg+xml" | "text/xml" | "application/xml"
+	)
+}
+
+/// Keep browsers from running a served file in the panel's origin. Content types are never
+/// sniffed, and active content is downloaded instead of shown and sandboxed if it is shown anyway.
+pub fn protect_content(headers: &mut HeaderMap) {
+	headers.insert(
+		header::X_CONTENT_TYPE_OPTIONS,
+		HeaderValue::from_static("nosniff"),
+	);
+
+	let is_active = headers
+		.get(header::CONTENT_TYPE)
+		.and_then(|value| value.to_str().ok())
+		.is_some_and(is_active_content);
+
+	if is_active {
+		headers.insert(
+			header::CONTENT_DISPOSITION,
+			HeaderValue::from_static("attachment"),
+		);
+		headers.insert(
+			header::CONTENT_SECURITY_POLICY,
+			HeaderValue::from_static("sandbox"),
+		);
+	}
+}
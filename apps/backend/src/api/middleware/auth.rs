@@ -1,38 +1,107 @@
 use crate::{config::AUTH_COOKIE_NAME, services::auth::AuthServiceError, AppState};
 use axum::{
 	extract::{Request, State},
-	http::StatusCode,
+	http::{header, HeaderMap, StatusCode},
 	middleware::Next,
-	response::Response,
+	response::{IntoResponse, Response},
 };
+use base64::{engine::general_purpose, Engine};
 use std::sync::Arc;
 use tower_cookies::Cookies;
 
+/// Credentials a request is authenticated with
+enum Credentials {
+	/// An auth token from the auth cookie, or a bearer token for clients that can't store cookies
+	Token(String),
+	/// An app password sent with HTTP Basic authentication, for clients that can only send a
+	/// username and password, such as network drive clients
+	AppPassword { username: String, password: String },
+}
+
+/// Internal: Get the credentials of a request, preferring the auth cookie
+fn request_credentials(cookies: &Cookies, headers: &HeaderMap) -> Option<Credentials> {
+	if let Some(cookie) = cookies.get(AUTH_COOKIE_NAME) {
+		return Some(Credentials::Token(cookie.value().to_string()));
+	}
+
+	let authorization = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+
+	if let Some(token) = authorization.strip_prefix("Bearer ") {
+		return Some(Credentials::Token(token.to_string()));
+	}
+
+	let encoded = authorization.strip_prefix("Basic ")?;
+	let decoded = general_purpose::STANDARD.decode(encoded.trim()).ok()?;
+	let (username, password) = std::str::from_utf8(&decoded).ok()?.split_once(':')?;
+
+	Some(Credentials::AppPassword {
+		username: username.to_string(),
+		password: password.to_string(),
+	})
+}
+
+/// Whether a request carries a sudo token, in the auth cookie or as a bearer token. For routes
+/// that only need sudo for some changes; others use `require_sudo`.
+pub fn request_is_sudo(state: &AppState, cookies: &Cookies, headers: &HeaderMap) -> bool {
+	match request_credentials(cookies, headers) {
+		Some(Credentials::Token(token)) => {
+			matches!(state.auth_service.token_is_sudo(&token), Ok(true))
+		}
+		Some(Credentials::AppPassword { .. }) | None => false,
+	}
+}
+
+/// Internal: Respond that a request isn't authenticated. DAV clients are asked for a username
+/// and password, which browsers would also do for requests of the panel itself.
+fn unauthorized(req: &Request) -> Response {
+	let is_dav = req
+		.uri()
+		.path()
+		.strip_prefix("/servers/")
+		.is_some_and(|path| path.split('/').nth(1) == Some("dav"));
+
+	if is_dav {
+		(
+			StatusCode::UNAUTHORIZED,
+			[(
+				header::WWW_AUTHENTICATE,
+				"Basic realm=\"ScaffoldMC\", charset=\"UTF-8\"",
+			)],
+		)
+			.into_response()
+	} else {
+		StatusCode::UNAUTHORIZED.into_response()
+	}
+}
+
 pub async fn require_auth(
 	cookies: Cookies,
 	State(state): State<Arc<AppState>>,
 	mut req: Request,
 	next: Next,
-) -> Result<Response, StatusCode> {
-	let token = cookies
-		.get(AUTH_COOKIE_NAME)
-		.map(|cookie| cookie.value().to_string());
-
-	if token.is_none() {
-		return Err(StatusCode::UNAUTHORIZED);
-	}
-
-	let token = token.unwrap();
+) -> Result<Response, Response> {
+	let user = match request_credentials(&cookies, req.headers()) {
+		Some(Credentials::Token(token)) => {
+			state.auth_service.get_user_from_token(&token, false).await
+		}
+		Some(Credentials::AppPassword { username, password }) => {
+			state
+				.auth_service
+				.verify_app_password(&username, &password)
+				.await
+		}
+		None => return Err(unauthorized(&req)),
+	};
 
-	let user = match state.auth_service.get_user_from_token(&token, false).await {
+	let user = match user {
 		Ok(user) => user,
 		Err(err) => match err {
 			AuthServiceError::Unauthorized | AuthServiceError::InvalidCredentials => {
-				return Err(StatusCode::UNAUTHORIZED)
+				return Err(unauthorized(&req))
 			}
 			_ => {
 				tracing::error!("Authentication error: {}", err);
-				return Err(StatusCode::INTERNAL_SERVER_ERROR);
+				return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
 			}
 		},
 	};
@@ -47,15 +116,12 @@ pub async fn require_sudo(
 	mut req: Request,
 	next: Next,
 ) -> Result<Response, StatusCode> {
-	let token = cookies
-		.get(AUTH_COOKIE_NAME)
-		.map(|cookie| cookie.value().to_string());
-
-	if token.is_none() {
-		return Err(StatusCode::UNAUTHORIZED);
-	}
-
-	let token = token.unwrap();
+	// App passwords never grant sudo
+	let token = match request_credentials(&cookies, req.headers()) {
+		Some(Credentials::Token(token)) => token,
+		Some(Credentials::AppPassword { .. }) => return Err(StatusCode::FORBIDDEN),
+		None => return Err(StatusCode::UNAUTHORIZED),
+	};
 
 	let user = match state.auth_service.get_user_from_token(&token, true).await {
 		Ok(user) => user,
@@ -0,0 +1,22 @@
+use axum::{
+	extract::{Request, State},
+	http::header,
+	middleware::Next,
+	response::Response,
+};
+use tower::{Layer, ServiceExt};
+use tower_http::cors::CorsLayer;
+
+/// Apply `cors` to requests from browsers. Requests without an origin are passed through, so
+/// `OPTIONS` requests from DAV clients reach their handler instead of being answered as
+/// preflight requests.
+pub async fn apply_cors(State(cors): State<CorsLayer>, req: Request, next: Next) -> Response {
+	if !req.headers().contains_key(header::ORIGIN) {
+		return next.run(req).await;
+	}
+
+	match cors.layer(next).oneshot(req).await {
+		Ok(response) => response,
+		Err(infallible) => match infallible {},
+	}
+}
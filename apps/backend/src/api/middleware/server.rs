@@ -34,6 +34,8 @@ pub async fn require_server(
 		},
 	};
 
+	// File routes only need the file manager, so they can be served from any implementation
+	req.extensions_mut().insert(server.get_fs());
 	req.extensions_mut().insert(server);
 	Ok(next.run(req).await)
 }
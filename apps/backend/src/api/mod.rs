@@ -1,3 +1,4 @@
+pub mod file_response;
 pub mod middleware;
 pub mod routes;
 pub mod types;
@@ -0,0 +1,75 @@
+use crate::api::middleware::auth::require_sudo;
+use crate::api::types::user::{AppPasswordCreateRequest, AppPasswordCreateResponse};
+use crate::db::models::user::User;
+use crate::services::user::UserServiceError;
+use crate::AppState;
+use axum::extract::{Path, State};
+use axum::middleware;
+use axum::{http::StatusCode, response::IntoResponse, routing, Extension, Json, Router};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// App passwords grant access to the panel's API and DAV shares, so changing them requires sudo
+pub fn create_router(state: &Arc<AppState>) -> Router<Arc<AppState>> {
+	Router::new()
+		.route("/", routing::post(post))
+		.route("/{id}", routing::delete(delete))
+		.route_layer(middleware::from_fn_with_state(state.clone(), require_sudo))
+		.route("/", routing::get(get))
+}
+
+/// List the app passwords of the current user
+async fn get(
+	Extension(user): Extension<User>,
+	State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+	match state.user_service.list_app_passwords(&user).await {
+		Ok(passwords) => (StatusCode::OK, Json(passwords)).into_response(),
+		Err(err) => handle_error(&err).into_response(),
+	}
+}
+
+/// Create an app password for the current user
+async fn post(
+	Extension(user): Extension<User>,
+	State(state): State<Arc<AppState>>,
+	Json(req): Json<AppPasswordCreateRequest>,
+) -> impl IntoResponse {
+	match state
+		.user_service
+		.create_app_password(&user, &req.name)
+		.await
+	{
+		Ok((app_password, password)) => (
+			StatusCode::CREATED,
+			Json(AppPasswordCreateResponse {
+				app_password,
+				password,
+			}),
+		)
+			.into_response(),
+		Err(err) => handle_error(&err).into_response(),
+	}
+}
+
+/// Revoke an app password of the current user
+async fn delete(
+	Path(id): Path<Uuid>,
+	Extension(user): Extension<User>,
+	State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+	match state.user_service.delete_app_password(&user, id).await {
+		Ok(true) => StatusCode::NO_CONTENT.into_response(),
+		Ok(false) => (StatusCode::NOT_FOUND, "No such app password").into_response(),
+		Err(err) => handle_error(&err).into_response(),
+	}
+}
+
+/// Internal: Map a user service error to a response
+fn handle_error(err: &UserServiceError) -> impl IntoResponse {
+	tracing::error!("Error managing app passwords: {}", err);
+	(
+		StatusCode::INTERNAL_SERVER_ERROR,
+		"Internal server error".to_string(),
+	)
+}
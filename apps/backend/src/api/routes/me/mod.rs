@@ -1,3 +1,6 @@
+mod app_passwords;
+mod ssh_keys;
+
 use crate::AppState;
 use crate::{api::middleware::auth::require_sudo, db::models::user::User};
 use axum::extract::State;
@@ -12,6 +15,8 @@ pub fn create_router(state: &Arc<AppState>) -> Router<Arc<AppState>> {
 		.route("/", routing::patch(patch))
 		.route_layer(middleware::from_fn_with_state(state.clone(), require_sudo))
 		.route("/", routing::get(get))
+		.nest("/ssh-keys", ssh_keys::create_router(state))
+		.nest("/app-passwords", app_passwords::create_router(state))
 }
 
 pub async fn get(Extension(user): Extension<User>) -> impl IntoResponse {
@@ -32,7 +37,9 @@ pub async fn patch(
 				crate::services::user::UserServiceError::UsernameTaken => {
 					return (StatusCode::CONFLICT, "Username already taken").into_response();
 				}
-				crate::services::user::UserServiceError::ServerError(_) => {
+				crate::services::user::UserServiceError::ServerError(_)
+				| crate::services::user::UserServiceError::InvalidSshKey(_)
+				| crate::services::user::UserServiceError::SshKeyExists => {
 					tracing::error!("Error updating username: {}", err);
 
 					return (
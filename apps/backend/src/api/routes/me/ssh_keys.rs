@@ -0,0 +1,74 @@
+use crate::api::middleware::auth::require_sudo;
+use crate::api::types::user::SshKeyCreateRequest;
+use crate::db::models::user::User;
+use crate::services::user::UserServiceError;
+use crate::AppState;
+use axum::extract::{Path, State};
+use axum::middleware;
+use axum::{http::StatusCode, response::IntoResponse, routing, Extension, Json, Router};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Keys grant access to the files of all servers over SFTP, so changing them requires sudo
+pub fn create_router(state: &Arc<AppState>) -> Router<Arc<AppState>> {
+	Router::new()
+		.route("/", routing::post(post))
+		.route("/{id}", routing::delete(delete))
+		.route_layer(middleware::from_fn_with_state(state.clone(), require_sudo))
+		.route("/", routing::get(get))
+}
+
+/// List the SSH keys of the current user
+async fn get(
+	Extension(user): Extension<User>,
+	State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+	match state.user_service.list_ssh_keys(&user).await {
+		Ok(keys) => (StatusCode::OK, Json(keys)).into_response(),
+		Err(err) => handle_error(&err).into_response(),
+	}
+}
+
+/// Register an SSH key for the current user
+async fn post(
+	Extension(user): Extension<User>,
+	State(state): State<Arc<AppState>>,
+	Json(req): Json<SshKeyCreateRequest>,
+) -> impl IntoResponse {
+	match state
+		.user_service
+		.add_ssh_key(&user, &req.name, &req.public_key)
+		.await
+	{
+		Ok(key) => (StatusCode::CREATED, Json(key)).into_response(),
+		Err(err) => handle_error(&err).into_response(),
+	}
+}
+
+/// Remove an SSH key of the current user
+async fn delete(
+	Path(id): Path<Uuid>,
+	Extension(user): Extension<User>,
+	State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+	match state.user_service.delete_ssh_key(&user, id).await {
+		Ok(true) => StatusCode::NO_CONTENT.into_response(),
+		Ok(false) => (StatusCode::NOT_FOUND, "No such SSH key").into_response(),
+		Err(err) => handle_error(&err).into_response(),
+	}
+}
+
+/// Internal: Map a user service error to a response
+fn handle_error(err: &UserServiceError) -> impl IntoResponse {
+	match err {
+		UserServiceError::InvalidSshKey(_) => (StatusCode::BAD_REQUEST, err.to_string()),
+		UserServiceError::SshKeyExists => (StatusCode::CONFLICT, err.to_string()),
+		UserServiceError::UsernameTaken | UserServiceError::ServerError(_) => {
+			tracing::error!("Error managing SSH keys: {}", err);
+			(
+				StatusCode::INTERNAL_SERVER_ERROR,
+				"Internal server error".to_string(),
+			)
+		}
+	}
+}
@@ -3,9 +3,10 @@ mod binaries;
 mod jvms;
 mod me;
 mod servers;
+mod system;
 mod versions;
 
-use crate::api::middleware::{auth::require_auth, log::log_request};
+use crate::api::middleware::{auth::require_auth, cors::apply_cors, log::log_request};
 use crate::AppState;
 use axum::{http, middleware, Router};
 use std::sync::Arc;
@@ -30,6 +31,18 @@ pub fn create_router(state: Arc<AppState>) -> Router {
 			http::header::CONTENT_TYPE,
 			http::header::ACCEPT,
 			http::header::CACHE_CONTROL,
+			http::header::RANGE,
+			http::header::IF_RANGE,
+			http::header::IF_MATCH,
+			http::header::IF_NONE_MATCH,
+			http::header::IF_MODIFIED_SINCE,
+			http::header::IF_UNMODIFIED_SINCE,
+		])
+		.expose_headers([
+			http::header::ETAG,
+			http::header::LAST_MODIFIED,
+			http::header::CONTENT_RANGE,
+			http::header::ACCEPT_RANGES,
 		])
 		.allow_credentials(true);
 
@@ -39,11 +52,12 @@ pub fn create_router(state: Arc<AppState>) -> Router {
 		.nest("/binaries", binaries::create_router())
 		.nest("/me", me::create_router(&state))
 		.nest("/jvms", jvms::create_router())
+		.nest("/system", system::create_router())
 		.route_layer(middleware::from_fn_with_state(state.clone(), require_auth))
 		.nest("/auth", auth::create_router(&state))
 		.route_layer(middleware::from_fn(log_request))
 		.layer(CookieManagerLayer::new())
-		.layer(cors)
+		.layer(middleware::from_fn_with_state(cors, apply_cors))
 		.layer(NormalizePathLayer::trim_trailing_slash())
 		.with_state(state)
 }
@@ -0,0 +1,67 @@
+use crate::api::types::server::CommandPreviewRequest;
+use crate::models::file_schemas::server_config::ServerConfig;
+use crate::models::jvm::LaunchCommand;
+use crate::models::server::Server;
+use crate::AppState;
+use axum::extract::State;
+use axum::response::IntoResponse;
+use axum::{routing, Extension, Json, Router};
+use reqwest::StatusCode;
+use std::sync::Arc;
+
+/// Shown in place of the server jar while the game isn't installed yet
+const BINARY_PLACEHOLDER: &str = "<server.jar>";
+
+pub fn create_router() -> Router<Arc<AppState>> {
+	Router::new().route("/", routing::get(get).post(post))
+}
+
+/// Get the command line the server is started with
+async fn get(
+	State(state): State<Arc<AppState>>,
+	Extension(server): Extension<Arc<Server>>,
+) -> impl IntoResponse {
+	let config = server.get_server_info().await.config;
+
+	resolve(&state, &config).await.into_response()
+}
+
+/// Preview the command line for unsaved JVM settings
+async fn post(
+	State(state): State<Arc<AppState>>,
+	Extension(server): Extension<Arc<Server>>,
+	Json(request): Json<CommandPreviewRequest>,
+) -> impl IntoResponse {
+	let mut config = server.get_server_info().await.config;
+
+	if let Some(args) = request.args {
+		config.args = args;
+	}
+
+	if let Some(jvm) = request.jvm {
+		config.jvm = jvm;
+	}
+
+	resolve(&state, &config).await.into_response()
+}
+
+/// Internal: Validate the JVM settings of a config and resolve its command line
+async fn resolve(state: &AppState, config: &ServerConfig) -> impl IntoResponse {
+	if let Err(err) = config.jvm.validate(&config.args, &config.limits) {
+		return (StatusCode::BAD_REQUEST, err).into_response();
+	}
+
+	let binary_path = state
+		.binary_service
+		.installed_binary(&config.game)
+		.await
+		.and_then(|path| std::fs::canonicalize(path).ok())
+		.map_or_else(
+			|| BINARY_PLACEHOLDER.to_string(),
+			|path| path.to_string_lossy().into_owned(),
+		);
+
+	let command = LaunchCommand::resolve(config, &binary_path);
+
+	(StatusCode::OK, Json(command)).into_response()
+}
@@ -1,6 +1,9 @@
 use crate::{
 	api::types::server::{ConsoleQueryParams, ServerCommandRequest},
-	models::server::{Server, ServerStateInfo},
+	models::{
+		console::ConsoleFilter,
+		server::{Server, ServerStateInfo},
+	},
 	AppState,
 };
 use axum::{
@@ -26,9 +29,12 @@ async fn get(
 	Extension(server): Extension<Arc<Server>>,
 	Query(query): Query<ConsoleQueryParams>,
 ) -> Sse<impl Stream<Item = Result<Event, String>>> {
+	let filter = Arc::new(ConsoleFilter::new(query.level, query.text.as_deref()));
+	let format = query.format.unwrap_or_default();
+
 	let stream = stream::unfold(
-		(state, server.clone(), query.since),
-		|(state, server, mut since)| async move {
+		(state, server.clone(), query.since, filter),
+		move |(state, server, mut since, filter)| async move {
 			let mut prev_server_state: Option<ServerStateInfo> = None;
 
 			loop {
@@ -50,7 +56,7 @@ async fn get(
 				prev_server_state.replace(server_info.state.clone());
 
 				// Get the next snapshot of console lines
-				let snapshot = match server.get_console_snapshot(since).await {
+				let snapshot = match server.get_console_snapshot(since, &filter).await {
 					Ok(snapshot) => snapshot,
 					Err(err) => {
 						tracing::error!(
@@ -72,6 +78,8 @@ async fn get(
 				let last_num = snapshot.last().map(|line| line.num).unwrap_or_default();
 				since = Some(last_num);
 
+				let snapshot: Vec<_> = snapshot.iter().map(|line| line.formatted(format)).collect();
+
 				// Serialize the snapshot to JSON
 				let payload = match serde_json::to_string(&snapshot) {
 					Ok(payload) => payload,
@@ -86,7 +94,7 @@ async fn get(
 							.event("error")
 							.data("Failed to serialize console snapshot");
 
-						return Some((Ok(event), (state, server, since)));
+						return Some((Ok(event), (state, server, since, filter)));
 					}
 				};
 
@@ -95,7 +103,7 @@ async fn get(
 					.id(last_num.to_string())
 					.data(payload);
 
-				return Some((Ok(event), (state, server, since)));
+				return Some((Ok(event), (state, server, since, filter)));
 			}
 		},
 	);
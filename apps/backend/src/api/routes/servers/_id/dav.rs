@@ -0,0 +1,61 @@
+use crate::api::file_response::protect_content;
+use crate::models::file_manager::dav::ServerDavFs;
+use crate::models::server::Server;
+use crate::AppState;
+use axum::body::Body;
+use axum::extract::{OriginalUri, Request};
+use axum::response::IntoResponse;
+use axum::{routing, Extension, Router};
+use dav_server::fakels::FakeLs;
+use dav_server::DavHandler;
+use reqwest::StatusCode;
+use std::sync::Arc;
+
+/// Mount point of the DAV share below a server's routes
+const MOUNT_PATH: &str = "/dav";
+
+/// Create the routes of the DAV share. They are merged instead of nested, as nested routers
+/// don't match the trailing slash clients use for the root collection.
+pub fn create_router() -> Router<Arc<AppState>> {
+	Router::new()
+		.route(MOUNT_PATH, routing::any(handle))
+		.route(&format!("{MOUNT_PATH}/"), routing::any(handle))
+		.route(&format!("{MOUNT_PATH}/{{*path}}"), routing::any(handle))
+}
+
+/// Serve the server's files to DAV clients, so they can be mounted as a network drive
+async fn handle(
+	Extension(server): Extension<Arc<Server>>,
+	OriginalUri(original_uri): OriginalUri,
+	request: Request,
+) -> impl IntoResponse {
+	// The handler needs the full path of the mount point to build the URLs in its responses.
+	// Trailing slashes may have been trimmed from the request path.
+	let prefix = original_uri
+		.path()
+		.trim_end_matches('/')
+		.strip_suffix(request.uri().path().trim_end_matches('/'))
+		.map(|server_path| format!("{server_path}{MOUNT_PATH}"));
+
+	let Some(prefix) = prefix else {
+		return StatusCode::BAD_REQUEST.into_response();
+	};
+
+	let handler = DavHandler::builder()
+		.filesystem(Box::new(ServerDavFs::new(server)))
+		// Some clients only mount shares read-write if they support locking
+		.locksystem(FakeLs::new())
+		.strip_prefix(prefix)
+		.build_handler();
+
+	let (mut parts, body) = request.into_parts();
+	parts.uri = original_uri;
+
+	let mut response = handler
+		.handle(Request::from_parts(parts, body))
+		.await
+		.map(Body::new);
+	protect_content(response.headers_mut());
+
+	response.into_response()
+}
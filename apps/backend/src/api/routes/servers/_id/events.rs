@@ -0,0 +1,25 @@
+use crate::models::server::Server;
+use crate::AppState;
+use axum::extract::State;
+use axum::response::IntoResponse;
+use axum::{routing, Extension, Json, Router};
+use reqwest::StatusCode;
+use std::sync::Arc;
+
+pub fn create_router() -> Router<Arc<AppState>> {
+	Router::new().route("/", routing::get(get))
+}
+
+/// List the most recent events recorded for the server, newest first
+async fn get(
+	State(state): State<Arc<AppState>>,
+	Extension(server): Extension<Arc<Server>>,
+) -> impl IntoResponse {
+	match state.trigger_service.list_events(server.id()).await {
+		Ok(events) => (StatusCode::OK, Json(events)).into_response(),
+		Err(err) => {
+			tracing::error!("Error listing server events: {}", err);
+			StatusCode::INTERNAL_SERVER_ERROR.into_response()
+		}
+	}
+}
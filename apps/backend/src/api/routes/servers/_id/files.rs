@@ -1,20 +1,27 @@
+use crate::api::file_response::{
+	detect_content_type, etag_header, http_date, is_not_modified, protect_content, requested_range,
+	write_preconditions_pass, ByteRange, CONTENT_SNIFF_LENGTH,
+};
+use crate::api::middleware::auth::require_sudo;
 use crate::api::types::server::{
 	FilesGetQueryParams, FilesPostQueryParams, FilesPostType, FilesPutOperation,
 	FilesPutQueryParams,
 };
-use crate::models::file_manager::types::{FSEntry, FileManagerError};
+use crate::models::file_manager::types::{FSEntry, FSFileEntry, FSListOptions, FileManagerError};
+use crate::models::file_manager::FileManager;
 use crate::models::server::Server;
 use crate::AppState;
 use axum::body::Body;
 use axum::extract::{Path, Query, Request};
-use axum::http::Response;
+use axum::http::{header, HeaderMap, Response};
 use axum::response::IntoResponse;
-use axum::{routing, Extension, Json, Router};
+use axum::{middleware, routing, Extension, Json, Router};
 use futures_util::TryStreamExt;
 use reqwest::StatusCode;
+use std::io::SeekFrom;
 use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::io::{copy, AsyncWriteExt};
+use tokio::io::{copy, AsyncReadExt, AsyncSeekExt};
 use tokio_util::io::{ReaderStream, StreamReader};
 
 pub fn create_router() -> Router<Arc<AppState>> {
@@ -26,9 +33,16 @@ pub fn create_router() -> Router<Arc<AppState>> {
 		.route("/", routing::get(get_root).put(put_root))
 }
 
-fn handle_error(error: &FileManagerError) -> impl IntoResponse {
+pub fn create_purge_router(state: &Arc<AppState>) -> Router<Arc<AppState>> {
+	// Permanent deletion can't be undone, so it requires a sudo token
+	Router::new()
+		.route("/{*path}", routing::delete(purge_delete))
+		.route_layer(middleware::from_fn_with_state(state.clone(), require_sudo))
+}
+
+pub(super) fn handle_error(error: &FileManagerError) -> impl IntoResponse {
 	match error {
-		FileManagerError::NoPermission => {
+		FileManagerError::NoPermission | FileManagerError::InvalidPattern(_) => {
 			(StatusCode::BAD_REQUEST, error.to_string()).into_response()
 		}
 		FileManagerError::UnknownType
@@ -38,19 +52,28 @@ fn handle_error(error: &FileManagerError) -> impl IntoResponse {
 			(StatusCode::INTERNAL_SERVER_ERROR, error.to_string()).into_response()
 		}
 		FileManagerError::NotFound => (StatusCode::NOT_FOUND, error.to_string()).into_response(),
+		FileManagerError::AlreadyExists => {
+			(StatusCode::CONFLICT, error.to_string()).into_response()
+		}
+		FileManagerError::ReadOnly => (StatusCode::FORBIDDEN, error.to_string()).into_response(),
+		FileManagerError::Unsupported => {
+			(StatusCode::NOT_IMPLEMENTED, error.to_string()).into_response()
+		}
+		FileManagerError::QuotaExceeded => {
+			(StatusCode::INSUFFICIENT_STORAGE, error.to_string()).into_response()
+		}
 	}
 }
 
-fn to_root_relative_path(path: &str) -> PathBuf {
+pub(super) fn to_root_relative_path(path: &str) -> PathBuf {
 	PathBuf::from(path.trim_start_matches('/'))
 }
 
 async fn post(
 	Path((_, file_path)): Path<(String, String)>,
 	Query(query): Query<FilesPostQueryParams>,
-	Extension(server): Extension<Arc<Server>>,
+	Extension(file_manager): Extension<Arc<dyn FileManager>>,
 ) -> impl IntoResponse {
-	let file_manager = server.get_fs();
 	let path_buf = PathBuf::from(file_path);
 
 	match file_manager.stat(&path_buf).await {
@@ -79,25 +102,31 @@ async fn post(
 
 async fn get_root(
 	Query(query): Query<FilesGetQueryParams>,
-	Extension(server): Extension<Arc<Server>>,
+	Extension(file_manager): Extension<Arc<dyn FileManager>>,
+	headers: HeaderMap,
 ) -> impl IntoResponse {
-	get_handler(server, "", query).await.into_response()
+	get_handler(file_manager, "", query, &headers)
+		.await
+		.into_response()
 }
 
 async fn get(
 	Path((_, file_path)): Path<(String, String)>,
 	Query(query): Query<FilesGetQueryParams>,
-	Extension(server): Extension<Arc<Server>>,
+	Extension(file_manager): Extension<Arc<dyn FileManager>>,
+	headers: HeaderMap,
 ) -> impl IntoResponse {
-	get_handler(server, &file_path, query).await.into_response()
+	get_handler(file_manager, &file_path, query, &headers)
+		.await
+		.into_response()
 }
 
 async fn get_handler(
-	server: Arc<Server>,
+	file_manager: Arc<dyn FileManager>,
 	file_path: &str,
 	query: FilesGetQueryParams,
+	headers: &HeaderMap,
 ) -> impl IntoResponse {
-	let file_manager = server.get_fs();
 	let path_buf = PathBuf::from(file_path);
 
 	let path_stat = match file_manager.stat(&path_buf).await {
@@ -107,38 +136,147 @@ async fn get_handler(
 
 	if query.content.is_some() {
 		match path_stat {
-			FSEntry::File(_) => {
-				let file_content = match file_manager.read_file(&path_buf).await {
-					Ok(file_content) => file_content,
-					Err(err) => return handle_error(&err).into_response(),
+			FSEntry::File(file) => serve_file(file_manager.as_ref(), &path_buf, &file, headers)
+				.await
+				.into_response(),
+			FSEntry::Dir(_) => {
+				let options = FSListOptions {
+					sort: query.sort.unwrap_or_default(),
+					order: query.order.unwrap_or_default(),
+					filter: query.filter,
+					cursor: query.cursor,
+					limit: query.limit,
 				};
 
-				let stream = ReaderStream::new(file_content);
-
-				Response::builder()
-					.status(StatusCode::OK)
-					.header("Content-Type", "application/octet-stream")
-					.body(Body::from_stream(stream))
-					.unwrap()
-					.into_response()
+				match file_manager.list_dir_with(&path_buf, &options).await {
+					Ok(dir_content) => (StatusCode::OK, Json(dir_content)).into_response(),
+					Err(err) => handle_error(&err).into_response(),
+				}
 			}
-			FSEntry::Dir(_) => match file_manager.list_dir(&path_buf).await {
-				Ok(dir_content) => (StatusCode::OK, Json(dir_content)).into_response(),
-				Err(err) => handle_error(&err).into_response(),
-			},
 		}
 	} else {
 		(StatusCode::OK, Json(path_stat)).into_response()
 	}
 }
 
+/// Stream a file's content, honoring conditional and range request headers
+async fn serve_file(
+	file_manager: &dyn FileManager,
+	path: &std::path::Path,
+	file: &FSFileEntry,
+	headers: &HeaderMap,
+) -> impl IntoResponse {
+	let mut builder = Response::builder()
+		.header(header::ETAG, etag_header(&file.etag))
+		.header(header::ACCEPT_RANGES, "bytes");
+
+	if let Some(last_modified) = file.modified.and_then(http_date) {
+		builder = builder.header(header::LAST_MODIFIED, last_modified);
+	}
+
+	if is_not_modified(headers, file) {
+		return builder
+			.status(StatusCode::NOT_MODIFIED)
+			.body(Body::empty())
+			.unwrap()
+			.into_response();
+	}
+
+	let (start, end) = match requested_range(headers, file) {
+		ByteRange::Full => (0, file.size.saturating_sub(1)),
+		ByteRange::Partial { start, end } => {
+			builder = builder.status(StatusCode::PARTIAL_CONTENT).header(
+				header::CONTENT_RANGE,
+				format!("bytes {start}-{end}/{}", file.size),
+			);
+			(start, end)
+		}
+		ByteRange::Unsatisfiable => {
+			return builder
+				.status(StatusCode::RANGE_NOT_SATISFIABLE)
+				.header(header::CONTENT_RANGE, format!("bytes */{}", file.size))
+				.body(Body::empty())
+				.unwrap()
+				.into_response();
+		}
+	};
+
+	let mut file_content = match file_manager.read_file(path).await {
+		Ok(file_content) => file_content,
+		Err(err) => return handle_error(&err).into_response(),
+	};
+
+	// Sniff the leading bytes for the content type, then rewind to the start of the range
+	let mut head = Vec::with_capacity(CONTENT_SNIFF_LENGTH);
+	let sniff_result = (&mut file_content)
+		.take(CONTENT_SNIFF_LENGTH as u64)
+		.read_to_end(&mut head)
+		.await;
+
+	if let Err(err) = sniff_result {
+		return handle_error(&FileManagerError::IoError(err)).into_response();
+	}
+
+	if let Err(err) = file_content.seek(SeekFrom::Start(start)).await {
+		return handle_error(&FileManagerError::IoError(err)).into_response();
+	}
+
+	let length = if file.size == 0 { 0 } else { end - start + 1 };
+	let stream = ReaderStream::new(file_content.take(length));
+
+	builder = builder
+		.header(header::CONTENT_TYPE, detect_content_type(&file.name, &head))
+		.header(header::CONTENT_LENGTH, length);
+
+	if let Some(headers) = builder.headers_mut() {
+		protect_content(headers);
+	}
+
+	builder
+		.body(Body::from_stream(stream))
+		.unwrap()
+		.into_response()
+}
+
 async fn delete(
 	Path((_, file_path)): Path<(String, String)>,
 	Extension(server): Extension<Arc<Server>>,
+	headers: HeaderMap,
+) -> impl IntoResponse {
+	let file_manager = server.get_fs();
+	let path_buf = PathBuf::from(file_path);
+
+	if let Err(response) =
+		check_delete_preconditions(file_manager.as_ref(), &path_buf, &headers).await
+	{
+		return response;
+	}
+
+	match server
+		.get_trash()
+		.put(file_manager.as_ref(), &path_buf)
+		.await
+	{
+		Ok(entry) => (StatusCode::OK, Json(entry)).into_response(),
+		Err(err) => handle_error(&err).into_response(),
+	}
+}
+
+/// Delete a file or directory permanently, bypassing the trash
+async fn purge_delete(
+	Path((_, file_path)): Path<(String, String)>,
+	Extension(server): Extension<Arc<Server>>,
+	headers: HeaderMap,
 ) -> impl IntoResponse {
 	let file_manager = server.get_fs();
 	let path_buf = PathBuf::from(file_path);
 
+	if let Err(response) =
+		check_delete_preconditions(file_manager.as_ref(), &path_buf, &headers).await
+	{
+		return response;
+	}
+
 	if let Err(err) = file_manager.delete(&path_buf).await {
 		return handle_error(&err).into_response();
 	}
@@ -146,12 +284,37 @@ async fn delete(
 	StatusCode::NO_CONTENT.into_response()
 }
 
+/// Internal: Check that the path exists and that the request's `If-Match` still holds
+async fn check_delete_preconditions(
+	file_manager: &dyn FileManager,
+	path: &std::path::Path,
+	headers: &HeaderMap,
+) -> Result<(), axum::response::Response> {
+	let path_stat = file_manager
+		.stat(path)
+		.await
+		.map_err(|err| handle_error(&err).into_response())?;
+
+	let file = match &path_stat {
+		FSEntry::File(file) => Some(file),
+		FSEntry::Dir(_) => None,
+	};
+
+	if !write_preconditions_pass(headers, file) {
+		return Err(StatusCode::PRECONDITION_FAILED.into_response());
+	}
+
+	Ok(())
+}
+
 async fn put_root(
 	Query(query): Query<FilesPutQueryParams>,
-	Extension(server): Extension<Arc<Server>>,
+	Extension(file_manager): Extension<Arc<dyn FileManager>>,
 	request: Request,
 ) -> impl IntoResponse {
-	put_handler(String::new(), query, server, request.into_body())
+	let (parts, body) = request.into_parts();
+
+	put_handler(String::new(), query, file_manager, &parts.headers, body)
 		.await
 		.into_response()
 }
@@ -159,10 +322,12 @@ async fn put_root(
 async fn put(
 	Path((_, file_path)): Path<(String, String)>,
 	Query(query): Query<FilesPutQueryParams>,
-	Extension(server): Extension<Arc<Server>>,
+	Extension(file_manager): Extension<Arc<dyn FileManager>>,
 	request: Request,
 ) -> impl IntoResponse {
-	put_handler(file_path, query, server, request.into_body())
+	let (parts, body) = request.into_parts();
+
+	put_handler(file_path, query, file_manager, &parts.headers, body)
 		.await
 		.into_response()
 }
@@ -170,10 +335,10 @@ async fn put(
 async fn put_handler(
 	file_path: String,
 	query: FilesPutQueryParams,
-	server: Arc<Server>,
+	file_manager: Arc<dyn FileManager>,
+	headers: &HeaderMap,
 	req_body: Body,
 ) -> impl IntoResponse {
-	let file_manager = server.get_fs();
 	let path_buf = PathBuf::from(file_path);
 
 	match query.operation {
@@ -239,8 +404,12 @@ async fn put_handler(
 				Err(err) => return handle_error(&err).into_response(),
 			};
 
-			if let FSEntry::Dir(_) = path_stat {
+			let FSEntry::File(file) = path_stat else {
 				return (StatusCode::BAD_REQUEST, "Cannot write to a directory").into_response();
+			};
+
+			if !write_preconditions_pass(headers, Some(&file)) {
+				return StatusCode::PRECONDITION_FAILED.into_response();
 			}
 
 			let mut file_writer = match file_manager.write_file(&path_buf).await {
@@ -253,16 +422,126 @@ async fn put_handler(
 			let mut body_reader = StreamReader::new(body_stream);
 
 			if let Err(err) = copy(&mut body_reader, &mut file_writer).await {
+				if err.kind() == std::io::ErrorKind::StorageFull {
+					return handle_error(&FileManagerError::QuotaExceeded).into_response();
+				}
+
 				tracing::error!("Error while copying body to file: {}", err);
 				return StatusCode::INTERNAL_SERVER_ERROR.into_response();
 			}
 
-			if let Err(err) = file_writer.flush().await {
-				tracing::error!("Error while flushing file writer: {}", err);
-				return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+			if let Err(err) = file_writer.commit().await {
+				return handle_error(&err).into_response();
 			}
 
-			StatusCode::OK.into_response()
+			// Return the new entity tag so clients can chain conditional writes
+			match file_manager.stat(&path_buf).await {
+				Ok(FSEntry::File(file)) => {
+					(StatusCode::OK, [(header::ETAG, etag_header(&file.etag))]).into_response()
+				}
+				Ok(FSEntry::Dir(_)) => StatusCode::OK.into_response(),
+				Err(err) => handle_error(&err).into_response(),
+			}
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::models::file_manager::memory::MemoryFileManager;
+	use axum::body::{to_bytes, Bytes};
+	use tower::ServiceExt;
+
+	/// Internal: Serve the file routes from a file manager, which `require_server` provides in
+	/// the app
+	fn router(file_manager: Arc<dyn FileManager>) -> Router {
+		Router::new()
+			.route("/servers/{id}/files", routing::get(get_root))
+			.route(
+				"/servers/{id}/files/{*path}",
+				routing::get(get).post(post).put(put),
+			)
+			.layer(Extension(file_manager))
+	}
+
+	/// Internal: Send a request and get the status and body of the response
+	async fn send(router: &Router, method: &str, uri: &str, body: &str) -> (StatusCode, Bytes) {
+		let request = axum::http::Request::builder()
+			.method(method)
+			.uri(uri)
+			.body(Body::from(body.to_string()))
+			.expect("Request should be valid");
+
+		let response = router
+			.clone()
+			.oneshot(request)
+			.await
+			.expect("Router should be infallible");
+		let status = response.status();
+		let body = to_bytes(response.into_body(), usize::MAX)
+			.await
+			.expect("Body should be readable");
+
+		(status, body)
+	}
+
+	#[tokio::test]
+	async fn creates_writes_and_lists_files() {
+		let router = router(Arc::new(MemoryFileManager::new()));
+		let base = "/servers/test/files";
+
+		let (status, _) = send(
+			&router,
+			"POST",
+			&format!("{base}/plugins?type=directory"),
+			"",
+		)
+		.await;
+		assert_eq!(status, StatusCode::CREATED);
+
+		let file = format!("{base}/server.properties");
+		let (status, _) = send(&router, "POST", &format!("{file}?type=file"), "").await;
+		assert_eq!(status, StatusCode::CREATED);
+
+		let (status, _) = send(
+			&router,
+			"PUT",
+			&format!("{file}?operation=write"),
+			"motd=Hi",
+		)
+		.await;
+		assert_eq!(status, StatusCode::OK);
+
+		let (status, body) = send(&router, "GET", &format!("{file}?content"), "").await;
+		assert_eq!(status, StatusCode::OK);
+		assert_eq!(body.as_ref(), b"motd=Hi");
+
+		let (status, body) = send(&router, "GET", &format!("{base}?content"), "").await;
+		assert_eq!(status, StatusCode::OK);
+
+		let listing: serde_json::Value =
+			serde_json::from_slice(&body).expect("Listing should be JSON");
+		let names: Vec<&str> = listing["entries"]
+			.as_array()
+			.expect("Listing should have entries")
+			.iter()
+			.filter_map(|entry| entry["name"].as_str())
+			.collect();
+
+		assert_eq!(listing["total"], 2);
+		assert_eq!(names, ["plugins", "server.properties"]);
+	}
+
+	#[tokio::test]
+	async fn rejects_creating_existing_paths() {
+		let router = router(Arc::new(MemoryFileManager::new()));
+		let uri = "/servers/test/files/world?type=directory";
+
+		let (status, _) = send(&router, "POST", uri, "").await;
+		assert_eq!(status, StatusCode::CREATED);
+
+		let (status, _) = send(&router, "POST", uri, "").await;
+		assert_eq!(status, StatusCode::BAD_REQUEST);
+	}
+}
@@ -0,0 +1,158 @@
+use super::files::{handle_error, to_root_relative_path};
+use crate::api::types::server::HistoryQueryParams;
+use crate::models::file_manager::types::{FSEntry, FileManagerError};
+use crate::models::server::Server;
+use crate::AppState;
+use axum::extract::{Path, Query};
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::{routing, Extension, Json, Router};
+use reqwest::StatusCode;
+use similar::TextDiff;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+pub fn create_router() -> Router<Arc<AppState>> {
+	Router::new()
+		.route("/", routing::get(get))
+		.route("/{version}", routing::get(get_version))
+		.route("/{version}/diff", routing::get(get_diff))
+		.route("/{version}/restore", routing::post(restore_post))
+}
+
+/// List the recorded versions of a file
+async fn get(
+	Query(query): Query<HistoryQueryParams>,
+	Extension(server): Extension<Arc<Server>>,
+) -> impl IntoResponse {
+	let path_buf = to_root_relative_path(&query.path);
+
+	if let Err(err) = server.get_path_policy().ensure_visible(&path_buf) {
+		return handle_error(&err).into_response();
+	}
+
+	match server.get_history().list(&path_buf).await {
+		Ok(versions) => (StatusCode::OK, Json(versions)).into_response(),
+		Err(err) => handle_error(&err).into_response(),
+	}
+}
+
+/// Get the content of a recorded version
+async fn get_version(
+	Path((_, version)): Path<(String, u64)>,
+	Query(query): Query<HistoryQueryParams>,
+	Extension(server): Extension<Arc<Server>>,
+) -> impl IntoResponse {
+	let path_buf = to_root_relative_path(&query.path);
+
+	if let Err(err) = server.get_path_policy().ensure_visible(&path_buf) {
+		return handle_error(&err).into_response();
+	}
+
+	match server.get_history().read_version(&path_buf, version).await {
+		Ok(content) => (
+			StatusCode::OK,
+			[(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+			content,
+		)
+			.into_response(),
+		Err(err) => handle_error(&err).into_response(),
+	}
+}
+
+/// Get a unified diff from a recorded version to the file's current content
+async fn get_diff(
+	Path((_, version)): Path<(String, u64)>,
+	Query(query): Query<HistoryQueryParams>,
+	Extension(server): Extension<Arc<Server>>,
+) -> impl IntoResponse {
+	let file_manager = server.get_fs();
+	let path_buf = to_root_relative_path(&query.path);
+
+	// Hidden paths are reported as not found, which would otherwise read as a deleted file
+	if let Err(err) = server.get_path_policy().ensure_visible(&path_buf) {
+		return handle_error(&err).into_response();
+	}
+
+	let old_content = match server.get_history().read_version(&path_buf, version).await {
+		Ok(content) => content,
+		Err(err) => return handle_error(&err).into_response(),
+	};
+
+	let new_content = match file_manager.stat(&path_buf).await {
+		Ok(FSEntry::File(_)) => {
+			let mut reader = match file_manager.read_file(&path_buf).await {
+				Ok(reader) => reader,
+				Err(err) => return handle_error(&err).into_response(),
+			};
+
+			let mut content = Vec::new();
+			if let Err(err) = reader.read_to_end(&mut content).await {
+				tracing::error!("Error while reading file for diff: {}", err);
+				return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+			}
+
+			content
+		}
+		Ok(FSEntry::Dir(_)) => {
+			return (StatusCode::BAD_REQUEST, "Path is a directory").into_response();
+		}
+		// A deleted file diffs against empty content
+		Err(FileManagerError::NotFound) => Vec::new(),
+		Err(err) => return handle_error(&err).into_response(),
+	};
+
+	let old_text = String::from_utf8_lossy(&old_content);
+	let new_text = String::from_utf8_lossy(&new_content);
+	let name = query.path.trim_start_matches('/');
+
+	let diff = TextDiff::from_lines(old_text.as_ref(), new_text.as_ref())
+		.unified_diff()
+		.header(&format!("a/{name}"), &format!("b/{name}"))
+		.to_string();
+
+	(
+		StatusCode::OK,
+		[(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+		diff,
+	)
+		.into_response()
+}
+
+/// Restore a recorded version. The current content is recorded as a new version first.
+async fn restore_post(
+	Path((_, version)): Path<(String, u64)>,
+	Query(query): Query<HistoryQueryParams>,
+	Extension(server): Extension<Arc<Server>>,
+) -> impl IntoResponse {
+	let file_manager = server.get_fs();
+	let path_buf = to_root_relative_path(&query.path);
+
+	let content = match server.get_history().read_version(&path_buf, version).await {
+		Ok(content) => content,
+		Err(err) => return handle_error(&err).into_response(),
+	};
+
+	// Recreate the file if it was deleted since the version was recorded
+	if let Err(FileManagerError::NotFound) = file_manager.stat(&path_buf).await {
+		if let Err(err) = file_manager.create_file(&path_buf).await {
+			return handle_error(&err).into_response();
+		}
+	}
+
+	let mut file_writer = match file_manager.write_file(&path_buf).await {
+		Ok(file_writer) => file_writer,
+		Err(err) => return handle_error(&err).into_response(),
+	};
+
+	if let Err(err) = file_writer.write_all(&content).await {
+		tracing::error!("Error while restoring file version: {}", err);
+		return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+	}
+
+	if let Err(err) = file_writer.commit().await {
+		return handle_error(&err).into_response();
+	}
+
+	StatusCode::OK.into_response()
+}
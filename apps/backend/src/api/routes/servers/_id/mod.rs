@@ -1,21 +1,38 @@
 use crate::{
-	api::middleware::server::require_server,
-	models::{file_schemas::server_config::PartialServerConfig, server::Server},
+	api::middleware::{auth::request_is_sudo, server::require_server},
+	db::models::user::User,
+	models::{
+		file_schemas::server_config::PartialServerConfig,
+		server::{Server, ServerError},
+	},
+	services::server::StartOutcome,
 	AppState,
 };
 use axum::{
 	extract::{Path, State},
+	http::HeaderMap,
 	middleware,
 	response::IntoResponse,
 	routing, Extension, Json, Router,
 };
 use reqwest::StatusCode;
 use std::sync::Arc;
+use tower_cookies::Cookies;
 use uuid::Uuid;
 
+mod command;
 mod console;
+mod dav;
+mod events;
 mod files;
+mod history;
+mod properties;
+mod search;
 mod status;
+mod trash;
+mod triggers;
+mod uploads;
+mod watch;
 
 pub fn create_router(state: &Arc<AppState>) -> Router<Arc<AppState>> {
 	Router::new()
@@ -24,10 +41,22 @@ pub fn create_router(state: &Arc<AppState>) -> Router<Arc<AppState>> {
 		.route("/start", routing::post(start_post))
 		.route("/stop", routing::post(stop_post))
 		.route("/kill", routing::post(kill_post))
+		.route("/eula", routing::post(eula_post))
 		.route("/config", routing::patch(config_patch))
+		.nest("/command", command::create_router())
+		.nest("/properties", properties::create_router())
 		.nest("/status", status::create_router())
 		.nest("/files", files::create_router())
+		.nest("/purge", files::create_purge_router(state))
+		.nest("/search", search::create_router())
+		.merge(dav::create_router())
+		.nest("/uploads", uploads::create_router())
+		.nest("/history", history::create_router())
+		.nest("/trash", trash::create_router(state))
+		.nest("/watch", watch::create_router())
 		.nest("/console", console::create_router())
+		.nest("/triggers", triggers::create_router())
+		.nest("/events", events::create_router())
 		.route_layer(middleware::from_fn_with_state(
 			state.clone(),
 			require_server,
@@ -35,11 +64,30 @@ pub fn create_router(state: &Arc<AppState>) -> Router<Arc<AppState>> {
 }
 
 async fn config_patch(
+	State(state): State<Arc<AppState>>,
 	Extension(server): Extension<Arc<Server>>,
+	cookies: Cookies,
+	headers: HeaderMap,
 	Json(config): Json<PartialServerConfig>,
 ) -> impl IntoResponse {
+	// Hooks and the wrapper run commands on the host, the environment is passed to both and the
+	// sandbox keeps the server away from the host, so changing them requires a sudo token
+	let is_privileged = config.hooks.is_some()
+		|| config.wrapper.is_some()
+		|| config.env.is_some()
+		|| config.sandbox.is_some();
+
+	if is_privileged && !request_is_sudo(&state, &cookies, &headers) {
+		return (
+			StatusCode::FORBIDDEN,
+			"Changing hooks, the wrapper, the environment or the sandbox requires sudo",
+		)
+			.into_response();
+	}
+
 	match server.update_config(config).await {
 		Ok(()) => StatusCode::OK.into_response(),
+		Err(ServerError::InvalidConfig(err)) => (StatusCode::BAD_REQUEST, err).into_response(),
 		Err(err) => {
 			tracing::error!("Error updating server config: {}", err);
 			StatusCode::INTERNAL_SERVER_ERROR.into_response()
@@ -51,15 +99,53 @@ async fn start_post(
 	State(state): State<Arc<AppState>>,
 	Extension(server): Extension<Arc<Server>>,
 ) -> impl IntoResponse {
-	if let Err(err) = server.start(state.binary_service.clone()).await {
-		tracing::error!("Error starting server: {}", err);
-		StatusCode::INTERNAL_SERVER_ERROR.into_response()
-	} else {
-		StatusCode::OK.into_response()
+	match state.server_service.start(&server).await {
+		Ok(StartOutcome::Started) => StatusCode::OK.into_response(),
+		Ok(StartOutcome::Queued) => StatusCode::ACCEPTED.into_response(),
+		Err(
+			err @ (ServerError::AlreadyRunning
+			| ServerError::EulaNotAccepted
+			| ServerError::PortUnavailable(_)
+			| ServerError::InsufficientMemory { .. }),
+		) => (StatusCode::CONFLICT, err.to_string()).into_response(),
+		Err(
+			err @ (ServerError::ResourceLimits(_)
+			| ServerError::Sandbox(_)
+			| ServerError::HookFailed(_)),
+		) => {
+			tracing::warn!("Error starting server: {}", err);
+			(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response()
+		}
+		Err(err) => {
+			tracing::error!("Error starting server: {}", err);
+			StatusCode::INTERNAL_SERVER_ERROR.into_response()
+		}
+	}
+}
+
+/// Accept the EULA for the server as the current user
+async fn eula_post(
+	Extension(server): Extension<Arc<Server>>,
+	Extension(user): Extension<User>,
+) -> impl IntoResponse {
+	match server.accept_eula(&user).await {
+		Ok(acceptance) => (StatusCode::OK, Json(acceptance)).into_response(),
+		Err(err) => {
+			tracing::error!("Error accepting EULA: {}", err);
+			StatusCode::INTERNAL_SERVER_ERROR.into_response()
+		}
 	}
 }
 
-async fn stop_post(Extension(server): Extension<Arc<Server>>) -> impl IntoResponse {
+async fn stop_post(
+	State(state): State<Arc<AppState>>,
+	Extension(server): Extension<Arc<Server>>,
+) -> impl IntoResponse {
+	// Stopping a server that waits for memory takes it out of the queue
+	if state.server_service.cancel_start(server.id()).await {
+		return StatusCode::OK.into_response();
+	}
+
 	match server.stop().await {
 		Ok(()) => StatusCode::OK.into_response(),
 		Err(err) => {
@@ -87,7 +173,13 @@ async fn get(Extension(server): Extension<Arc<Server>>) -> impl IntoResponse {
 
 async fn delete(State(state): State<Arc<AppState>>, Path(id): Path<Uuid>) -> impl IntoResponse {
 	match state.server_service.delete(id).await {
-		Ok(()) => StatusCode::OK.into_response(),
+		Ok(()) => {
+			if let Err(err) = state.trigger_service.delete_server(id).await {
+				tracing::warn!("Error deleting console triggers of server: {}", err);
+			}
+
+			StatusCode::OK.into_response()
+		}
 		Err(err) => {
 			tracing::error!("Error deleting server: {}", err);
 			StatusCode::INTERNAL_SERVER_ERROR.into_response()
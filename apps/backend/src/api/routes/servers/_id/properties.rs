@@ -0,0 +1,156 @@
+use super::files::handle_error;
+use crate::api::types::server::{
+	PatchServerPropertiesRequest, PatchServerPropertiesResponse, ServerPropertiesErrorResponse,
+	ServerPropertiesResponse, ServerPropertyEntry,
+};
+use crate::models::file_schemas::server_properties::ServerProperties;
+use crate::models::game::properties::{find_property, property_schema, PropertyValue};
+use crate::models::server::{Server, ServerStateInfo};
+use crate::AppState;
+use axum::response::IntoResponse;
+use axum::{routing, Extension, Json, Router};
+use reqwest::StatusCode;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+pub fn create_router() -> Router<Arc<AppState>> {
+	Router::new().route("/", routing::get(get).patch(patch))
+}
+
+/// Get the entries of `server.properties` along with the properties known for the server's game
+/// version
+async fn get(Extension(server): Extension<Arc<Server>>) -> impl IntoResponse {
+	let game = server.get_server_info().await.config.game;
+	let version = game.game_version();
+
+	let properties = match ServerProperties::load(server.get_fs().as_ref()).await {
+		Ok(properties) => properties,
+		Err(err) => return handle_error(&err).into_response(),
+	};
+
+	let entries = properties
+		.entries()
+		.map(|(key, raw)| {
+			let schema = find_property(version, key);
+			let value = schema.and_then(|schema| schema.parse(raw));
+			let valid = match (schema, &value) {
+				(Some(schema), Some(value)) => schema.validate(value).is_ok(),
+				(Some(_), None) => false,
+				(None, _) => true,
+			};
+
+			ServerPropertyEntry {
+				key: key.to_string(),
+				value: value.unwrap_or_else(|| PropertyValue::String(raw.to_string())),
+				known: schema.is_some(),
+				valid,
+			}
+		})
+		.collect();
+
+	let response = ServerPropertiesResponse {
+		properties: entries,
+		schema: property_schema(version).cloned().collect(),
+	};
+
+	(StatusCode::OK, Json(response)).into_response()
+}
+
+/// Change entries of `server.properties`. Nothing is written if any value is invalid.
+async fn patch(
+	Extension(server): Extension<Arc<Server>>,
+	Json(request): Json<PatchServerPropertiesRequest>,
+) -> impl IntoResponse {
+	let game = server.get_server_info().await.config.game;
+	let version = game.game_version();
+
+	let mut errors = BTreeMap::new();
+	let mut updates = Vec::with_capacity(request.properties.len());
+
+	for (key, value) in request.properties {
+		if key.is_empty() || key.chars().any(char::is_control) {
+			errors.insert(key, "Invalid key".to_string());
+			continue;
+		}
+
+		let raw = match (&value, find_property(version, &key)) {
+			(Some(value), Some(schema)) => match schema.validate(value) {
+				Ok(raw) => Some(raw),
+				Err(err) => {
+					errors.insert(key, err);
+					continue;
+				}
+			},
+			(Some(value), None) => Some(value.to_raw()),
+			(None, _) => None,
+		};
+
+		updates.push((key, value, raw));
+	}
+
+	if !errors.is_empty() {
+		let response = ServerPropertiesErrorResponse { errors };
+		return (StatusCode::BAD_REQUEST, Json(response)).into_response();
+	}
+
+	let file_manager = server.get_fs();
+	let mut properties = match ServerProperties::load(file_manager.as_ref()).await {
+		Ok(properties) => properties,
+		Err(err) => return handle_error(&err).into_response(),
+	};
+
+	let mut changed = Vec::new();
+
+	for (key, value, raw) in updates {
+		let is_changed = match &raw {
+			Some(raw) if properties.get(&key) != Some(raw.as_str()) => {
+				properties.set(&key, raw);
+				true
+			}
+			Some(_) => false,
+			None => properties.remove(&key),
+		};
+
+		if is_changed {
+			changed.push((key, value));
+		}
+	}
+
+	if !changed.is_empty() {
+		if let Err(err) = properties.save(file_manager.as_ref()).await {
+			return handle_error(&err).into_response();
+		}
+	}
+
+	let is_running = matches!(
+		server.get_server_state().await,
+		Ok(ServerStateInfo::Running)
+	);
+
+	let mut applied = Vec::new();
+
+	if is_running && request.apply {
+		for (key, value) in &changed {
+			let command = value.as_ref().and_then(|value| {
+				find_property(version, key).and_then(|schema| schema.live_command(value))
+			});
+
+			let Some(command) = command else {
+				continue;
+			};
+
+			match server.send_command(&command).await {
+				Ok(()) => applied.push(key.clone()),
+				Err(err) => tracing::warn!("Failed to apply property {}: {}", key, err),
+			}
+		}
+	}
+
+	let response = PatchServerPropertiesResponse {
+		restart_required: is_running && applied.len() < changed.len(),
+		changed: changed.into_iter().map(|(key, _)| key).collect(),
+		applied,
+	};
+
+	(StatusCode::OK, Json(response)).into_response()
+}
@@ -0,0 +1,38 @@
+use super::files::{handle_error, to_root_relative_path};
+use crate::api::types::server::FilesSearchQueryParams;
+use crate::config::SEARCH_MAX_RESULTS;
+use crate::models::file_manager::search::{search, SearchOptions};
+use crate::models::file_manager::FileManager;
+use crate::AppState;
+use axum::extract::Query;
+use axum::response::IntoResponse;
+use axum::{routing, Extension, Json, Router};
+use reqwest::StatusCode;
+use std::sync::Arc;
+
+pub fn create_router() -> Router<Arc<AppState>> {
+	Router::new().route("/", routing::get(get))
+}
+
+/// Search the content of the files at or below a path
+async fn get(
+	Query(query): Query<FilesSearchQueryParams>,
+	Extension(file_manager): Extension<Arc<dyn FileManager>>,
+) -> impl IntoResponse {
+	let path_buf = to_root_relative_path(query.path.as_deref().unwrap_or_default());
+
+	let options = SearchOptions {
+		query: query.query,
+		regex: query.regex.unwrap_or(false),
+		case_sensitive: query.case_sensitive.unwrap_or(false),
+		glob: query.glob,
+		max_results: query
+			.limit
+			.map_or(SEARCH_MAX_RESULTS, |limit| limit.min(SEARCH_MAX_RESULTS)),
+	};
+
+	match search(file_manager.as_ref(), &path_buf, &options).await {
+		Ok(results) => (StatusCode::OK, Json(results)).into_response(),
+		Err(err) => handle_error(&err).into_response(),
+	}
+}
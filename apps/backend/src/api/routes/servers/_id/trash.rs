@@ -0,0 +1,68 @@
+use super::files::{handle_error, to_root_relative_path};
+use crate::api::middleware::auth::require_sudo;
+use crate::api::types::server::TrashRestoreQueryParams;
+use crate::models::server::Server;
+use crate::AppState;
+use axum::extract::{Path, Query};
+use axum::middleware;
+use axum::response::IntoResponse;
+use axum::{routing, Extension, Json, Router};
+use reqwest::StatusCode;
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub fn create_router(state: &Arc<AppState>) -> Router<Arc<AppState>> {
+	// Purging can't be undone, so it requires a sudo token
+	Router::new()
+		.route("/", routing::delete(delete_all))
+		.route("/{entry_id}", routing::delete(delete))
+		.route_layer(middleware::from_fn_with_state(state.clone(), require_sudo))
+		.route("/", routing::get(get))
+		.route("/{entry_id}/restore", routing::post(restore_post))
+}
+
+/// List the entries in the trash
+async fn get(Extension(server): Extension<Arc<Server>>) -> impl IntoResponse {
+	match server.get_trash().list().await {
+		Ok(entries) => (StatusCode::OK, Json(entries)).into_response(),
+		Err(err) => handle_error(&err).into_response(),
+	}
+}
+
+/// Restore an entry to its original path, or to the path given by `to`
+async fn restore_post(
+	Path((_, entry_id)): Path<(String, Uuid)>,
+	Query(query): Query<TrashRestoreQueryParams>,
+	Extension(server): Extension<Arc<Server>>,
+) -> impl IntoResponse {
+	let file_manager = server.get_fs();
+	let to = query.to.as_deref().map(to_root_relative_path);
+
+	match server
+		.get_trash()
+		.restore(file_manager.as_ref(), entry_id, to.as_deref())
+		.await
+	{
+		Ok(entry) => (StatusCode::OK, Json(entry)).into_response(),
+		Err(err) => handle_error(&err).into_response(),
+	}
+}
+
+/// Permanently delete an entry
+async fn delete(
+	Path((_, entry_id)): Path<(String, Uuid)>,
+	Extension(server): Extension<Arc<Server>>,
+) -> impl IntoResponse {
+	match server.get_trash().purge(entry_id).await {
+		Ok(()) => StatusCode::NO_CONTENT.into_response(),
+		Err(err) => handle_error(&err).into_response(),
+	}
+}
+
+/// Permanently delete all entries
+async fn delete_all(Extension(server): Extension<Arc<Server>>) -> impl IntoResponse {
+	match server.get_trash().purge_all().await {
+		Ok(()) => StatusCode::NO_CONTENT.into_response(),
+		Err(err) => handle_error(&err).into_response(),
+	}
+}
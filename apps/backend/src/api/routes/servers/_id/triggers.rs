@@ -0,0 +1,96 @@
+use crate::api::types::server::TriggerRequest;
+use crate::models::server::Server;
+use crate::services::trigger::{TriggerServiceError, TriggerSettings};
+use crate::AppState;
+use axum::extract::{Path, State};
+use axum::response::IntoResponse;
+use axum::{routing, Extension, Json, Router};
+use reqwest::StatusCode;
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub fn create_router() -> Router<Arc<AppState>> {
+	Router::new()
+		.route("/", routing::get(get).post(post))
+		.route("/{trigger_id}", routing::put(put).delete(delete))
+}
+
+fn handle_trigger_error(error: &TriggerServiceError) -> impl IntoResponse {
+	match error {
+		TriggerServiceError::NoSuchTrigger(_) => {
+			(StatusCode::NOT_FOUND, error.to_string()).into_response()
+		}
+		TriggerServiceError::InvalidTrigger(_) => {
+			(StatusCode::BAD_REQUEST, error.to_string()).into_response()
+		}
+		TriggerServiceError::ServerError(_) => {
+			tracing::error!("{}", error);
+			StatusCode::INTERNAL_SERVER_ERROR.into_response()
+		}
+	}
+}
+
+impl From<TriggerRequest> for TriggerSettings {
+	fn from(req: TriggerRequest) -> Self {
+		Self {
+			name: req.name,
+			pattern: req.pattern,
+			stream: req.stream,
+			cooldown: req.cooldown,
+			action: req.action,
+			enabled: req.enabled,
+		}
+	}
+}
+
+/// List the console triggers of the server
+async fn get(
+	State(state): State<Arc<AppState>>,
+	Extension(server): Extension<Arc<Server>>,
+) -> impl IntoResponse {
+	match state.trigger_service.list(server.id()).await {
+		Ok(triggers) => (StatusCode::OK, Json(triggers)).into_response(),
+		Err(err) => handle_trigger_error(&err).into_response(),
+	}
+}
+
+/// Create a console trigger
+async fn post(
+	State(state): State<Arc<AppState>>,
+	Extension(server): Extension<Arc<Server>>,
+	Json(req): Json<TriggerRequest>,
+) -> impl IntoResponse {
+	match state.trigger_service.create(server.id(), req.into()).await {
+		Ok(trigger) => (StatusCode::CREATED, Json(trigger)).into_response(),
+		Err(err) => handle_trigger_error(&err).into_response(),
+	}
+}
+
+/// Replace the settings of a console trigger
+async fn put(
+	Path((_, trigger_id)): Path<(String, Uuid)>,
+	State(state): State<Arc<AppState>>,
+	Extension(server): Extension<Arc<Server>>,
+	Json(req): Json<TriggerRequest>,
+) -> impl IntoResponse {
+	match state
+		.trigger_service
+		.update(server.id(), trigger_id, req.into())
+		.await
+	{
+		Ok(trigger) => (StatusCode::OK, Json(trigger)).into_response(),
+		Err(err) => handle_trigger_error(&err).into_response(),
+	}
+}
+
+/// Delete a console trigger
+async fn delete(
+	Path((_, trigger_id)): Path<(String, Uuid)>,
+	State(state): State<Arc<AppState>>,
+	Extension(server): Extension<Arc<Server>>,
+) -> impl IntoResponse {
+	match state.trigger_service.delete(server.id(), trigger_id).await {
+		Ok(()) => StatusCode::OK.into_response(),
+		Err(err) => handle_trigger_error(&err).into_response(),
+	}
+}
@@ -0,0 +1,170 @@
+use super::files::{handle_error, to_root_relative_path};
+use crate::api::types::server::{
+	CompleteUploadRequest, CreateUploadRequest, UploadChunkQueryParams,
+};
+use crate::models::file_manager::types::{FSEntry, FileManagerError};
+use crate::models::server::Server;
+use crate::models::upload::UploadError;
+use crate::AppState;
+use axum::extract::{Path, Query, Request};
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::{routing, Extension, Json, Router};
+use futures_util::TryStreamExt;
+use reqwest::StatusCode;
+use std::sync::Arc;
+use tokio_util::io::StreamReader;
+use uuid::Uuid;
+
+pub fn create_router() -> Router<Arc<AppState>> {
+	Router::new()
+		.route("/", routing::post(post))
+		.route("/{upload_id}", routing::get(get).put(put).delete(delete))
+		.route("/{upload_id}/complete", routing::post(complete_post))
+}
+
+fn handle_upload_error(error: &UploadError) -> impl IntoResponse {
+	match error {
+		UploadError::NoSuchSession(_) => (StatusCode::NOT_FOUND, error.to_string()).into_response(),
+		UploadError::OffsetMismatch(_) => (StatusCode::CONFLICT, error.to_string()).into_response(),
+		UploadError::SizeExceeded(_) | UploadError::Incomplete { .. } => {
+			(StatusCode::BAD_REQUEST, error.to_string()).into_response()
+		}
+		UploadError::ChecksumMismatch { .. } => {
+			(StatusCode::UNPROCESSABLE_ENTITY, error.to_string()).into_response()
+		}
+		UploadError::FileManager(err) => handle_error(err).into_response(),
+		UploadError::IoError(_) | UploadError::Persist(_) => {
+			tracing::error!("{}", error.to_string());
+			(StatusCode::INTERNAL_SERVER_ERROR, error.to_string()).into_response()
+		}
+	}
+}
+
+/// Create an upload session
+async fn post(
+	Extension(server): Extension<Arc<Server>>,
+	Json(req): Json<CreateUploadRequest>,
+) -> impl IntoResponse {
+	let file_manager = server.get_fs();
+	let path_buf = to_root_relative_path(&req.path);
+
+	let replaced = match file_manager.stat(&path_buf).await {
+		Ok(FSEntry::Dir(_)) => {
+			return (StatusCode::BAD_REQUEST, "Cannot upload to a directory").into_response()
+		}
+		Ok(FSEntry::File(file)) => file.size,
+		Err(FileManagerError::NotFound) => 0,
+		Err(err) => return handle_error(&err).into_response(),
+	};
+
+	// Reject uploads that can't fit before any data is sent
+	if let Some(size) = req.size {
+		if let Err(err) = server.get_disk_usage().ensure_room(size, replaced).await {
+			return handle_error(&err).into_response();
+		}
+	}
+
+	let parent = path_buf.parent().unwrap_or(&path_buf).to_path_buf();
+
+	match file_manager.stat(&parent).await {
+		Ok(FSEntry::Dir(_)) => {}
+		Ok(FSEntry::File(_)) => {
+			return (
+				StatusCode::BAD_REQUEST,
+				"The parent of the upload path must be a directory",
+			)
+				.into_response()
+		}
+		Err(err) => return handle_error(&err).into_response(),
+	}
+
+	match server.get_uploads().create(path_buf, req.size).await {
+		Ok(info) => (StatusCode::CREATED, Json(info)).into_response(),
+		Err(err) => handle_upload_error(&err).into_response(),
+	}
+}
+
+/// Get the progress of an upload session
+async fn get(
+	Path((_, upload_id)): Path<(String, Uuid)>,
+	Extension(server): Extension<Arc<Server>>,
+) -> impl IntoResponse {
+	match server.get_uploads().get(upload_id).await {
+		Ok(info) => (StatusCode::OK, Json(info)).into_response(),
+		Err(err) => handle_upload_error(&err).into_response(),
+	}
+}
+
+/// Append a chunk to an upload session
+async fn put(
+	Path((_, upload_id)): Path<(String, Uuid)>,
+	Query(query): Query<UploadChunkQueryParams>,
+	Extension(server): Extension<Arc<Server>>,
+	request: Request,
+) -> impl IntoResponse {
+	// Uploads of unknown size are checked against the quota as their chunks arrive
+	let room = match server.get_disk_usage().available(0).await {
+		Ok(room) => room,
+		Err(err) => return handle_error(&err).into_response(),
+	};
+
+	let chunk_length = request
+		.headers()
+		.get(header::CONTENT_LENGTH)
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.parse::<u64>().ok());
+
+	// Reject chunks that can't fit before reading them. Chunks without a length are cut off
+	// once the upload no longer fits.
+	if let (Some(room), Some(chunk_length)) = (room, chunk_length) {
+		if query.offset.saturating_add(chunk_length) > room {
+			return handle_error(&FileManagerError::QuotaExceeded).into_response();
+		}
+	}
+
+	let body_stream = request
+		.into_body()
+		.into_data_stream()
+		.map_err(std::io::Error::other);
+
+	let body_reader = StreamReader::new(body_stream);
+
+	match server
+		.get_uploads()
+		.write_chunk(upload_id, query.offset, body_reader, room)
+		.await
+	{
+		Ok(info) => (StatusCode::OK, Json(info)).into_response(),
+		Err(err) => handle_upload_error(&err).into_response(),
+	}
+}
+
+/// Verify an upload session and move the file into place
+async fn complete_post(
+	Path((_, upload_id)): Path<(String, Uuid)>,
+	Extension(server): Extension<Arc<Server>>,
+	Json(req): Json<CompleteUploadRequest>,
+) -> impl IntoResponse {
+	let file_manager = server.get_fs();
+
+	match server
+		.get_uploads()
+		.finalize(upload_id, req.checksum, file_manager.as_ref())
+		.await
+	{
+		Ok(info) => (StatusCode::OK, Json(info)).into_response(),
+		Err(err) => handle_upload_error(&err).into_response(),
+	}
+}
+
+/// Cancel an upload session
+async fn delete(
+	Path((_, upload_id)): Path<(String, Uuid)>,
+	Extension(server): Extension<Arc<Server>>,
+) -> impl IntoResponse {
+	match server.get_uploads().cancel(upload_id).await {
+		Ok(()) => StatusCode::NO_CONTENT.into_response(),
+		Err(err) => handle_upload_error(&err).into_response(),
+	}
+}
@@ -0,0 +1,50 @@
+use super::files::{handle_error, to_root_relative_path};
+use crate::api::types::server::WatchQueryParams;
+use crate::models::server::Server;
+use crate::AppState;
+use axum::extract::Query;
+use axum::response::sse::{Event, KeepAlive};
+use axum::response::{IntoResponse, Sse};
+use axum::{routing, Extension, Router};
+use futures_util::stream;
+use std::convert::Infallible;
+use std::sync::Arc;
+
+pub fn create_router() -> Router<Arc<AppState>> {
+	Router::new().route("/", routing::get(get))
+}
+
+/// Stream changes below a path as server-sent events
+async fn get(
+	Extension(server): Extension<Arc<Server>>,
+	Query(query): Query<WatchQueryParams>,
+) -> impl IntoResponse {
+	let path_buf = to_root_relative_path(query.path.as_deref().unwrap_or_default());
+
+	let watch = match server.get_fs().watch(&path_buf).await {
+		Ok(watch) => watch,
+		Err(err) => return handle_error(&err).into_response(),
+	};
+
+	// Tell the client the watch is established, so it can list the directory without missing
+	// changes made in between
+	let ready = stream::once(async { Ok::<_, Infallible>(Event::default().event("ready")) });
+
+	let changes = stream::unfold(watch, |mut watch| async move {
+		loop {
+			let change = watch.next().await?;
+
+			match serde_json::to_string(&change) {
+				Ok(payload) => {
+					let event = Event::default().event("change").data(payload);
+					return Some((Ok(event), watch));
+				}
+				Err(err) => tracing::error!("Failed to serialize file change: {}", err),
+			}
+		}
+	});
+
+	Sse::new(futures_util::StreamExt::chain(ready, changes))
+		.keep_alive(KeepAlive::default())
+		.into_response()
+}
@@ -1,8 +1,10 @@
 use crate::api::types::server::CreateServerRequest;
+use crate::db::models::user::User;
+use crate::models::server::ServerError;
 use crate::AppState;
 use axum::extract::State;
 use axum::response::IntoResponse;
-use axum::{routing, Json, Router};
+use axum::{routing, Extension, Json, Router};
 use reqwest::StatusCode;
 use std::sync::Arc;
 
@@ -22,13 +24,27 @@ async fn get(State(state): State<Arc<AppState>>) -> impl IntoResponse {
 
 async fn post(
 	State(state): State<Arc<AppState>>,
+	Extension(user): Extension<User>,
 	Json(req): Json<CreateServerRequest>,
 ) -> impl IntoResponse {
-	let result = state.server_service.create(&req.name, req.game).await;
+	let server_id = match state.server_service.create(&req.name, req.game).await {
+		Ok(server_id) => server_id,
+		Err(err) => {
+			tracing::error!("Error creating server: {}", err);
+			return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+		}
+	};
 
-	if let Err(err) = result {
-		tracing::error!("Error creating server: {}", err);
-		return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+	if req.accept_eula {
+		let result = match state.server_service.get_server(server_id).await {
+			Ok(server) => server.accept_eula(&user).await.map(|_| ()),
+			Err(err) => Err(ServerError::NoSuchServer(err.to_string())),
+		};
+
+		// The server exists at this point, the EULA can still be accepted later
+		if let Err(err) = result {
+			tracing::error!("Error accepting EULA for new server: {}", err);
+		}
 	}
 
 	StatusCode::CREATED.into_response()
@@ -0,0 +1,20 @@
+use std::sync::Arc;
+
+use axum::{extract::State, response::IntoResponse, routing, Json, Router};
+use reqwest::StatusCode;
+
+use crate::api::types::system::SystemResponse;
+use crate::AppState;
+
+pub fn create_router() -> Router<Arc<AppState>> {
+	Router::new().route("/", routing::get(get))
+}
+
+/// Get the host's resources and how much of them the servers are expected to use
+async fn get(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+	let response = SystemResponse {
+		memory: state.server_service.memory_usage().await,
+	};
+
+	(StatusCode::OK, Json(response)).into_response()
+}
@@ -1,4 +1,5 @@
 pub mod auth;
 pub mod server;
+pub mod system;
 pub mod user;
 pub mod versions;
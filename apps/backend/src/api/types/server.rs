@@ -1,8 +1,20 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use ts_rs::TS;
 use uuid::Uuid;
 
-use crate::models::game::Game;
+use crate::db::models::console_trigger::{TriggerAction, TriggerStream};
+use crate::models::{
+	console::LogLevel,
+	console_style::ConsoleFormat,
+	file_manager::types::{FSSortField, FSSortOrder},
+	file_schemas::server_config::JvmSettings,
+	game::{
+		properties::{PropertySchema, PropertyValue},
+		Game,
+	},
+	upload::UploadChecksum,
+};
 
 #[derive(TS, Debug, Clone, Serialize, Deserialize)]
 #[ts(export)]
@@ -16,6 +28,9 @@ pub struct PartialServer {
 pub struct CreateServerRequest {
 	pub name: String,
 	pub game: Game,
+	/// Accept the EULA as the current user, so the server can be started right away
+	#[serde(default)]
+	pub accept_eula: bool,
 }
 
 #[derive(TS, Debug, Clone, Serialize, Deserialize)]
@@ -28,12 +43,24 @@ pub struct ServerCommandRequest {
 #[ts(export)]
 pub struct ConsoleQueryParams {
 	pub since: Option<u64>,
+	/// Only return log lines of at least this level
+	pub level: Option<LogLevel>,
+	/// Only return lines containing this text, ignoring case
+	pub text: Option<String>,
+	/// Representations of the lines to return, defaults to raw
+	pub format: Option<ConsoleFormat>,
 }
 
 #[derive(TS, Debug, Clone, Serialize, Deserialize)]
 #[ts(export)]
 pub struct FilesGetQueryParams {
 	pub content: Option<String>,
+	pub sort: Option<FSSortField>,
+	pub order: Option<FSSortOrder>,
+	/// Glob that entry names must match when listing a directory
+	pub filter: Option<String>,
+	pub cursor: Option<usize>,
+	pub limit: Option<usize>,
 }
 
 #[derive(TS, Debug, Clone, Serialize, Deserialize)]
@@ -51,6 +78,21 @@ pub struct FilesPostQueryParams {
 	pub entry_type: FilesPostType,
 }
 
+#[derive(TS, Debug, Clone, Serialize, Deserialize)]
+#[ts(export)]
+pub struct FilesSearchQueryParams {
+	pub query: String,
+	/// Directory or file to search, defaults to the server root
+	pub path: Option<String>,
+	/// Only search files whose path matches this glob, e.g. `plugins/**/*.yml`
+	pub glob: Option<String>,
+	/// Treat the query as a regular expression
+	pub regex: Option<bool>,
+	pub case_sensitive: Option<bool>,
+	/// Maximum number of matches to return
+	pub limit: Option<usize>,
+}
+
 #[derive(TS, Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[ts(export)]
@@ -66,3 +108,125 @@ pub struct FilesPutQueryParams {
 	pub operation: FilesPutOperation,
 	pub to: Option<String>,
 }
+
+#[derive(TS, Debug, Clone, Serialize, Deserialize)]
+#[ts(export)]
+pub struct CreateUploadRequest {
+	/// Destination path, relative to the server's root
+	pub path: String,
+	/// Expected total size in bytes
+	pub size: Option<u64>,
+}
+
+#[derive(TS, Debug, Clone, Serialize, Deserialize)]
+#[ts(export)]
+pub struct UploadChunkQueryParams {
+	pub offset: u64,
+}
+
+#[derive(TS, Debug, Clone, Serialize, Deserialize)]
+#[ts(export)]
+pub struct CompleteUploadRequest {
+	pub checksum: Option<UploadChecksum>,
+}
+
+#[derive(TS, Debug, Clone, Serialize, Deserialize)]
+#[ts(export)]
+pub struct HistoryQueryParams {
+	/// Path of the file, relative to the server's root
+	pub path: String,
+}
+
+#[derive(TS, Debug, Clone, Serialize, Deserialize)]
+#[ts(export)]
+pub struct TrashRestoreQueryParams {
+	/// Path to restore to instead of the original path
+	pub to: Option<String>,
+}
+
+#[derive(TS, Debug, Clone, Serialize, Deserialize)]
+#[ts(export)]
+pub struct WatchQueryParams {
+	/// Path to watch, defaults to the server root
+	pub path: Option<String>,
+}
+
+#[derive(TS, Debug, Clone, Serialize, Deserialize)]
+#[ts(export)]
+pub struct ServerPropertyEntry {
+	pub key: String,
+	/// Typed value for known properties. Unknown properties and values that don't match the
+	/// property's type are strings.
+	pub value: PropertyValue,
+	/// The property is known for the server's game version
+	pub known: bool,
+	/// The value is accepted by the property's schema. Always true for unknown properties.
+	pub valid: bool,
+}
+
+#[derive(TS, Debug, Clone, Serialize, Deserialize)]
+#[ts(export)]
+pub struct ServerPropertiesResponse {
+	/// Entries of `server.properties` in file order
+	pub properties: Vec<ServerPropertyEntry>,
+	/// Properties known for the server's game version
+	pub schema: Vec<PropertySchema>,
+}
+
+#[derive(TS, Debug, Clone, Serialize, Deserialize)]
+#[ts(export)]
+pub struct PatchServerPropertiesRequest {
+	/// New values by key. Null removes a property.
+	pub properties: BTreeMap<String, Option<PropertyValue>>,
+	/// Apply changes to a running server with console commands where possible
+	#[serde(default)]
+	pub apply: bool,
+}
+
+#[derive(TS, Debug, Clone, Serialize, Deserialize)]
+#[ts(export)]
+pub struct PatchServerPropertiesResponse {
+	/// Keys whose value changed
+	pub changed: Vec<String>,
+	/// Keys that were applied to the running server
+	pub applied: Vec<String>,
+	/// The server is running and some changes only take effect after a restart
+	pub restart_required: bool,
+}
+
+#[derive(TS, Debug, Clone, Serialize, Deserialize)]
+#[ts(export)]
+pub struct ServerPropertiesErrorResponse {
+	/// Validation error by key
+	pub errors: BTreeMap<String, String>,
+}
+
+/// Unsaved JVM settings to resolve the command line for. Unset fields use the server's config.
+#[derive(TS, Debug, Clone, Serialize, Deserialize)]
+#[ts(export)]
+pub struct CommandPreviewRequest {
+	pub args: Option<Vec<String>>,
+	pub jvm: Option<JvmSettings>,
+}
+
+/// Settings of a console trigger to create or replace
+#[derive(TS, Debug, Clone, Serialize, Deserialize)]
+#[ts(export)]
+pub struct TriggerRequest {
+	pub name: String,
+	/// Regular expression searched for in the plain text of each line
+	pub pattern: String,
+	#[serde(default)]
+	pub stream: TriggerStream,
+	/// Seconds after firing during which the trigger doesn't fire again
+	#[serde(default)]
+	pub cooldown: u32,
+	pub action: TriggerAction,
+	#[serde(default = "default_true")]
+	pub enabled: bool,
+}
+
+/// Internal: Serde default for flags that are on unless set
+fn default_true() -> bool {
+	true
+}
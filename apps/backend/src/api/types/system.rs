@@ -0,0 +1,10 @@
+use crate::models::memory::MemoryUsage;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+#[derive(TS, Clone, Serialize, Deserialize)]
+#[ts(export)]
+pub struct SystemResponse {
+	/// Missing if the host's memory can't be read
+	pub memory: Option<MemoryUsage>,
+}
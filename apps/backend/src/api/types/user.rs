@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 use uuid::Uuid;
 
-use crate::db::models::user::User;
+use crate::db::models::{app_password::AppPassword, user::User};
 
 #[derive(TS, Debug, Clone, Serialize, Deserialize)]
 #[ts(export)]
@@ -30,3 +30,27 @@ impl From<User> for UserResponse {
 		}
 	}
 }
+
+#[derive(TS, Debug, Clone, Deserialize)]
+#[ts(export)]
+pub struct SshKeyCreateRequest {
+	pub name: String,
+	/// The key in OpenSSH format, as in `~/.ssh/id_ed25519.pub`
+	pub public_key: String,
+}
+
+#[derive(TS, Debug, Clone, Deserialize)]
+#[ts(export)]
+pub struct AppPasswordCreateRequest {
+	pub name: String,
+}
+
+/// A new app password, with the password itself. It can't be retrieved later.
+#[derive(TS, Serialize)]
+#[ts(export)]
+pub struct AppPasswordCreateResponse {
+	#[serde(flatten)]
+	#[ts(flatten)]
+	pub app_password: AppPassword,
+	pub password: String,
+}
@@ -21,6 +21,104 @@ pub static CLIENT_USER_AGENT: &str = "ScaffoldMC/0.0.0 (https://github.com/Scaff
 // Server runtime
 pub static SERVER_WATCHER_TICK: TokioDuration = TokioDuration::from_millis(200);
 pub static SERVER_CONSOLE_MAX_LINES: usize = 500;
+/// Bytes of a console line that are kept, the rest is replaced with a truncation marker
+pub static SERVER_CONSOLE_MAX_LINE_LENGTH: usize = 16 * 1024;
+/// How long console output written before a server exits is still read
+pub static SERVER_CONSOLE_DRAIN_TIMEOUT: TokioDuration = TokioDuration::from_secs(1);
+/// Lines buffered for console subscribers that fall behind
+pub static SERVER_CONSOLE_BROADCAST_CAPACITY: usize = 1024;
+
+// Lifecycle hooks
+pub static HOOK_DEFAULT_TIMEOUT: TokioDuration = TokioDuration::from_mins(5);
+
+// Console triggers
+/// Compiled size limit of trigger patterns, in bytes
+pub static TRIGGER_REGEX_SIZE_LIMIT: usize = 1024 * 1024;
+/// How often a restarting trigger checks whether the server has stopped
+pub static TRIGGER_RESTART_POLL: TokioDuration = TokioDuration::from_millis(100);
+pub static TRIGGER_NOTIFY_TIMEOUT: TokioDuration = TokioDuration::from_secs(10);
+/// Events returned when listing a server's event log
+pub static SERVER_EVENTS_LIMIT: u32 = 200;
+
+// Ports
+/// Range new servers are assigned game ports from
+pub static SERVER_PORT_RANGE: (u16, u16) = (25565, 25664);
+
+// Memory admission
+/// Memory servers may be configured to use in total, as a percentage of the host's memory
+pub static MEMORY_OVERCOMMIT_PERCENT: u64 = 100;
+/// Queue starts that would overcommit until enough memory is free, instead of rejecting them
+pub static MEMORY_QUEUE_STARTS: bool = false;
+pub static MEMORY_QUEUE_POLL: TokioDuration = TokioDuration::from_secs(2);
+
+// Resource limits
+pub static CGROUP_MOUNT_PATH: &str = "/sys/fs/cgroup";
+/// Leaf the panel moves itself into, as cgroups with children can't hold processes
+pub static CGROUP_PANEL_LEAF: &str = "panel";
+/// Parent of the per-server cgroups below the panel's delegated cgroup
+pub static CGROUP_SERVERS_NAME: &str = "servers";
+
+// Sandbox
+pub static SANDBOX_BWRAP_BINARY: &str = "bwrap";
+/// `PATH` of sandboxed servers and shell hooks, which don't get the panel's environment
+pub static SANDBOX_PATH: &str = "/usr/local/bin:/usr/bin:/bin";
+/// System paths sandboxed servers can read, skipped if they don't exist
+pub static SANDBOX_SYSTEM_PATHS: &[&str] = &[
+	"/usr",
+	"/bin",
+	"/lib",
+	"/lib64",
+	"/etc/alternatives",
+	"/etc/ssl",
+	"/etc/pki",
+	"/etc/ca-certificates",
+	"/etc/resolv.conf",
+	"/etc/hosts",
+	"/etc/nsswitch.conf",
+	"/etc/localtime",
+	"/etc/passwd",
+	"/etc/group",
+];
+
+// File history
+pub static FILE_HISTORY_MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+// Disk usage
+/// How often disk usage is recalculated to pick up changes made by the server process
+pub static DISK_USAGE_REFRESH_INTERVAL: TokioDuration = TokioDuration::from_mins(1);
+
+// File watching
+pub static FS_WATCH_DEBOUNCE: TokioDuration = TokioDuration::from_millis(250);
+pub static FS_WATCH_BUFFER: usize = 1024;
+
+// File search
+pub static SEARCH_MAX_FILE_SIZE: u64 = 16 * 1024 * 1024;
+pub static SEARCH_MAX_LINE_LENGTH: usize = 1000;
+pub static SEARCH_MAX_RESULTS: usize = 1000;
+pub static SEARCH_SNIFF_LENGTH: usize = 1024;
+pub static SEARCH_TIMEOUT: TokioDuration = TokioDuration::from_secs(10);
+
+// File trash
+pub static TRASH_RETENTION: Duration = Duration::days(7);
+/// How often expired trash entries are looked for, besides when the trash is used
+pub static TRASH_PURGE_INTERVAL: TokioDuration = TokioDuration::from_hours(1);
+
+// File uploads
+pub static UPLOAD_SESSION_EXPIRY: Duration = Duration::hours(24);
+
+// SFTP
+/// Port of the embedded SSH server that serves the files of all servers over SFTP
+pub static SFTP_PORT: u16 = 2022;
+/// Environment variable overriding `SFTP_PORT`, set to 0 to disable SFTP
+pub static SFTP_PORT_ENV: &str = "SCAFFOLD_SFTP_PORT";
+pub static SFTP_MAX_READ_LENGTH: usize = 256 * 1024;
+pub static SSH_SERVER_VERSION: &str = "SSH-2.0-ScaffoldMC_0.0.0";
+/// Time a client has to exchange keys and authenticate
+pub static SSH_LOGIN_TIMEOUT: TokioDuration = TokioDuration::from_mins(1);
+pub static SSH_MAX_AUTH_ATTEMPTS: u32 = 6;
+pub static SSH_MAX_PACKET_LENGTH: usize = 256 * 1024;
+/// Bytes a client may send on a channel before the server has processed them
+pub static SSH_CHANNEL_WINDOW: u32 = 2 * 1024 * 1024;
 
 // APIs
 pub static FABRIC_API_URL: &str = "https://meta.fabricmc.net/v2";
@@ -30,6 +128,11 @@ pub static MOJANG_API_URL: &str = "https://piston-meta.mojang.com";
 // Generated variables
 
 pub static SERVERS_DIRECTORY: LazyLock<String> = LazyLock::new(|| format!("{DATA_FOLDER}/servers"));
+pub static SERVER_META_DIRECTORY: LazyLock<String> =
+	LazyLock::new(|| format!("{DATA_FOLDER}/server_meta"));
+/// Directory `sync_template` hooks copy templates from, one subdirectory per template
+pub static TEMPLATES_DIRECTORY: LazyLock<String> =
+	LazyLock::new(|| format!("{DATA_FOLDER}/templates"));
 
 // Helper functions
 
@@ -37,3 +140,9 @@ pub static SERVERS_DIRECTORY: LazyLock<String> = LazyLock::new(|| format!("{DATA
 pub fn server_dir(server_id: Uuid) -> PathBuf {
 	format!("{}/{}", SERVERS_DIRECTORY.clone(), server_id).into()
 }
+
+/// Get the directory for panel-managed data of a server. This lives outside of the server's
+/// directory so it is never exposed through the server's file manager.
+pub fn server_meta_dir(server_id: Uuid) -> PathBuf {
+	format!("{}/{}", SERVER_META_DIRECTORY.clone(), server_id).into()
+}
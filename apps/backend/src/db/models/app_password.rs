@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::prelude::FromRow;
+use ts_rs::TS;
+use uuid::Uuid;
+
+/// A password a user created for a client that can't log in interactively, such as a network
+/// drive client. It is sent with HTTP Basic authentication and can be revoked on its own.
+#[derive(TS, Debug, Clone, Serialize, Deserialize, FromRow)]
+#[ts(export)]
+pub struct AppPassword {
+	pub id: Uuid,
+	pub user_id: Uuid,
+	pub name: String,
+	/// SHA-256 hash of the password. Passwords are random, so a slow hash isn't needed.
+	#[serde(skip)]
+	#[ts(skip)]
+	pub password_hash: String,
+	/// Creation time as a Unix timestamp in seconds
+	pub created_at: i64,
+}
+
+impl AppPassword {
+	/// Hash a password for storage and lookup
+	pub fn hash(password: &str) -> String {
+		hex::encode(Sha256::digest(password.as_bytes()))
+	}
+}
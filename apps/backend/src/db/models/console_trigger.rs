@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use sqlx::types::Json;
+use ts_rs::TS;
+use uuid::Uuid;
+
+/// Console streams a trigger matches lines of
+#[derive(TS, Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[ts(export)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum TriggerStream {
+	#[default]
+	Any,
+	Stdout,
+	Stderr,
+}
+
+/// What a trigger does when a line matches. Texts can refer to captures of the pattern, e.g.
+/// `$1` or `${name}`.
+#[derive(TS, Debug, Clone, Serialize, Deserialize)]
+#[ts(export)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TriggerAction {
+	/// Send a command to the server
+	SendCommand { command: String },
+	/// Post the line and a message to a webhook
+	Notify { url: String, message: String },
+	/// Mark the server as crashed and start it again
+	Restart,
+	/// Record an event in the server's event log
+	RecordEvent { message: String },
+}
+
+/// A rule reacting to lines of a server's console output
+#[derive(TS, Debug, Clone, Serialize, Deserialize, FromRow)]
+#[ts(export)]
+pub struct ConsoleTrigger {
+	pub id: Uuid,
+	pub server_id: Uuid,
+	pub name: String,
+	/// Regular expression searched for in the plain text of each line, without escape sequences
+	/// and formatting codes
+	pub pattern: String,
+	pub stream: TriggerStream,
+	/// Seconds after firing during which the trigger doesn't fire again
+	pub cooldown: u32,
+	#[ts(as = "TriggerAction")]
+	pub action: Json<TriggerAction>,
+	pub enabled: bool,
+	/// Creation time as a Unix timestamp in seconds
+	pub created_at: i64,
+}
+
+/// An entry of a server's event log
+#[derive(TS, Debug, Clone, Serialize, Deserialize, FromRow)]
+#[ts(export)]
+pub struct ServerEvent {
+	pub id: Uuid,
+	pub server_id: Uuid,
+	/// Trigger that recorded the event
+	pub trigger_id: Option<Uuid>,
+	pub message: String,
+	/// Time of the event as a Unix timestamp in seconds
+	pub created_at: i64,
+}
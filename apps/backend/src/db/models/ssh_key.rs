@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+use ts_rs::TS;
+use uuid::Uuid;
+
+/// A public key a user registered to log in over SFTP
+#[derive(TS, Debug, Clone, Serialize, Deserialize, FromRow)]
+#[ts(export)]
+pub struct SshKey {
+	pub id: Uuid,
+	pub user_id: Uuid,
+	pub name: String,
+	/// The key in OpenSSH format, e.g. `ssh-ed25519 AAAA...`
+	pub public_key: String,
+	/// SHA-256 fingerprint of the key, as shown by `ssh-keygen -l`
+	pub fingerprint: String,
+	/// Creation time as a Unix timestamp in seconds
+	pub created_at: i64,
+}
@@ -0,0 +1,88 @@
+use crate::db::models::app_password::AppPassword;
+use async_trait::async_trait;
+use sqlx::types::Uuid;
+
+/// Repository structure for managing the app passwords of users.
+#[async_trait]
+pub trait AppPasswordRepository: Send + Sync {
+	async fn list_user_passwords(&self, user_id: Uuid) -> Result<Vec<AppPassword>, sqlx::Error>;
+	async fn get_password_by_hash(
+		&self,
+		password_hash: &str,
+	) -> Result<Option<AppPassword>, sqlx::Error>;
+	async fn create_password(&self, password: &AppPassword) -> Result<(), sqlx::Error>;
+	/// Delete a password of a user, returning whether it existed
+	async fn delete_user_password(
+		&self,
+		user_id: Uuid,
+		password_id: Uuid,
+	) -> Result<bool, sqlx::Error>;
+}
+
+/// Sqlx implementation of the `AppPasswordRepository` trait.
+pub struct SqlxAppPasswordRepository {
+	pool: sqlx::SqlitePool,
+}
+
+impl SqlxAppPasswordRepository {
+	pub fn new(pool: sqlx::SqlitePool) -> Self {
+		Self { pool }
+	}
+}
+
+#[async_trait]
+impl AppPasswordRepository for SqlxAppPasswordRepository {
+	async fn list_user_passwords(&self, user_id: Uuid) -> Result<Vec<AppPassword>, sqlx::Error> {
+		sqlx::query_as!(
+			AppPassword,
+			r#"SELECT id as "id: uuid::Uuid", user_id as "user_id: uuid::Uuid", name, password_hash, created_at FROM app_passwords WHERE user_id = ? ORDER BY pk"#,
+			user_id
+		)
+		.fetch_all(&self.pool)
+		.await
+	}
+
+	async fn get_password_by_hash(
+		&self,
+		password_hash: &str,
+	) -> Result<Option<AppPassword>, sqlx::Error> {
+		sqlx::query_as!(
+			AppPassword,
+			r#"SELECT id as "id: uuid::Uuid", user_id as "user_id: uuid::Uuid", name, password_hash, created_at FROM app_passwords WHERE password_hash = ?"#,
+			password_hash
+		)
+		.fetch_optional(&self.pool)
+		.await
+	}
+
+	async fn create_password(&self, password: &AppPassword) -> Result<(), sqlx::Error> {
+		sqlx::query!(
+			r#"INSERT INTO app_passwords (id, user_id, name, password_hash, created_at) VALUES (?, ?, ?, ?, ?)"#,
+			password.id,
+			password.user_id,
+			password.name,
+			password.password_hash,
+			password.created_at
+		)
+		.execute(&self.pool)
+		.await?;
+
+		Ok(())
+	}
+
+	async fn delete_user_password(
+		&self,
+		user_id: Uuid,
+		password_id: Uuid,
+	) -> Result<bool, sqlx::Error> {
+		let result = sqlx::query!(
+			r#"DELETE FROM app_passwords WHERE user_id = ? AND id = ?"#,
+			user_id,
+			password_id
+		)
+		.execute(&self.pool)
+		.await?;
+
+		Ok(result.rows_affected() > 0)
+	}
+}
@@ -0,0 +1,153 @@
+use crate::db::models::console_trigger::{
+	ConsoleTrigger, ServerEvent, TriggerAction, TriggerStream,
+};
+use async_trait::async_trait;
+use sqlx::types::{Json, Uuid};
+
+/// Repository structure for managing console triggers and the events they record.
+#[async_trait]
+pub trait TriggerRepository: Send + Sync {
+	async fn list_triggers(&self) -> Result<Vec<ConsoleTrigger>, sqlx::Error>;
+	async fn list_server_triggers(
+		&self,
+		server_id: Uuid,
+	) -> Result<Vec<ConsoleTrigger>, sqlx::Error>;
+	async fn create_trigger(&self, trigger: &ConsoleTrigger) -> Result<(), sqlx::Error>;
+	async fn update_trigger(&self, trigger: &ConsoleTrigger) -> Result<(), sqlx::Error>;
+	async fn delete_trigger(&self, trigger_id: Uuid) -> Result<(), sqlx::Error>;
+	async fn delete_server_triggers(&self, server_id: Uuid) -> Result<(), sqlx::Error>;
+	async fn add_event(&self, event: &ServerEvent) -> Result<(), sqlx::Error>;
+	async fn list_events(
+		&self,
+		server_id: Uuid,
+		limit: u32,
+	) -> Result<Vec<ServerEvent>, sqlx::Error>;
+}
+
+/// Sqlx implementation of the `TriggerRepository` trait.
+pub struct SqlxTriggerRepository {
+	pool: sqlx::SqlitePool,
+}
+
+impl SqlxTriggerRepository {
+	pub fn new(pool: sqlx::SqlitePool) -> Self {
+		Self { pool }
+	}
+}
+
+#[async_trait]
+impl TriggerRepository for SqlxTriggerRepository {
+	async fn list_triggers(&self) -> Result<Vec<ConsoleTrigger>, sqlx::Error> {
+		sqlx::query_as!(
+			ConsoleTrigger,
+			r#"SELECT id as "id: uuid::Uuid", server_id as "server_id: uuid::Uuid", name, pattern, stream as "stream: TriggerStream", cooldown as "cooldown: u32", action as "action: Json<TriggerAction>", enabled as "enabled: bool", created_at FROM console_triggers ORDER BY pk"#
+		)
+		.fetch_all(&self.pool)
+		.await
+	}
+
+	async fn list_server_triggers(
+		&self,
+		server_id: Uuid,
+	) -> Result<Vec<ConsoleTrigger>, sqlx::Error> {
+		sqlx::query_as!(
+			ConsoleTrigger,
+			r#"SELECT id as "id: uuid::Uuid", server_id as "server_id: uuid::Uuid", name, pattern, stream as "stream: TriggerStream", cooldown as "cooldown: u32", action as "action: Json<TriggerAction>", enabled as "enabled: bool", created_at FROM console_triggers WHERE server_id = ? ORDER BY pk"#,
+			server_id
+		)
+		.fetch_all(&self.pool)
+		.await
+	}
+
+	async fn create_trigger(&self, trigger: &ConsoleTrigger) -> Result<(), sqlx::Error> {
+		sqlx::query!(
+			r#"INSERT INTO console_triggers (id, server_id, name, pattern, stream, cooldown, action, enabled, created_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+			trigger.id,
+			trigger.server_id,
+			trigger.name,
+			trigger.pattern,
+			trigger.stream,
+			trigger.cooldown,
+			trigger.action,
+			trigger.enabled,
+			trigger.created_at
+		)
+		.execute(&self.pool)
+		.await?;
+
+		Ok(())
+	}
+
+	async fn update_trigger(&self, trigger: &ConsoleTrigger) -> Result<(), sqlx::Error> {
+		sqlx::query!(
+			r#"UPDATE console_triggers SET name = ?, pattern = ?, stream = ?, cooldown = ?, action = ?, enabled = ? WHERE id = ?"#,
+			trigger.name,
+			trigger.pattern,
+			trigger.stream,
+			trigger.cooldown,
+			trigger.action,
+			trigger.enabled,
+			trigger.id
+		)
+		.execute(&self.pool)
+		.await?;
+
+		Ok(())
+	}
+
+	async fn delete_trigger(&self, trigger_id: Uuid) -> Result<(), sqlx::Error> {
+		sqlx::query!(r#"DELETE FROM console_triggers WHERE id = ?"#, trigger_id)
+			.execute(&self.pool)
+			.await?;
+
+		Ok(())
+	}
+
+	async fn delete_server_triggers(&self, server_id: Uuid) -> Result<(), sqlx::Error> {
+		sqlx::query!(
+			r#"DELETE FROM console_triggers WHERE server_id = ?"#,
+			server_id
+		)
+		.execute(&self.pool)
+		.await?;
+
+		sqlx::query!(
+			r#"DELETE FROM server_events WHERE server_id = ?"#,
+			server_id
+		)
+		.execute(&self.pool)
+		.await?;
+
+		Ok(())
+	}
+
+	async fn add_event(&self, event: &ServerEvent) -> Result<(), sqlx::Error> {
+		sqlx::query!(
+			r#"INSERT INTO server_events (id, server_id, trigger_id, message, created_at) VALUES (?, ?, ?, ?, ?)"#,
+			event.id,
+			event.server_id,
+			event.trigger_id,
+			event.message,
+			event.created_at
+		)
+		.execute(&self.pool)
+		.await?;
+
+		Ok(())
+	}
+
+	async fn list_events(
+		&self,
+		server_id: Uuid,
+		limit: u32,
+	) -> Result<Vec<ServerEvent>, sqlx::Error> {
+		sqlx::query_as!(
+			ServerEvent,
+			r#"SELECT id as "id: uuid::Uuid", server_id as "server_id: uuid::Uuid", trigger_id as "trigger_id: uuid::Uuid", message, created_at FROM server_events WHERE server_id = ? ORDER BY created_at DESC, pk DESC LIMIT ?"#,
+			server_id,
+			limit
+		)
+		.fetch_all(&self.pool)
+		.await
+	}
+}
@@ -1,2 +1,5 @@
+pub mod app_password;
+pub mod console_trigger;
 pub mod refresh_token;
+pub mod ssh_key;
 pub mod user;
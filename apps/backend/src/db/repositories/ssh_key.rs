@@ -0,0 +1,84 @@
+use crate::db::models::ssh_key::SshKey;
+use async_trait::async_trait;
+use sqlx::types::Uuid;
+
+/// Repository structure for managing the SSH keys of users.
+#[async_trait]
+pub trait SshKeyRepository: Send + Sync {
+	async fn list_user_keys(&self, user_id: Uuid) -> Result<Vec<SshKey>, sqlx::Error>;
+	async fn get_user_key_by_fingerprint(
+		&self,
+		user_id: Uuid,
+		fingerprint: &str,
+	) -> Result<Option<SshKey>, sqlx::Error>;
+	async fn create_key(&self, key: &SshKey) -> Result<(), sqlx::Error>;
+	/// Delete a key of a user, returning whether it existed
+	async fn delete_user_key(&self, user_id: Uuid, key_id: Uuid) -> Result<bool, sqlx::Error>;
+}
+
+/// Sqlx implementation of the `SshKeyRepository` trait.
+pub struct SqlxSshKeyRepository {
+	pool: sqlx::SqlitePool,
+}
+
+impl SqlxSshKeyRepository {
+	pub fn new(pool: sqlx::SqlitePool) -> Self {
+		Self { pool }
+	}
+}
+
+#[async_trait]
+impl SshKeyRepository for SqlxSshKeyRepository {
+	async fn list_user_keys(&self, user_id: Uuid) -> Result<Vec<SshKey>, sqlx::Error> {
+		sqlx::query_as!(
+			SshKey,
+			r#"SELECT id as "id: uuid::Uuid", user_id as "user_id: uuid::Uuid", name, public_key, fingerprint, created_at FROM ssh_keys WHERE user_id = ? ORDER BY pk"#,
+			user_id
+		)
+		.fetch_all(&self.pool)
+		.await
+	}
+
+	async fn get_user_key_by_fingerprint(
+		&self,
+		user_id: Uuid,
+		fingerprint: &str,
+	) -> Result<Option<SshKey>, sqlx::Error> {
+		sqlx::query_as!(
+			SshKey,
+			r#"SELECT id as "id: uuid::Uuid", user_id as "user_id: uuid::Uuid", name, public_key, fingerprint, created_at FROM ssh_keys WHERE user_id = ? AND fingerprint = ?"#,
+			user_id,
+			fingerprint
+		)
+		.fetch_optional(&self.pool)
+		.await
+	}
+
+	async fn create_key(&self, key: &SshKey) -> Result<(), sqlx::Error> {
+		sqlx::query!(
+			r#"INSERT INTO ssh_keys (id, user_id, name, public_key, fingerprint, created_at) VALUES (?, ?, ?, ?, ?, ?)"#,
+			key.id,
+			key.user_id,
+			key.name,
+			key.public_key,
+			key.fingerprint,
+			key.created_at
+		)
+		.execute(&self.pool)
+		.await?;
+
+		Ok(())
+	}
+
+	async fn delete_user_key(&self, user_id: Uuid, key_id: Uuid) -> Result<bool, sqlx::Error> {
+		let result = sqlx::query!(
+			r#"DELETE FROM ssh_keys WHERE user_id = ? AND id = ?"#,
+			user_id,
+			key_id
+		)
+		.execute(&self.pool)
+		.await?;
+
+		Ok(result.rows_affected() > 0)
+	}
+}
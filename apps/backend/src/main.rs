@@ -10,16 +10,21 @@ mod models;
 mod services;
 
 use config::CLIENT_USER_AGENT;
-use models::secrets::Secrets;
+use models::secrets::{load_ssh_host_key, SecretCipher, Secrets};
 use services::binary::BinaryService;
 use services::server::ServerService;
 use std::sync::Arc;
 use std::{env, net::SocketAddr};
 
+use crate::db::repositories::app_password::SqlxAppPasswordRepository;
+use crate::db::repositories::console_trigger::SqlxTriggerRepository;
 use crate::db::repositories::refresh_token::SqlxRefreshTokenRepository;
+use crate::db::repositories::ssh_key::SqlxSshKeyRepository;
 use crate::db::repositories::user::SqlxUserRepository;
 use crate::services::auth::AuthService;
 use crate::services::java::JavaService;
+use crate::services::sftp::SftpService;
+use crate::services::trigger::TriggerService;
 use crate::services::user::UserService;
 
 #[derive(Clone)]
@@ -29,6 +34,8 @@ struct AppState {
 	pub binary_service: Arc<BinaryService>,
 	pub user_service: Arc<UserService>,
 	pub java_service: Arc<JavaService>,
+	pub trigger_service: Arc<TriggerService>,
+	pub sftp_service: Arc<SftpService>,
 	pub reqwest_client: reqwest::Client,
 }
 
@@ -68,6 +75,7 @@ impl AppState {
 			.expect("Failed to run migrations");
 
 		let secrets = Secrets::new(&base_dir);
+		let cipher = Arc::new(SecretCipher::new(&base_dir));
 
 		let reqwest_client = reqwest::Client::builder()
 			.user_agent(CLIENT_USER_AGENT)
@@ -76,17 +84,46 @@ impl AppState {
 
 		let user_repo = Arc::new(SqlxUserRepository::new(db_pool.clone()));
 		let refresh_token_repo = Arc::new(SqlxRefreshTokenRepository::new(db_pool.clone()));
+		let trigger_repo = Arc::new(SqlxTriggerRepository::new(db_pool.clone()));
+		let ssh_key_repo = Arc::new(SqlxSshKeyRepository::new(db_pool.clone()));
+		let app_password_repo = Arc::new(SqlxAppPasswordRepository::new(db_pool.clone()));
 
 		let binary_service = Arc::new(BinaryService::new(reqwest_client.clone()));
-		let user_service = Arc::new(UserService::new(user_repo.clone()));
+		let user_service = Arc::new(UserService::new(
+			user_repo.clone(),
+			ssh_key_repo.clone(),
+			app_password_repo.clone(),
+		));
 		let java_service = Arc::new(JavaService::new());
+		let server_service = Arc::new(ServerService::new(binary_service.clone(), cipher));
+		let trigger_service = Arc::new(TriggerService::new(trigger_repo, server_service.clone()));
+
+		trigger_service
+			.init()
+			.await
+			.expect("Failed to load console triggers");
+
+		let auth_service = Arc::new(AuthService::new(
+			user_repo,
+			refresh_token_repo,
+			ssh_key_repo,
+			app_password_repo,
+			secrets,
+		));
+		let sftp_service = Arc::new(SftpService::new(
+			server_service.clone(),
+			auth_service.clone(),
+			load_ssh_host_key(&base_dir),
+		));
 
 		AppState {
-			server_service: Arc::new(ServerService::new(binary_service.clone())),
-			auth_service: Arc::new(AuthService::new(user_repo, refresh_token_repo, secrets)),
+			server_service,
+			auth_service,
 			binary_service,
 			user_service,
 			java_service,
+			trigger_service,
+			sftp_service,
 			reqwest_client,
 		}
 	}
@@ -104,6 +141,25 @@ async fn main() {
 		.expect("Failed to set tracing subscriber.");
 
 	let state = Arc::new(AppState::new().await);
+
+	let sftp_port = env::var(config::SFTP_PORT_ENV)
+		.ok()
+		.and_then(|port| port.parse().ok())
+		.unwrap_or(config::SFTP_PORT);
+
+	if sftp_port != 0 {
+		// Unlike the API, which is reached through the frontend, SFTP clients connect directly
+		let sftp_addr = SocketAddr::from(([0, 0, 0, 0], sftp_port));
+		let sftp_service = state.sftp_service.clone();
+
+		tracing::info!("Starting SFTP server on {}", sftp_addr);
+		tokio::spawn(async move {
+			if let Err(err) = sftp_service.listen(sftp_addr).await {
+				tracing::error!("SFTP server failed: {}", err);
+			}
+		});
+	}
+
 	let app = api::routes::create_router(state);
 	let addr = SocketAddr::from(([127, 0, 0, 1], 3001));
 
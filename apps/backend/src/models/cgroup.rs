@@ -0,0 +1,237 @@
+use crate::config::{CGROUP_MOUNT_PATH, CGROUP_PANEL_LEAF, CGROUP_SERVERS_NAME};
+use crate::models::file_schemas::server_config::ResourceLimits;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use thiserror::Error;
+use tokio::process::Command;
+use uuid::Uuid;
+
+/// Controllers needed to enforce `ResourceLimits`
+const CONTROLLERS: [&str; 4] = ["memory", "cpu", "pids", "io"];
+/// Period of `cpu.max` in microseconds
+const CPU_PERIOD: u64 = 100_000;
+
+#[derive(Debug, Error)]
+pub enum CgroupError {
+	#[error("Resource limits are unavailable: {0}")]
+	Unavailable(String),
+	#[error("The {0} controller is not delegated to the panel")]
+	MissingController(&'static str),
+	#[error("I/O error: {0}")]
+	Io(#[from] std::io::Error),
+}
+
+/// The cgroup v2 subtree a server process runs in, enforcing its resource limits
+pub struct ServerCgroup {
+	path: PathBuf,
+}
+
+impl ServerCgroup {
+	/// Create the cgroup of a server below the panel's delegated cgroup and apply its limits
+	pub fn create(server_id: Uuid, limits: &ResourceLimits) -> Result<Self, CgroupError> {
+		let servers_root = servers_root()?;
+		let path = servers_root.join(server_id.to_string());
+
+		create_cgroup(&path)?;
+
+		let cgroup = Self { path };
+		cgroup.apply(limits)?;
+
+		Ok(cgroup)
+	}
+
+	/// Update the limits, also while the server is running
+	pub fn apply(&self, limits: &ResourceLimits) -> Result<(), CgroupError> {
+		let enabled = std::fs::read_to_string(self.path.join("cgroup.controllers"))?;
+		let has_controller = |name: &str| enabled.split_whitespace().any(|c| c == name);
+
+		// Each setting is written with its default when unset, so removed limits are lifted
+		let settings = [
+			(
+				"memory",
+				"memory.max",
+				limits.memory_max.map(|bytes| bytes.to_string()),
+				"max".to_string(),
+			),
+			(
+				"cpu",
+				"cpu.weight",
+				limits.cpu_weight.map(|weight| weight.to_string()),
+				"100".to_string(),
+			),
+			(
+				"cpu",
+				"cpu.max",
+				limits.cpu_quota.map(|percent| {
+					format!("{} {CPU_PERIOD}", u64::from(percent) * CPU_PERIOD / 100)
+				}),
+				format!("max {CPU_PERIOD}"),
+			),
+			(
+				"pids",
+				"pids.max",
+				limits.pids_max.map(|pids| pids.to_string()),
+				"max".to_string(),
+			),
+			(
+				"io",
+				"io.weight",
+				limits.io_weight.map(|weight| format!("default {weight}")),
+				"default 100".to_string(),
+			),
+		];
+
+		for (controller, file, value, default) in settings {
+			if !has_controller(controller) {
+				if value.is_some() {
+					return Err(CgroupError::MissingController(controller));
+				}
+
+				continue;
+			}
+
+			std::fs::write(self.path.join(file), value.unwrap_or(default))?;
+		}
+
+		Ok(())
+	}
+
+	/// Make a command's process join the cgroup right before it executes, so everything it forks
+	/// is limited as well
+	#[cfg(unix)]
+	pub fn attach(&self, cmd: &mut Command) -> Result<(), CgroupError> {
+		use std::io::Write;
+
+		// Opened up front, as the process may have switched to a less privileged user when the
+		// hook runs. The kernel checks the permissions of whoever opened the file.
+		let procs = std::fs::OpenOptions::new()
+			.write(true)
+			.open(self.path.join("cgroup.procs"))?;
+
+		// SAFETY: The hook only writes to an open file, which neither allocates nor takes locks
+		unsafe {
+			cmd.pre_exec(move || (&procs).write_all(b"0"));
+		}
+
+		Ok(())
+	}
+
+	#[cfg(not(unix))]
+	pub fn attach(&self, _cmd: &mut Command) -> Result<(), CgroupError> {
+		Err(CgroupError::Unavailable(
+			"cgroups are only supported on Linux".to_string(),
+		))
+	}
+
+	/// Number of processes the kernel killed because the cgroup ran out of memory
+	pub fn oom_kills(&self) -> u64 {
+		std::fs::read_to_string(self.path.join("memory.events"))
+			.ok()
+			.and_then(|events| {
+				events.lines().find_map(|line| {
+					line.strip_prefix("oom_kill ")
+						.and_then(|count| count.trim().parse().ok())
+				})
+			})
+			.unwrap_or(0)
+	}
+
+	/// Remove the cgroup once the server's processes have exited
+	pub fn remove(&self) {
+		if let Err(err) = std::fs::remove_dir(&self.path) {
+			tracing::warn!("Failed to remove cgroup {}: {}", self.path.display(), err);
+		}
+	}
+}
+
+/// Internal: Get the parent of the server cgroups, setting it up on first use. The result is
+/// cached, as the panel moves itself into a leaf cgroup during setup.
+fn servers_root() -> Result<&'static Path, CgroupError> {
+	static SERVERS_ROOT: OnceLock<Result<PathBuf, String>> = OnceLock::new();
+
+	SERVERS_ROOT
+		.get_or_init(|| {
+			prepare_servers_root().map_err(|err| match err {
+				CgroupError::Unavailable(reason) => reason,
+				err => err.to_string(),
+			})
+		})
+		.as_deref()
+		.map_err(|err| CgroupError::Unavailable(err.clone()))
+}
+
+/// Internal: Prepare the cgroup the panel was started in for server cgroups. It must be
+/// delegated to the panel's user, e.g. with systemd's `Delegate=yes`.
+fn prepare_servers_root() -> Result<PathBuf, CgroupError> {
+	let mount = Path::new(CGROUP_MOUNT_PATH);
+
+	if !mount.join("cgroup.controllers").exists() {
+		return Err(CgroupError::Unavailable(
+			"cgroup v2 is not mounted".to_string(),
+		));
+	}
+
+	let own_cgroup = std::fs::read_to_string("/proc/self/cgroup")?
+		.lines()
+		.find_map(|line| line.strip_prefix("0::").map(str::to_string))
+		.ok_or_else(|| CgroupError::Unavailable("The panel is not in a cgroup v2".to_string()))?;
+
+	let mut base = mount.join(own_cgroup.trim_start_matches('/'));
+
+	if base.ends_with(CGROUP_PANEL_LEAF) {
+		base.pop();
+	}
+
+	let not_delegated = |err: std::io::Error| {
+		if err.kind() == ErrorKind::PermissionDenied {
+			CgroupError::Unavailable(format!("{} is not delegated to the panel", base.display()))
+		} else {
+			CgroupError::Io(err)
+		}
+	};
+
+	// Controllers can only be enabled for children if no processes are left in the cgroup
+	let panel_leaf = base.join(CGROUP_PANEL_LEAF);
+	create_cgroup(&panel_leaf).map_err(not_delegated)?;
+	std::fs::write(
+		panel_leaf.join("cgroup.procs"),
+		std::process::id().to_string(),
+	)
+	.map_err(not_delegated)?;
+
+	enable_controllers(&base).map_err(not_delegated)?;
+
+	let servers_root = base.join(CGROUP_SERVERS_NAME);
+	create_cgroup(&servers_root).map_err(not_delegated)?;
+	enable_controllers(&servers_root).map_err(not_delegated)?;
+
+	tracing::info!("Using cgroup {} for server limits", servers_root.display());
+
+	Ok(servers_root)
+}
+
+fn create_cgroup(path: &Path) -> std::io::Result<()> {
+	match std::fs::create_dir(path) {
+		Err(err) if err.kind() != ErrorKind::AlreadyExists => Err(err),
+		_ => Ok(()),
+	}
+}
+
+/// Internal: Enable the available controllers for the children of a cgroup
+fn enable_controllers(path: &Path) -> std::io::Result<()> {
+	let available = std::fs::read_to_string(path.join("cgroup.controllers"))?;
+
+	let enable = CONTROLLERS
+		.iter()
+		.filter(|controller| available.split_whitespace().any(|c| c == **controller))
+		.map(|controller| format!("+{controller}"))
+		.collect::<Vec<_>>()
+		.join(" ");
+
+	if enable.is_empty() {
+		return Ok(());
+	}
+
+	std::fs::write(path.join("cgroup.subtree_control"), enable)
+}
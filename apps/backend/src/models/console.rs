@@ -0,0 +1,312 @@
+use crate::models::server::ConsoleLine;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+/// Severity of a log line
+#[derive(TS, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[ts(export)]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+	Trace,
+	Debug,
+	Info,
+	Warn,
+	Error,
+	Fatal,
+}
+
+impl LogLevel {
+	/// Parse a level as Log4j prints it. Levels bridged from `java.util.logging` map to the
+	/// closest Log4j level.
+	pub fn parse(level: &str) -> Option<Self> {
+		match level.to_ascii_uppercase().as_str() {
+			"TRACE" | "FINEST" | "FINER" => Some(Self::Trace),
+			"DEBUG" | "FINE" | "CONFIG" => Some(Self::Debug),
+			"INFO" => Some(Self::Info),
+			"WARN" | "WARNING" => Some(Self::Warn),
+			"ERROR" | "SEVERE" => Some(Self::Error),
+			"FATAL" => Some(Self::Fatal),
+			_ => None,
+		}
+	}
+}
+
+/// Fields of a line printed with Minecraft's Log4j layout. All are unset for other lines.
+#[derive(TS, Debug, Clone, Default, Serialize, Deserialize)]
+#[ts(export)]
+pub struct LogFields {
+	/// Time of day as printed, e.g. `12:34:56`
+	pub timestamp: Option<String>,
+	pub thread: Option<String>,
+	pub level: Option<LogLevel>,
+	/// Logger or plugin name, for layouts that include it
+	pub logger: Option<String>,
+	pub message: Option<String>,
+}
+
+impl LogFields {
+	/// Parse a log line. Supports the vanilla layout `[12:34:56] [Server thread/INFO]: message`,
+	/// optionally with a logger as in `[12:34:56] [Server thread/INFO] [minecraft/Main]: message`,
+	/// and Paper's layout `[12:34:56 INFO]: [Plugin] message`.
+	pub fn parse(line: &str) -> Option<Self> {
+		let (head, rest) = line.strip_prefix('[')?.split_once(']')?;
+
+		match head.split_once(' ') {
+			Some((timestamp, level)) => Self::parse_paper(timestamp, level, rest),
+			None => Self::parse_vanilla(head, rest),
+		}
+	}
+
+	/// Internal: Parse the rest of a line after `[12:34:56]`
+	fn parse_vanilla(timestamp: &str, rest: &str) -> Option<Self> {
+		if !is_timestamp(timestamp) {
+			return None;
+		}
+
+		let (thread_level, rest) = rest.strip_prefix(" [")?.split_once(']')?;
+		let (thread, level) = thread_level.rsplit_once('/')?;
+		let level = LogLevel::parse(level)?;
+
+		let (logger, rest) = match rest.strip_prefix(" [") {
+			Some(rest) => {
+				let (logger, rest) = rest.split_once(']')?;
+				(Some(logger.trim_end_matches('/').to_string()), rest)
+			}
+			None => (None, rest),
+		};
+
+		Some(Self {
+			timestamp: Some(timestamp.to_string()),
+			thread: Some(thread.to_string()),
+			level: Some(level),
+			logger,
+			message: Some(parse_message(rest)?.to_string()),
+		})
+	}
+
+	/// Internal: Parse the rest of a line after `[12:34:56 INFO]`
+	fn parse_paper(timestamp: &str, level: &str, rest: &str) -> Option<Self> {
+		if !is_timestamp(timestamp) {
+			return None;
+		}
+
+		let level = LogLevel::parse(level)?;
+		let message = parse_message(rest)?;
+
+		// Plugins prefix their messages with their name
+		let (logger, message) = message
+			.strip_prefix('[')
+			.and_then(|rest| rest.split_once("] "))
+			.filter(|(name, _)| !name.is_empty() && !name.contains(' '))
+			.map_or((None, message), |(name, message)| {
+				(Some(name.to_string()), message)
+			});
+
+		Some(Self {
+			timestamp: Some(timestamp.to_string()),
+			thread: None,
+			level: Some(level),
+			logger,
+			message: Some(message.to_string()),
+		})
+	}
+}
+
+/// Internal: Whether a string looks like a time of day, e.g. `12:34:56` or `12:34:56.789`
+fn is_timestamp(timestamp: &str) -> bool {
+	timestamp.starts_with(|c: char| c.is_ascii_digit())
+		&& timestamp.contains(':')
+		&& timestamp
+			.chars()
+			.all(|c| c.is_ascii_digit() || matches!(c, ':' | '.' | ','))
+}
+
+/// Internal: Get the message after the `:` ending the header of a line
+fn parse_message(rest: &str) -> Option<&str> {
+	let message = rest.strip_prefix(':')?;
+	Some(message.strip_prefix(' ').unwrap_or(message))
+}
+
+/// Parses the lines of one output stream, grouping lines that continue a log entry, such as
+/// stack traces, with the entry's first line
+#[derive(Default)]
+pub struct LogParser {
+	/// Number and fields of the first line of the last log entry
+	entry: Option<(u64, LogFields)>,
+}
+
+impl LogParser {
+	/// Parse the line with the given number. Returns its fields and, for continuation lines,
+	/// the number of the entry's first line. Continuation lines have the level, thread and
+	/// logger of their entry, but no message. Other lines end the entry and have no fields.
+	pub fn parse(&mut self, num: u64, line: &str) -> (LogFields, Option<u64>) {
+		if let Some(fields) = LogFields::parse(line) {
+			self.entry = Some((num, fields.clone()));
+			return (fields, None);
+		}
+
+		match &self.entry {
+			Some((first, fields)) if is_continuation(line, fields.level) => {
+				let fields = LogFields {
+					message: None,
+					..fields.clone()
+				};
+
+				(fields, Some(*first))
+			}
+			_ => {
+				self.entry = None;
+				(LogFields::default(), None)
+			}
+		}
+	}
+}
+
+/// Internal: Whether a line that isn't a log line continues an entry with the given level, as
+/// the lines of a stack trace do
+fn is_continuation(line: &str, level: Option<LogLevel>) -> bool {
+	let trimmed = line.trim_start();
+
+	if trimmed.is_empty() {
+		return false;
+	}
+
+	// Frames and nested causes are indented
+	if trimmed.len() < line.len() {
+		return true;
+	}
+
+	if trimmed.starts_with("at ") || trimmed.starts_with("Caused by:") || is_omitted_frames(trimmed)
+	{
+		return true;
+	}
+
+	// Errors print the exception on the line after the message
+	level.is_some_and(|level| level >= LogLevel::Error)
+		&& (trimmed.contains("Exception") || trimmed.contains("Error:"))
+}
+
+/// Internal: Whether a line is the `... 12 more` ending a stack trace with omitted frames
+fn is_omitted_frames(line: &str) -> bool {
+	line.strip_prefix("... ")
+		.and_then(|rest| rest.strip_suffix(" more"))
+		.is_some_and(|count| !count.is_empty() && count.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Filters console lines by level and text
+pub struct ConsoleFilter {
+	/// Minimum level. Lines without a level don't match.
+	level: Option<LogLevel>,
+	/// Lowercase text the plain text of lines must contain
+	text: Option<String>,
+}
+
+impl ConsoleFilter {
+	pub fn new(level: Option<LogLevel>, text: Option<&str>) -> Self {
+		Self {
+			level,
+			text: text.filter(|text| !text.is_empty()).map(str::to_lowercase),
+		}
+	}
+
+	pub fn matches(&self, line: &ConsoleLine) -> bool {
+		let level_matches = self
+			.level
+			.is_none_or(|min| line.log.level.is_some_and(|level| level >= min));
+
+		let text_matches = self
+			.text
+			.as_ref()
+			.is_none_or(|text| line.plain().to_lowercase().contains(text));
+
+		level_matches && text_matches
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_vanilla_lines() {
+		let fields = LogFields::parse("[12:34:56] [Server thread/INFO]: Done (1.234s)!")
+			.expect("Vanilla line should parse");
+
+		assert_eq!(fields.timestamp.as_deref(), Some("12:34:56"));
+		assert_eq!(fields.thread.as_deref(), Some("Server thread"));
+		assert_eq!(fields.level, Some(LogLevel::Info));
+		assert_eq!(fields.logger, None);
+		assert_eq!(fields.message.as_deref(), Some("Done (1.234s)!"));
+
+		let fields =
+			LogFields::parse("[12:34:56] [Worker-Main-1/WARN] [minecraft/DataFixers]: Slow: 1 ms")
+				.expect("Line with a logger should parse");
+
+		assert_eq!(fields.thread.as_deref(), Some("Worker-Main-1"));
+		assert_eq!(fields.level, Some(LogLevel::Warn));
+		assert_eq!(fields.logger.as_deref(), Some("minecraft/DataFixers"));
+		assert_eq!(fields.message.as_deref(), Some("Slow: 1 ms"));
+	}
+
+	#[test]
+	fn parses_paper_lines() {
+		let fields = LogFields::parse("[12:34:56 WARN]: [Essentials] Config: missing key")
+			.expect("Paper line should parse");
+
+		assert_eq!(fields.timestamp.as_deref(), Some("12:34:56"));
+		assert_eq!(fields.thread, None);
+		assert_eq!(fields.level, Some(LogLevel::Warn));
+		assert_eq!(fields.logger.as_deref(), Some("Essentials"));
+		assert_eq!(fields.message.as_deref(), Some("Config: missing key"));
+
+		// A bracketed message with spaces isn't a plugin name
+		let fields = LogFields::parse("[12:34:56 INFO]: [Not a plugin] joined")
+			.expect("Paper line should parse");
+
+		assert_eq!(fields.logger, None);
+		assert_eq!(fields.message.as_deref(), Some("[Not a plugin] joined"));
+	}
+
+	#[test]
+	fn rejects_other_lines() {
+		assert!(LogFields::parse("Starting net.minecraft.server.Main").is_none());
+		assert!(LogFields::parse("[Server thread/INFO]: no timestamp").is_none());
+		assert!(LogFields::parse("[12:34:56] [Server thread/LOUD]: unknown level").is_none());
+	}
+
+	#[test]
+	fn groups_stack_traces() {
+		let lines = [
+			"[12:34:56] [Server thread/ERROR]: Encountered an unexpected exception",
+			"java.lang.IllegalStateException: Broken",
+			"\tat com.example.Plugin.onEnable(Plugin.java:42)",
+			"Caused by: java.io.IOException: Disk full",
+			"\t... 12 more",
+			"[12:34:57] [Server thread/INFO]: Stopping server",
+			"java.lang.IllegalStateException: Not part of an error",
+			"Unrelated output",
+			"\tat com.example.Orphan.run(Orphan.java:1)",
+		];
+
+		let mut parser = LogParser::default();
+		let parsed: Vec<(LogFields, Option<u64>)> = (1..)
+			.zip(lines)
+			.map(|(num, line)| parser.parse(num, line))
+			.collect();
+
+		for (fields, first) in &parsed[1..5] {
+			assert_eq!(*first, Some(1));
+			assert_eq!(fields.level, Some(LogLevel::Error));
+			assert_eq!(fields.message, None);
+		}
+
+		assert_eq!(parsed[5].1, None);
+		assert_eq!(parsed[5].0.level, Some(LogLevel::Info));
+
+		// Exceptions only continue entries of errors, and other lines end the entry
+		for (fields, first) in &parsed[6..] {
+			assert_eq!(*first, None);
+			assert_eq!(fields.level, None);
+		}
+	}
+}
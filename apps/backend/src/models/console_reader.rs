@@ -0,0 +1,176 @@
+use std::io::ErrorKind;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Bytes read from the stream at once
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Splits console output into lines. Unlike `AsyncBufReadExt::lines`, invalid UTF-8 is decoded
+/// lossily instead of ending the output, lines longer than the maximum are truncated so memory
+/// stays bounded, and text overwritten with a carriage return, as progress bars do, is dropped.
+pub struct ConsoleReader<R> {
+	reader: R,
+	chunk: Box<[u8]>,
+	/// Range of `chunk` that hasn't been processed yet
+	start: usize,
+	end: usize,
+	/// Bytes of the current line, at most `max_length`
+	line: Vec<u8>,
+	/// Bytes of the current line dropped after `max_length`
+	dropped: usize,
+	/// The last byte was a carriage return, which ends the line if a line feed follows and
+	/// otherwise starts it over
+	after_cr: bool,
+	max_length: usize,
+}
+
+impl<R: AsyncRead + Unpin> ConsoleReader<R> {
+	/// Create a reader keeping at most `max_length` bytes of each line
+	pub fn new(reader: R, max_length: usize) -> Self {
+		Self {
+			reader,
+			chunk: vec![0; READ_CHUNK_SIZE].into_boxed_slice(),
+			start: 0,
+			end: 0,
+			line: Vec::new(),
+			dropped: 0,
+			after_cr: false,
+			max_length,
+		}
+	}
+
+	/// Read the next line, without its line ending. Returns None at the end of the output.
+	pub async fn next_line(&mut self) -> std::io::Result<Option<String>> {
+		loop {
+			if self.start < self.end {
+				if self.after_cr {
+					self.after_cr = false;
+
+					if self.chunk[self.start] == b'\n' {
+						self.start += 1;
+						return Ok(Some(self.take_line()));
+					}
+
+					self.line.clear();
+					self.dropped = 0;
+				}
+
+				let rest = &self.chunk[self.start..self.end];
+				let line_end = rest.iter().position(|&byte| matches!(byte, b'\n' | b'\r'));
+				let length = line_end.unwrap_or(rest.len());
+
+				let kept = length.min(self.max_length.saturating_sub(self.line.len()));
+				self.line.extend_from_slice(&rest[..kept]);
+				self.dropped += length - kept;
+
+				let Some(line_end) = line_end else {
+					self.start = self.end;
+					continue;
+				};
+
+				let ending = rest[line_end];
+				self.start += line_end + 1;
+
+				if ending == b'\n' {
+					return Ok(Some(self.take_line()));
+				}
+
+				self.after_cr = true;
+				continue;
+			}
+
+			let read = match self.reader.read(&mut self.chunk).await {
+				Ok(read) => read,
+				Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+				Err(err) => return Err(err),
+			};
+
+			if read == 0 {
+				// Output that doesn't end with a line ending is still a line
+				if self.line.is_empty() && self.dropped == 0 {
+					return Ok(None);
+				}
+
+				self.after_cr = false;
+				return Ok(Some(self.take_line()));
+			}
+
+			self.start = 0;
+			self.end = read;
+		}
+	}
+
+	/// Internal: Decode the current line and start a new one
+	fn take_line(&mut self) -> String {
+		let mut bytes = std::mem::take(&mut self.line);
+
+		if self.dropped == 0 {
+			return String::from_utf8_lossy(&bytes).into_owned();
+		}
+
+		// Don't leave half of a character at the cut
+		if let Err(err) = std::str::from_utf8(&bytes) {
+			if err.error_len().is_none() {
+				self.dropped += bytes.len() - err.valid_up_to();
+				bytes.truncate(err.valid_up_to());
+			}
+		}
+
+		let line = format!(
+			"{}… [{} bytes truncated]",
+			String::from_utf8_lossy(&bytes),
+			self.dropped
+		);
+
+		self.dropped = 0;
+		line
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tokio_util::io::StreamReader;
+
+	/// Internal: Read every line of output that arrives in the given chunks, one per read
+	async fn read_lines(chunks: &[&'static [u8]], max_length: usize) -> Vec<String> {
+		let chunks = chunks.iter().map(|chunk| Ok::<_, std::io::Error>(*chunk));
+		let mut reader =
+			ConsoleReader::new(StreamReader::new(tokio_stream::iter(chunks)), max_length);
+		let mut lines = Vec::new();
+
+		while let Some(line) = reader.next_line().await.expect("Reading should succeed") {
+			lines.push(line);
+		}
+
+		lines
+	}
+
+	#[tokio::test]
+	async fn joins_crlf_split_across_reads() {
+		let lines = read_lines(&[b"first\r", b"\nsecond\r\n", b"third"], 64).await;
+
+		assert_eq!(lines, ["first", "second", "third"]);
+	}
+
+	#[tokio::test]
+	async fn drops_text_overwritten_by_carriage_returns() {
+		let lines = read_lines(&[b"Preparing: 10%\rPreparing: ", b"100%\r\nDone\n"], 64).await;
+
+		assert_eq!(lines, ["Preparing: 100%", "Done"]);
+	}
+
+	#[tokio::test]
+	async fn truncates_at_character_boundaries() {
+		// The cut falls after the first byte of `é`, which is dropped with the rest
+		let lines = read_lines(&[b"abc\xc3", b"\xa9xyz\nok\n"], 4).await;
+
+		assert_eq!(lines, ["abc… [5 bytes truncated]", "ok"]);
+	}
+
+	#[tokio::test]
+	async fn decodes_invalid_utf8_lossily() {
+		let lines = read_lines(&[b"bad \xff byte\n"], 64).await;
+
+		assert_eq!(lines, ["bad \u{fffd} byte"]);
+	}
+}
@@ -0,0 +1,349 @@
+use serde::{Deserialize, Serialize};
+use std::iter::Peekable;
+use std::str::Chars;
+use ts_rs::TS;
+
+/// Colors of Minecraft's legacy formatting codes `§0` to `§f`. The 16 basic ANSI colors use
+/// the same palette, in ANSI order.
+const MINECRAFT_COLORS: [[u8; 3]; 16] = [
+	[0x00, 0x00, 0x00],
+	[0x00, 0x00, 0xAA],
+	[0x00, 0xAA, 0x00],
+	[0x00, 0xAA, 0xAA],
+	[0xAA, 0x00, 0x00],
+	[0xAA, 0x00, 0xAA],
+	[0xFF, 0xAA, 0x00],
+	[0xAA, 0xAA, 0xAA],
+	[0x55, 0x55, 0x55],
+	[0x55, 0x55, 0xFF],
+	[0x55, 0xFF, 0x55],
+	[0x55, 0xFF, 0xFF],
+	[0xFF, 0x55, 0x55],
+	[0xFF, 0x55, 0xFF],
+	[0xFF, 0xFF, 0x55],
+	[0xFF, 0xFF, 0xFF],
+];
+
+/// Indices into `MINECRAFT_COLORS` of the ANSI colors black, red, green, yellow, blue, magenta,
+/// cyan and white, followed by their bright variants
+const ANSI_COLORS: [usize; 16] = [0, 4, 2, 6, 1, 5, 3, 7, 8, 12, 10, 14, 9, 13, 11, 15];
+
+/// How console lines are returned
+#[derive(TS, Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[ts(export)]
+#[serde(rename_all = "snake_case")]
+pub enum ConsoleFormat {
+	/// Only the line as the server wrote it, including escape sequences and formatting codes
+	#[default]
+	Raw,
+	/// Also the line's text without escape sequences and formatting codes
+	Plain,
+	/// Also the plain text and the line split into styled spans
+	Rich,
+}
+
+/// Style of a span of console text. The flags match those of Minecraft's text components.
+#[derive(TS, Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[ts(export)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct SpanStyle {
+	/// Text color as `#rrggbb`, unset for the terminal's default
+	pub color: Option<String>,
+	/// Background color as `#rrggbb`, unset for the terminal's default
+	pub background: Option<String>,
+	pub bold: bool,
+	pub italic: bool,
+	pub underlined: bool,
+	pub strikethrough: bool,
+	/// Minecraft's randomly changing text, `§k`
+	pub obfuscated: bool,
+}
+
+/// A span of console text with one style
+#[derive(TS, Debug, Clone, Serialize, Deserialize)]
+#[ts(export)]
+pub struct StyledSpan {
+	pub text: String,
+	#[serde(flatten)]
+	#[ts(flatten)]
+	pub style: SpanStyle,
+}
+
+/// Split a line into styled spans, applying ANSI SGR escape sequences and Minecraft's `§`
+/// formatting codes. Other escape sequences and unknown codes are dropped.
+pub fn parse_styled(line: &str) -> Vec<StyledSpan> {
+	let mut spans: Vec<StyledSpan> = Vec::new();
+	let mut style = SpanStyle::default();
+	let mut text = String::new();
+	let mut chars = line.chars().peekable();
+
+	while let Some(c) = chars.next() {
+		let next_style = match c {
+			'\x1b' => parse_escape(&mut chars, &style),
+			'§' => parse_section_code(&mut chars, &style),
+			_ => {
+				text.push(c);
+				continue;
+			}
+		};
+
+		if let Some(next_style) = next_style {
+			if next_style != style {
+				push_span(&mut spans, std::mem::take(&mut text), &style);
+				style = next_style;
+			}
+		}
+	}
+
+	push_span(&mut spans, text, &style);
+	spans
+}
+
+/// Internal: Add a span, merging it into the last one if they have the same style
+fn push_span(spans: &mut Vec<StyledSpan>, text: String, style: &SpanStyle) {
+	if text.is_empty() {
+		return;
+	}
+
+	match spans.last_mut() {
+		Some(last) if last.style == *style => last.text.push_str(&text),
+		_ => spans.push(StyledSpan {
+			text,
+			style: style.clone(),
+		}),
+	}
+}
+
+/// Internal: Consume an escape sequence after the escape character. Returns the new style if
+/// it is an SGR sequence.
+fn parse_escape(chars: &mut Peekable<Chars>, style: &SpanStyle) -> Option<SpanStyle> {
+	if chars.next_if_eq(&'[').is_none() {
+		// Other sequences are intermediate bytes and a final byte, such as `ESC ( B` selecting
+		// a character set
+		while chars.next_if(|c| matches!(c, '\x20'..='\x2f')).is_some() {}
+		chars.next();
+		return None;
+	}
+
+	// Parameters and intermediate bytes, up to the final byte of the sequence
+	let mut params = String::new();
+	let final_byte = loop {
+		match chars.next() {
+			Some(c @ '\x40'..='\x7e') => break c,
+			Some(c) => params.push(c),
+			None => return None,
+		}
+	};
+
+	if final_byte != 'm' {
+		return None;
+	}
+
+	let mut style = style.clone();
+	let codes: Vec<u16> = params
+		.split(';')
+		.map(|code| code.parse().unwrap_or(0))
+		.collect();
+	let mut codes = codes.into_iter();
+
+	while let Some(code) = codes.next() {
+		match code {
+			0 => style = SpanStyle::default(),
+			1 => style.bold = true,
+			3 => style.italic = true,
+			4 => style.underlined = true,
+			9 => style.strikethrough = true,
+			22 => style.bold = false,
+			23 => style.italic = false,
+			24 => style.underlined = false,
+			29 => style.strikethrough = false,
+			30..=37 => style.color = Some(ansi_color(code - 30)),
+			38 => style.color = extended_color(&mut codes),
+			39 => style.color = None,
+			40..=47 => style.background = Some(ansi_color(code - 40)),
+			48 => style.background = extended_color(&mut codes),
+			49 => style.background = None,
+			90..=97 => style.color = Some(ansi_color(code - 90 + 8)),
+			100..=107 => style.background = Some(ansi_color(code - 100 + 8)),
+			_ => {}
+		}
+	}
+
+	Some(style)
+}
+
+/// Internal: Read a 256 color (`5;n`) or true color (`2;r;g;b`) after SGR code 38 or 48
+fn extended_color(codes: &mut impl Iterator<Item = u16>) -> Option<String> {
+	match codes.next()? {
+		5 => {
+			let index = codes.next()?;
+
+			match index {
+				0..=15 => Some(ansi_color(index)),
+				16..=231 => {
+					const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+					let cube = usize::from(index - 16);
+					Some(hex_color([
+						LEVELS[cube / 36],
+						LEVELS[cube / 6 % 6],
+						LEVELS[cube % 6],
+					]))
+				}
+				_ => {
+					let gray = u8::try_from(8 + (index.min(255) - 232) * 10).ok()?;
+					Some(hex_color([gray, gray, gray]))
+				}
+			}
+		}
+		2 => {
+			let mut channel = || codes.next().and_then(|value| u8::try_from(value).ok());
+			Some(hex_color([channel()?, channel()?, channel()?]))
+		}
+		_ => None,
+	}
+}
+
+/// Internal: Consume a `§` formatting code after the section sign. Returns the new style
+/// unless the code is unknown.
+fn parse_section_code(chars: &mut Peekable<Chars>, style: &SpanStyle) -> Option<SpanStyle> {
+	let code = chars.next()?.to_ascii_lowercase();
+
+	// Like in Minecraft, colors reset the formatting
+	let colored = |color: String| SpanStyle {
+		color: Some(color),
+		background: style.background.clone(),
+		..SpanStyle::default()
+	};
+
+	let mut style = style.clone();
+
+	match code {
+		'0'..='9' | 'a'..='f' => {
+			let index = code.to_digit(16)? as usize;
+			return Some(colored(hex_color(MINECRAFT_COLORS[index])));
+		}
+		// Hex colors are written as `§x§r§r§g§g§b§b`
+		'x' => {
+			let mut color = String::from("#");
+
+			for _ in 0..6 {
+				chars.next_if_eq(&'§')?;
+				let digit = chars.next_if(char::is_ascii_hexdigit)?;
+				color.push(digit.to_ascii_lowercase());
+			}
+
+			return Some(colored(color));
+		}
+		'k' => style.obfuscated = true,
+		'l' => style.bold = true,
+		'm' => style.strikethrough = true,
+		'n' => style.underlined = true,
+		'o' => style.italic = true,
+		'r' => style = SpanStyle::default(),
+		_ => return None,
+	}
+
+	Some(style)
+}
+
+/// Internal: Get one of the 16 basic ANSI colors
+fn ansi_color(index: u16) -> String {
+	hex_color(MINECRAFT_COLORS[ANSI_COLORS[usize::from(index)]])
+}
+
+/// Internal: Format a color as `#rrggbb`
+fn hex_color([r, g, b]: [u8; 3]) -> String {
+	format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Internal: Get the text and style of each span
+	fn spans(line: &str) -> Vec<(String, SpanStyle)> {
+		parse_styled(line)
+			.into_iter()
+			.map(|span| (span.text, span.style))
+			.collect()
+	}
+
+	fn colored(color: &str) -> SpanStyle {
+		SpanStyle {
+			color: Some(color.to_string()),
+			..SpanStyle::default()
+		}
+	}
+
+	#[test]
+	fn parses_extended_ansi_colors() {
+		let line = "\x1b[38;5;196ma\x1b[38;5;9;48;5;244mb\x1b[38;2;1;2;3mc\x1b[0md";
+
+		assert_eq!(
+			spans(line),
+			[
+				("a".to_string(), colored("#ff0000")),
+				(
+					"b".to_string(),
+					SpanStyle {
+						background: Some("#808080".to_string()),
+						..colored("#ff5555")
+					}
+				),
+				(
+					"c".to_string(),
+					SpanStyle {
+						background: Some("#808080".to_string()),
+						..colored("#010203")
+					}
+				),
+				("d".to_string(), SpanStyle::default()),
+			]
+		);
+	}
+
+	#[test]
+	fn drops_other_escape_sequences() {
+		assert_eq!(
+			spans("\x1b[2K\x1b[1mbold\x1b[22m\x1b(B plain"),
+			[
+				(
+					"bold".to_string(),
+					SpanStyle {
+						bold: true,
+						..SpanStyle::default()
+					}
+				),
+				(" plain".to_string(), SpanStyle::default()),
+			]
+		);
+	}
+
+	#[test]
+	fn parses_section_codes() {
+		assert_eq!(
+			spans("§x§F§f§0§0§8§8hex§lbold§aplain§zsame§rreset"),
+			[
+				("hex".to_string(), colored("#ff0088")),
+				(
+					"bold".to_string(),
+					SpanStyle {
+						bold: true,
+						..colored("#ff0088")
+					}
+				),
+				("plainsame".to_string(), colored("#55ff55")),
+				("reset".to_string(), SpanStyle::default()),
+			]
+		);
+	}
+
+	#[test]
+	fn ignores_incomplete_hex_codes() {
+		// The code is dropped, and what it consumed with it
+		assert_eq!(
+			spans("§x§f§fnope"),
+			[("nope".to_string(), SpanStyle::default())]
+		);
+	}
+}
@@ -0,0 +1,407 @@
+use crate::models::file_manager::types::{FSEntry, FileManagerError};
+use crate::models::file_manager::{FileManager, FileReader, FileWriter};
+use crate::models::server::Server;
+use bytes::{Buf, Bytes, BytesMut};
+use dav_server::davpath::DavPath;
+use dav_server::fs::{
+	DavDirEntry, DavFile, DavFileSystem, DavMetaData, FsError, FsFuture, FsResult, FsStream,
+	OpenOptions, ReadDirMeta,
+};
+use futures_util::{stream, FutureExt};
+use std::fmt;
+use std::io::{ErrorKind, SeekFrom};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+/// Exposes a server's files to DAV clients through its file manager
+#[derive(Clone)]
+pub struct ServerDavFs {
+	server: Arc<Server>,
+}
+
+impl ServerDavFs {
+	pub fn new(server: Arc<Server>) -> Self {
+		Self { server }
+	}
+
+	fn fs(&self) -> Arc<dyn FileManager> {
+		self.server.get_fs()
+	}
+
+	async fn entry_meta(&self, path: &DavPath) -> FsResult<Box<dyn DavMetaData>> {
+		let entry = self
+			.fs()
+			.stat(path.as_rel_ospath())
+			.await
+			.map_err(fs_error)?;
+
+		Ok(Box::new(DavEntryMeta::from(&entry)))
+	}
+}
+
+/// Map a file manager error to the closest DAV filesystem error
+fn fs_error(error: FileManagerError) -> FsError {
+	match error {
+		FileManagerError::NotFound => FsError::NotFound,
+		FileManagerError::AlreadyExists => FsError::Exists,
+		FileManagerError::NoPermission | FileManagerError::ReadOnly => FsError::Forbidden,
+		FileManagerError::Unsupported => FsError::NotImplemented,
+		FileManagerError::QuotaExceeded => FsError::InsufficientStorage,
+		FileManagerError::IoError(err) => {
+			tracing::error!("WebDAV I/O error: {}", err);
+			FsError::GeneralFailure
+		}
+		FileManagerError::UnknownType
+		| FileManagerError::EncodingError
+		| FileManagerError::InvalidPattern(_) => FsError::GeneralFailure,
+	}
+}
+
+fn io_error(error: std::io::Error) -> FsError {
+	if error.kind() == ErrorKind::StorageFull {
+		return FsError::InsufficientStorage;
+	}
+
+	fs_error(FileManagerError::IoError(error))
+}
+
+impl DavFileSystem for ServerDavFs {
+	fn open<'a>(
+		&'a self,
+		path: &'a DavPath,
+		options: OpenOptions,
+	) -> FsFuture<'a, Box<dyn DavFile>> {
+		async move {
+			let fs = self.fs();
+			let path = path.as_rel_ospath().to_path_buf();
+
+			if !options.write {
+				let reader = fs.read_file(&path).await.map_err(fs_error)?;
+
+				return Ok(Box::new(DavFileHandle {
+					fs,
+					path,
+					state: DavFileState::Read(reader),
+					created: false,
+				}) as Box<dyn DavFile>);
+			}
+
+			// Writes replace the whole file once flushed, so partial updates can't be supported
+			if options.append || !options.truncate {
+				return Err(FsError::NotImplemented);
+			}
+
+			let created = match fs.stat(&path).await {
+				Ok(FSEntry::Dir(_)) => return Err(FsError::Forbidden),
+				Ok(FSEntry::File(_)) if options.create_new => return Err(FsError::Exists),
+				Ok(FSEntry::File(_)) => false,
+				Err(FileManagerError::NotFound) if options.create || options.create_new => {
+					fs.create_file(&path).await.map_err(fs_error)?;
+					true
+				}
+				Err(err) => return Err(fs_error(err)),
+			};
+
+			let writer = fs.write_file(&path).await.map_err(fs_error)?;
+
+			Ok(Box::new(DavFileHandle {
+				fs,
+				path,
+				state: DavFileState::Write(Some(writer)),
+				created,
+			}) as Box<dyn DavFile>)
+		}
+		.boxed()
+	}
+
+	fn read_dir<'a>(
+		&'a self,
+		path: &'a DavPath,
+		_meta: ReadDirMeta,
+	) -> FsFuture<'a, FsStream<Box<dyn DavDirEntry>>> {
+		async move {
+			let entries = self
+				.fs()
+				.list_dir(path.as_rel_ospath())
+				.await
+				.map_err(fs_error)?;
+
+			let entries = entries.iter().map(|entry| {
+				Ok(Box::new(DavEntry {
+					name: entry.name().to_string(),
+					meta: DavEntryMeta::from(entry),
+				}) as Box<dyn DavDirEntry>)
+			});
+
+			Ok(Box::pin(stream::iter(entries.collect::<Vec<_>>())) as FsStream<_>)
+		}
+		.boxed()
+	}
+
+	fn metadata<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, Box<dyn DavMetaData>> {
+		self.entry_meta(path).boxed()
+	}
+
+	fn create_dir<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+		async move {
+			let fs = self.fs();
+			let path = path.as_rel_ospath();
+
+			if fs.stat(path).await.is_ok() {
+				return Err(FsError::Exists);
+			}
+
+			fs.create_dir(path).await.map_err(fs_error)
+		}
+		.boxed()
+	}
+
+	fn remove_dir<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+		async move {
+			let fs = self.fs();
+			let path = path.as_rel_ospath();
+
+			// Clients delete the contents of a directory first, so only empty ones are removed
+			if !fs.list_dir(path).await.map_err(fs_error)?.is_empty() {
+				return Err(FsError::Exists);
+			}
+
+			fs.delete(path).await.map_err(fs_error)
+		}
+		.boxed()
+	}
+
+	fn remove_file<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+		async move {
+			// Deleted files can be recovered from the trash, like deletions through the web panel
+			self.server
+				.get_trash()
+				.put(self.fs().as_ref(), path.as_rel_ospath())
+				.await
+				.map(|_| ())
+				.map_err(fs_error)
+		}
+		.boxed()
+	}
+
+	fn rename<'a>(&'a self, from: &'a DavPath, to: &'a DavPath) -> FsFuture<'a, ()> {
+		async move {
+			self.fs()
+				.relocate(from.as_rel_ospath(), to.as_rel_ospath())
+				.await
+				.map_err(fs_error)
+		}
+		.boxed()
+	}
+
+	fn copy<'a>(&'a self, from: &'a DavPath, to: &'a DavPath) -> FsFuture<'a, ()> {
+		async move {
+			let fs = self.fs();
+			let to = to.as_rel_ospath();
+
+			let mut reader = fs.read_file(from.as_rel_ospath()).await.map_err(fs_error)?;
+
+			if let Err(FileManagerError::NotFound) = fs.stat(to).await {
+				fs.create_file(to).await.map_err(fs_error)?;
+			}
+
+			let mut writer = fs.write_file(to).await.map_err(fs_error)?;
+
+			tokio::io::copy(&mut reader, &mut writer)
+				.await
+				.map_err(io_error)?;
+
+			writer.commit().await.map_err(fs_error)
+		}
+		.boxed()
+	}
+
+	fn get_quota(&self) -> FsFuture<'_, (u64, Option<u64>)> {
+		async move {
+			let usage = self.server.get_disk_usage();
+			let used = usage.used().await.map_err(fs_error)?;
+
+			Ok((used, usage.quota()))
+		}
+		.boxed()
+	}
+}
+
+#[derive(Debug, Clone)]
+struct DavEntryMeta {
+	is_dir: bool,
+	size: u64,
+	modified: Option<i64>,
+	etag: Option<String>,
+}
+
+impl From<&FSEntry> for DavEntryMeta {
+	fn from(entry: &FSEntry) -> Self {
+		match entry {
+			FSEntry::File(file) => Self {
+				is_dir: false,
+				size: file.size,
+				modified: file.modified,
+				etag: Some(file.etag.clone()),
+			},
+			FSEntry::Dir(dir) => Self {
+				is_dir: true,
+				size: 0,
+				modified: dir.modified,
+				etag: None,
+			},
+		}
+	}
+}
+
+impl DavMetaData for DavEntryMeta {
+	fn len(&self) -> u64 {
+		self.size
+	}
+
+	fn modified(&self) -> FsResult<SystemTime> {
+		self.modified
+			.and_then(|seconds| u64::try_from(seconds).ok())
+			.map(|seconds| UNIX_EPOCH + Duration::from_secs(seconds))
+			.ok_or(FsError::NotImplemented)
+	}
+
+	fn is_dir(&self) -> bool {
+		self.is_dir
+	}
+
+	fn etag(&self) -> Option<String> {
+		// Use the same entity tags as the files API for files
+		self.etag.clone()
+	}
+}
+
+struct DavEntry {
+	name: String,
+	meta: DavEntryMeta,
+}
+
+impl DavDirEntry for DavEntry {
+	fn name(&self) -> Vec<u8> {
+		self.name.as_bytes().to_vec()
+	}
+
+	fn metadata(&self) -> FsFuture<'_, Box<dyn DavMetaData>> {
+		let meta: Box<dyn DavMetaData> = Box::new(self.meta.clone());
+		async move { Ok(meta) }.boxed()
+	}
+}
+
+enum DavFileState {
+	Read(FileReader),
+	/// The writer is committed and taken when the file is flushed
+	Write(Option<FileWriter>),
+}
+
+struct DavFileHandle {
+	fs: Arc<dyn FileManager>,
+	path: PathBuf,
+	state: DavFileState,
+	/// The file didn't exist before it was opened for writing
+	created: bool,
+}
+
+impl fmt::Debug for DavFileHandle {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("DavFileHandle")
+			.field("path", &self.path)
+			.finish_non_exhaustive()
+	}
+}
+
+impl DavFileHandle {
+	/// Internal: Write to the staged file. If writing fails, a file created when opening is removed
+	/// again, so failed uploads don't leave empty files behind.
+	async fn write(&mut self, data: &[u8]) -> FsResult<()> {
+		let DavFileState::Write(Some(writer)) = &mut self.state else {
+			return Err(FsError::GeneralFailure);
+		};
+
+		let Err(err) = writer.write_all(data).await else {
+			return Ok(());
+		};
+
+		// Dropping the writer discards the staged file
+		self.state = DavFileState::Write(None);
+
+		if self.created {
+			if let Err(err) = self.fs.delete(&self.path).await {
+				tracing::warn!(
+					"Failed to remove {} after a failed write: {}",
+					self.path.display(),
+					err
+				);
+			}
+		}
+
+		Err(io_error(err))
+	}
+}
+
+impl DavFile for DavFileHandle {
+	fn metadata(&mut self) -> FsFuture<'_, Box<dyn DavMetaData>> {
+		async move {
+			let entry = self.fs.stat(&self.path).await.map_err(fs_error)?;
+			Ok(Box::new(DavEntryMeta::from(&entry)) as Box<dyn DavMetaData>)
+		}
+		.boxed()
+	}
+
+	fn write_buf(&mut self, mut buf: Box<dyn Buf + Send>) -> FsFuture<'_, ()> {
+		async move {
+			let bytes = buf.copy_to_bytes(buf.remaining());
+			self.write(&bytes).await
+		}
+		.boxed()
+	}
+
+	fn write_bytes(&mut self, buf: Bytes) -> FsFuture<'_, ()> {
+		async move { self.write(&buf).await }.boxed()
+	}
+
+	fn read_bytes(&mut self, count: usize) -> FsFuture<'_, Bytes> {
+		async move {
+			let DavFileState::Read(reader) = &mut self.state else {
+				return Err(FsError::GeneralFailure);
+			};
+
+			let mut buf = BytesMut::zeroed(count);
+			let read = reader.read(&mut buf).await.map_err(io_error)?;
+			buf.truncate(read);
+
+			Ok(buf.freeze())
+		}
+		.boxed()
+	}
+
+	fn seek(&mut self, pos: SeekFrom) -> FsFuture<'_, u64> {
+		async move {
+			match &mut self.state {
+				DavFileState::Read(reader) => reader.seek(pos).await.map_err(io_error),
+				// The staged file is written as a stream
+				DavFileState::Write(_) => Err(FsError::NotImplemented),
+			}
+		}
+		.boxed()
+	}
+
+	fn flush(&mut self) -> FsFuture<'_, ()> {
+		async move {
+			match &mut self.state {
+				DavFileState::Write(writer) => match writer.take() {
+					Some(writer) => writer.commit().await.map_err(fs_error),
+					None => Ok(()),
+				},
+				DavFileState::Read(_) => Ok(()),
+			}
+		}
+		.boxed()
+	}
+}
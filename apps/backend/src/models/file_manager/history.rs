@@ -0,0 +1,209 @@
+use crate::config::FILE_HISTORY_MAX_FILE_SIZE;
+use crate::models::file_manager::normalize_relative;
+use crate::models::file_manager::types::FileManagerError;
+use crate::models::file_schemas::server_config::FileHistoryConfig;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use time::OffsetDateTime;
+use ts_rs::TS;
+
+/// A previous version of a file
+#[derive(TS, Debug, Clone, Serialize)]
+#[ts(export)]
+pub struct FileVersion {
+	/// Version identifier, the time the version was recorded in Unix milliseconds
+	pub version: u64,
+	pub size: u64,
+}
+
+struct HistoryState {
+	config: FileHistoryConfig,
+	globs: GlobSet,
+}
+
+/// Keeps previous versions of text files in a directory outside of the server root. Versions
+/// are recorded right before a file is replaced.
+pub struct FileHistory {
+	dir: PathBuf,
+	state: RwLock<HistoryState>,
+}
+
+impl FileHistory {
+	pub fn new(dir: PathBuf, config: FileHistoryConfig) -> Self {
+		let globs = Self::compile_globs(&config.globs).unwrap_or_else(|err| {
+			tracing::warn!("Invalid file history globs, history is disabled: {}", err);
+			GlobSet::empty()
+		});
+
+		Self {
+			dir,
+			state: RwLock::new(HistoryState { config, globs }),
+		}
+	}
+
+	/// Compile a list of globs, returning an error message for the first invalid glob
+	pub fn compile_globs(globs: &[String]) -> Result<GlobSet, String> {
+		let mut builder = GlobSetBuilder::new();
+
+		for glob in globs {
+			builder.add(Glob::new(glob).map_err(|err| err.to_string())?);
+		}
+
+		builder.build().map_err(|err| err.to_string())
+	}
+
+	/// Replace the history settings
+	pub fn set_config(&self, config: FileHistoryConfig) -> Result<(), String> {
+		let globs = Self::compile_globs(&config.globs)?;
+		let mut state = self
+			.state
+			.write()
+			.expect("History lock should not be poisoned");
+		*state = HistoryState { config, globs };
+		Ok(())
+	}
+
+	/// Whether history is kept for a server-relative path
+	pub fn is_tracked(&self, relative_path: &Path) -> bool {
+		let state = self
+			.state
+			.read()
+			.expect("History lock should not be poisoned");
+		state.config.enabled && state.globs.is_match(normalize_relative(relative_path))
+	}
+
+	/// Record the current content of `absolute_path` as a version of `relative_path`. Does
+	/// nothing if the path is not tracked, does not exist, is too large or is not UTF-8 text.
+	pub async fn snapshot(
+		&self,
+		relative_path: &Path,
+		absolute_path: &Path,
+	) -> Result<(), FileManagerError> {
+		if !self.is_tracked(relative_path) {
+			return Ok(());
+		}
+
+		let Ok(metadata) = tokio::fs::metadata(absolute_path).await else {
+			return Ok(());
+		};
+
+		if !metadata.is_file() || metadata.len() > FILE_HISTORY_MAX_FILE_SIZE {
+			return Ok(());
+		}
+
+		let content = tokio::fs::read(absolute_path)
+			.await
+			.map_err(FileManagerError::IoError)?;
+
+		if std::str::from_utf8(&content).is_err() {
+			return Ok(());
+		}
+
+		let versions = self.list(relative_path).await?;
+
+		// Skip recording a version identical to the most recent one
+		if let Some(latest) = versions.first() {
+			if self.read_version(relative_path, latest.version).await? == content {
+				return Ok(());
+			}
+		}
+
+		let file_dir = self.file_dir(relative_path);
+		tokio::fs::create_dir_all(&file_dir)
+			.await
+			.map_err(FileManagerError::IoError)?;
+
+		let now_millis = OffsetDateTime::now_utc().unix_timestamp_nanos() / 1_000_000;
+		let mut version = u64::try_from(now_millis).unwrap_or_default();
+
+		// Versions written within the same millisecond must not overwrite each other
+		if let Some(latest) = versions.first() {
+			version = version.max(latest.version + 1);
+		}
+
+		tokio::fs::write(file_dir.join(version.to_string()), &content)
+			.await
+			.map_err(FileManagerError::IoError)?;
+
+		self.prune(relative_path).await
+	}
+
+	/// List the recorded versions of a file, newest first
+	pub async fn list(&self, relative_path: &Path) -> Result<Vec<FileVersion>, FileManagerError> {
+		let file_dir = self.file_dir(relative_path);
+
+		let mut dir = match tokio::fs::read_dir(&file_dir).await {
+			Ok(dir) => dir,
+			Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+			Err(err) => return Err(FileManagerError::IoError(err)),
+		};
+
+		let mut versions = Vec::new();
+
+		while let Some(entry) = dir.next_entry().await.map_err(FileManagerError::IoError)? {
+			let Some(version) = entry
+				.file_name()
+				.to_str()
+				.and_then(|name| name.parse::<u64>().ok())
+			else {
+				continue;
+			};
+
+			let metadata = entry.metadata().await.map_err(FileManagerError::IoError)?;
+
+			versions.push(FileVersion {
+				version,
+				size: metadata.len(),
+			});
+		}
+
+		versions.sort_by_key(|version| std::cmp::Reverse(version.version));
+
+		Ok(versions)
+	}
+
+	/// Read the content of a recorded version
+	pub async fn read_version(
+		&self,
+		relative_path: &Path,
+		version: u64,
+	) -> Result<Vec<u8>, FileManagerError> {
+		let path = self.file_dir(relative_path).join(version.to_string());
+
+		tokio::fs::read(path).await.map_err(|err| match err.kind() {
+			std::io::ErrorKind::NotFound => FileManagerError::NotFound,
+			_ => FileManagerError::IoError(err),
+		})
+	}
+
+	/// Internal: Remove versions beyond the configured maximum
+	async fn prune(&self, relative_path: &Path) -> Result<(), FileManagerError> {
+		let max_versions = {
+			let state = self
+				.state
+				.read()
+				.expect("History lock should not be poisoned");
+			state.config.max_versions
+		};
+
+		let file_dir = self.file_dir(relative_path);
+
+		for old_version in self.list(relative_path).await?.iter().skip(max_versions) {
+			tokio::fs::remove_file(file_dir.join(old_version.version.to_string()))
+				.await
+				.map_err(FileManagerError::IoError)?;
+		}
+
+		Ok(())
+	}
+
+	/// Internal: Directory holding the versions of a file, keyed by a hash of its path
+	fn file_dir(&self, relative_path: &Path) -> PathBuf {
+		let normalized = normalize_relative(relative_path);
+		let hash = Sha256::digest(normalized.to_string_lossy().as_bytes());
+		self.dir.join(hex::encode(hash))
+	}
+}
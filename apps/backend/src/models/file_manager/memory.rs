@@ -0,0 +1,519 @@
+use crate::models::file_manager::types::FileManagerError::{NoPermission, NotFound};
+use crate::models::file_manager::types::{
+	FSDirectoryEntry, FSEntry, FSFileEntry, FileManagerError,
+};
+use crate::models::file_manager::{FileManager, FileReader, FileWrite, FileWriter};
+use async_trait::async_trait;
+use bytes::Bytes;
+use path_clean::clean;
+use std::collections::BTreeMap;
+use std::io::{self, Cursor, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::task::{Context, Poll};
+use std::time::SystemTime;
+use time::OffsetDateTime;
+use tokio::io::AsyncWrite;
+
+/// Entries by their path relative to the root. The root itself is stored under an empty path.
+type Entries = BTreeMap<PathBuf, MemoryEntry>;
+
+#[derive(Clone)]
+struct MemoryEntry {
+	/// Content of a file, `None` for directories
+	content: Option<Bytes>,
+	created: SystemTime,
+	modified: SystemTime,
+}
+
+impl MemoryEntry {
+	fn new(content: Option<Bytes>) -> Self {
+		let now = SystemTime::now();
+
+		Self {
+			content,
+			created: now,
+			modified: now,
+		}
+	}
+
+	fn is_dir(&self) -> bool {
+		self.content.is_none()
+	}
+}
+
+/// File manager that keeps its files in memory, e.g. to serve generated content or the
+/// content of an archive without touching the disk
+pub struct MemoryFileManager {
+	entries: Arc<RwLock<Entries>>,
+}
+
+impl Default for MemoryFileManager {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl MemoryFileManager {
+	/// Create a file manager with an empty root directory
+	pub fn new() -> Self {
+		let mut entries = Entries::new();
+		entries.insert(PathBuf::new(), MemoryEntry::new(None));
+
+		Self {
+			entries: Arc::new(RwLock::new(entries)),
+		}
+	}
+
+	/// Ensure the provided path stays below the root and normalize it
+	fn normalize_path(path: &Path) -> Result<PathBuf, FileManagerError> {
+		let clean_path = clean(path);
+
+		if clean_path.is_absolute() || clean_path.starts_with("..") {
+			return Err(NoPermission);
+		}
+
+		if clean_path == Path::new(".") {
+			Ok(PathBuf::new())
+		} else {
+			Ok(clean_path)
+		}
+	}
+
+	fn read_entries(&self) -> RwLockReadGuard<'_, Entries> {
+		self.entries
+			.read()
+			.expect("Memory file manager lock should not be poisoned")
+	}
+
+	fn write_entries(&self) -> RwLockWriteGuard<'_, Entries> {
+		self.entries
+			.write()
+			.expect("Memory file manager lock should not be poisoned")
+	}
+
+	/// Prevent mutating the root itself
+	fn ensure_not_root(path: &Path) -> Result<(), FileManagerError> {
+		if path.as_os_str().is_empty() {
+			Err(NoPermission)
+		} else {
+			Ok(())
+		}
+	}
+
+	/// Ensure the directory a path would be created in exists
+	fn ensure_parent_dir(entries: &Entries, path: &Path) -> Result<(), FileManagerError> {
+		let parent = path.parent().ok_or(NoPermission)?;
+
+		match entries.get(parent) {
+			Some(entry) if entry.is_dir() => Ok(()),
+			Some(_) => Err(Self::io_error(ErrorKind::NotADirectory)),
+			None => Err(NotFound),
+		}
+	}
+
+	fn io_error(kind: ErrorKind) -> FileManagerError {
+		FileManagerError::IoError(io::Error::from(kind))
+	}
+
+	/// Remove an entry and everything below it
+	fn remove_tree(entries: &mut Entries, path: &Path) {
+		entries.retain(|entry_path, _| !entry_path.starts_with(path));
+	}
+
+	/// Take an entry and everything below it out of the tree, keyed by their path relative to
+	/// the entry
+	fn take_tree(entries: &mut Entries, path: &Path) -> Vec<(PathBuf, MemoryEntry)> {
+		let paths: Vec<PathBuf> = entries
+			.keys()
+			.filter(|entry_path| entry_path.starts_with(path))
+			.cloned()
+			.collect();
+
+		paths
+			.into_iter()
+			.filter_map(|entry_path| {
+				let entry = entries.remove(&entry_path)?;
+				let relative = entry_path.strip_prefix(path).ok()?.to_path_buf();
+				Some((relative, entry))
+			})
+			.collect()
+	}
+
+	/// Join a relative path to a base path without adding a trailing separator for empty paths
+	fn join(base: &Path, relative: &Path) -> PathBuf {
+		if relative.as_os_str().is_empty() {
+			base.to_path_buf()
+		} else {
+			base.join(relative)
+		}
+	}
+
+	fn build_entry(entries: &Entries, name: String, path: &Path, entry: &MemoryEntry) -> FSEntry {
+		let modified = Self::unix_timestamp(entry.modified);
+		let created = Self::unix_timestamp(entry.created);
+
+		match &entry.content {
+			Some(content) => FSEntry::File(FSFileEntry {
+				name,
+				size: content.len() as u64,
+				modified: Some(modified),
+				created: Some(created),
+				permissions: None,
+				symlink: false,
+				etag: Self::etag(content, entry.modified),
+			}),
+			None => FSEntry::Dir(FSDirectoryEntry {
+				name,
+				modified: Some(modified),
+				created: Some(created),
+				permissions: None,
+				symlink: false,
+				children: Some(Self::children(entries, path).count() as u64),
+			}),
+		}
+	}
+
+	/// Iterate over the direct children of a directory
+	fn children<'a>(
+		entries: &'a Entries,
+		path: &'a Path,
+	) -> impl Iterator<Item = (&'a PathBuf, &'a MemoryEntry)> {
+		entries
+			.iter()
+			.filter(move |(entry_path, _)| entry_path.parent() == Some(path))
+	}
+
+	/// Derive an entity tag from a file's size and modification time, like files on disk
+	fn etag(content: &Bytes, modified: SystemTime) -> String {
+		let modified_nanos = modified
+			.duration_since(SystemTime::UNIX_EPOCH)
+			.map_or(0, |duration| duration.as_nanos());
+
+		format!("{:x}-{:x}", content.len(), modified_nanos)
+	}
+
+	fn unix_timestamp(time: SystemTime) -> i64 {
+		OffsetDateTime::from(time).unix_timestamp()
+	}
+
+	/// Internal: Read a file or directory tree from disk, keyed by path relative to `source`
+	fn read_disk_tree(source: &Path) -> io::Result<Vec<(PathBuf, Option<Bytes>)>> {
+		let mut tree = Vec::new();
+		let mut pending = vec![PathBuf::new()];
+
+		while let Some(relative) = pending.pop() {
+			let path = Self::join(source, &relative);
+
+			if std::fs::metadata(&path)?.is_dir() {
+				for dir_entry in std::fs::read_dir(&path)? {
+					pending.push(relative.join(dir_entry?.file_name()));
+				}
+
+				tree.push((relative, None));
+			} else {
+				tree.push((relative, Some(Bytes::from(std::fs::read(&path)?))));
+			}
+		}
+
+		Ok(tree)
+	}
+
+	/// Internal: Write a tree taken from memory to disk. Parents must come before their
+	/// children.
+	fn write_disk_tree(destination: &Path, tree: &[(PathBuf, MemoryEntry)]) -> io::Result<()> {
+		for (relative, entry) in tree {
+			let path = Self::join(destination, relative);
+
+			match &entry.content {
+				Some(content) => std::fs::write(path, content)?,
+				None => std::fs::create_dir(path)?,
+			}
+		}
+
+		Ok(())
+	}
+}
+
+#[async_trait]
+impl FileManager for MemoryFileManager {
+	async fn read_file(&self, path: &Path) -> Result<FileReader, FileManagerError> {
+		let path = Self::normalize_path(path)?;
+		let entries = self.read_entries();
+
+		let content = entries
+			.get(&path)
+			.ok_or(NotFound)?
+			.content
+			.clone()
+			.ok_or_else(|| Self::io_error(ErrorKind::IsADirectory))?;
+
+		Ok(Box::new(Cursor::new(content)))
+	}
+
+	async fn write_file(&self, path: &Path) -> Result<FileWriter, FileManagerError> {
+		let path = Self::normalize_path(path)?;
+
+		match self.read_entries().get(&path) {
+			Some(entry) if entry.is_dir() => return Err(Self::io_error(ErrorKind::IsADirectory)),
+			Some(_) => {}
+			None => return Err(NotFound),
+		}
+
+		Ok(Box::new(MemoryFileWriter {
+			entries: self.entries.clone(),
+			path,
+			content: Vec::new(),
+		}))
+	}
+
+	async fn delete(&self, path: &Path) -> Result<(), FileManagerError> {
+		let path = Self::normalize_path(path)?;
+		Self::ensure_not_root(&path)?;
+
+		let mut entries = self.write_entries();
+
+		if !entries.contains_key(&path) {
+			return Err(NotFound);
+		}
+
+		Self::remove_tree(&mut entries, &path);
+
+		Ok(())
+	}
+
+	async fn create_dir(&self, path: &Path) -> Result<(), FileManagerError> {
+		let path = Self::normalize_path(path)?;
+		let mut entries = self.write_entries();
+
+		if entries.contains_key(&path) {
+			return Err(FileManagerError::AlreadyExists);
+		}
+
+		Self::ensure_parent_dir(&entries, &path)?;
+		entries.insert(path, MemoryEntry::new(None));
+
+		Ok(())
+	}
+
+	async fn create_file(&self, path: &Path) -> Result<(), FileManagerError> {
+		let path = Self::normalize_path(path)?;
+		let mut entries = self.write_entries();
+
+		Self::ensure_parent_dir(&entries, &path)?;
+
+		// Creating an existing file truncates it, like on disk
+		match entries.get_mut(&path) {
+			Some(entry) if entry.is_dir() => Err(Self::io_error(ErrorKind::IsADirectory)),
+			Some(entry) => {
+				entry.content = Some(Bytes::new());
+				entry.modified = SystemTime::now();
+				Ok(())
+			}
+			None => {
+				entries.insert(path, MemoryEntry::new(Some(Bytes::new())));
+				Ok(())
+			}
+		}
+	}
+
+	async fn list_dir(&self, path: &Path) -> Result<Vec<FSEntry>, FileManagerError> {
+		let path = Self::normalize_path(path)?;
+		let entries = self.read_entries();
+
+		match entries.get(&path) {
+			Some(entry) if entry.is_dir() => {}
+			Some(_) => return Err(Self::io_error(ErrorKind::NotADirectory)),
+			None => return Err(NotFound),
+		}
+
+		Self::children(&entries, &path)
+			.map(|(entry_path, entry)| {
+				let name = entry_path
+					.file_name()
+					.and_then(|name| name.to_str())
+					.ok_or(FileManagerError::EncodingError)?
+					.to_string();
+
+				Ok(Self::build_entry(&entries, name, entry_path, entry))
+			})
+			.collect()
+	}
+
+	async fn relocate(&self, path: &Path, new_path: &Path) -> Result<(), FileManagerError> {
+		let path = Self::normalize_path(path)?;
+		let new_path = Self::normalize_path(new_path)?;
+		Self::ensure_not_root(&path)?;
+		Self::ensure_not_root(&new_path)?;
+
+		let mut entries = self.write_entries();
+
+		let source_is_dir = entries.get(&path).ok_or(NotFound)?.is_dir();
+
+		if path == new_path {
+			return Ok(());
+		}
+
+		if new_path.starts_with(&path) {
+			return Err(Self::io_error(ErrorKind::InvalidInput));
+		}
+
+		Self::ensure_parent_dir(&entries, &new_path)?;
+
+		// Like a rename on disk, a file may replace another file
+		match entries.get(&new_path) {
+			Some(existing) if source_is_dir || existing.is_dir() => {
+				return Err(FileManagerError::AlreadyExists)
+			}
+			Some(_) | None => {}
+		}
+
+		let tree = Self::take_tree(&mut entries, &path);
+		Self::remove_tree(&mut entries, &new_path);
+
+		for (relative, entry) in tree {
+			entries.insert(Self::join(&new_path, &relative), entry);
+		}
+
+		Ok(())
+	}
+
+	async fn import(&self, source: &Path, path: &Path) -> Result<(), FileManagerError> {
+		let path = Self::normalize_path(path)?;
+		Self::ensure_not_root(&path)?;
+		Self::ensure_parent_dir(&self.read_entries(), &path)?;
+
+		let source = source.to_path_buf();
+		let tree = tokio::task::spawn_blocking(move || {
+			let tree = Self::read_disk_tree(&source)?;
+
+			if source.is_dir() {
+				std::fs::remove_dir_all(&source)?;
+			} else {
+				std::fs::remove_file(&source)?;
+			}
+
+			Ok::<_, io::Error>(tree)
+		})
+		.await
+		.map_err(|err| FileManagerError::IoError(io::Error::other(err)))?
+		.map_err(FileManagerError::IoError)?;
+
+		let mut entries = self.write_entries();
+		Self::remove_tree(&mut entries, &path);
+
+		for (relative, content) in tree {
+			entries.insert(Self::join(&path, &relative), MemoryEntry::new(content));
+		}
+
+		Ok(())
+	}
+
+	async fn export(&self, path: &Path, destination: &Path) -> Result<(), FileManagerError> {
+		let path = Self::normalize_path(path)?;
+		Self::ensure_not_root(&path)?;
+
+		let tree = {
+			let mut entries = self.write_entries();
+
+			if !entries.contains_key(&path) {
+				return Err(NotFound);
+			}
+
+			Self::take_tree(&mut entries, &path)
+		};
+
+		let destination = destination.to_path_buf();
+		let written = tokio::task::spawn_blocking(move || {
+			let result = Self::write_disk_tree(&destination, &tree);
+			(result, tree)
+		})
+		.await
+		.map_err(|err| FileManagerError::IoError(io::Error::other(err)))?;
+
+		// Put the entries back if they couldn't be written
+		if let (Err(err), tree) = written {
+			let mut entries = self.write_entries();
+
+			for (relative, entry) in tree {
+				entries.insert(Self::join(&path, &relative), entry);
+			}
+
+			return Err(FileManagerError::IoError(err));
+		}
+
+		Ok(())
+	}
+
+	async fn stat(&self, path: &Path) -> Result<FSEntry, FileManagerError> {
+		let path = Self::normalize_path(path)?;
+		let entries = self.read_entries();
+		let entry = entries.get(&path).ok_or(NotFound)?;
+
+		let name = match path.file_name() {
+			Some(name) => name
+				.to_str()
+				.ok_or(FileManagerError::EncodingError)?
+				.to_string(),
+			None => String::new(),
+		};
+
+		Ok(Self::build_entry(&entries, name, &path, entry))
+	}
+}
+
+/// Writer that buffers new content in memory and stores it in the tree on `commit`
+struct MemoryFileWriter {
+	entries: Arc<RwLock<Entries>>,
+	path: PathBuf,
+	content: Vec<u8>,
+}
+
+#[async_trait]
+impl FileWrite for MemoryFileWriter {
+	async fn commit(self: Box<Self>) -> Result<(), FileManagerError> {
+		let mut entries = self
+			.entries
+			.write()
+			.expect("Memory file manager lock should not be poisoned");
+
+		MemoryFileManager::ensure_parent_dir(&entries, &self.path)?;
+
+		let content = Bytes::from(self.content);
+
+		match entries.get_mut(&self.path) {
+			Some(entry) if entry.is_dir() => {
+				Err(MemoryFileManager::io_error(ErrorKind::IsADirectory))
+			}
+			Some(entry) => {
+				entry.content = Some(content);
+				entry.modified = SystemTime::now();
+				Ok(())
+			}
+			None => {
+				entries.insert(self.path, MemoryEntry::new(Some(content)));
+				Ok(())
+			}
+		}
+	}
+}
+
+impl AsyncWrite for MemoryFileWriter {
+	fn poll_write(
+		mut self: Pin<&mut Self>,
+		_cx: &mut Context<'_>,
+		buf: &[u8],
+	) -> Poll<io::Result<usize>> {
+		self.content.extend_from_slice(buf);
+		Poll::Ready(Ok(buf.len()))
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+		Poll::Ready(Ok(()))
+	}
+
+	fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+		Poll::Ready(Ok(()))
+	}
+}
@@ -1,19 +1,113 @@
-use crate::models::file_manager::types::{FSEntry, FileManagerError};
+use crate::models::file_manager::types::{
+	FSEntry, FSListOptions, FSListing, FSSortField, FSSortOrder, FSWalkEntry, FileManagerError,
+	SortKey,
+};
 use async_trait::async_trait;
-use std::path::Path;
-use tokio::fs::File;
-use tokio::io::{BufReader, BufWriter};
+use globset::{Glob, GlobMatcher};
+use path_clean::clean;
+use std::cmp::Ordering;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncBufRead, AsyncSeek, AsyncWrite};
+use watch::FSWatch;
 
+pub mod dav;
+pub mod history;
+pub mod memory;
+pub mod policy;
 pub mod scoped;
+pub mod search;
+pub mod trash;
 pub mod types;
+pub mod usage;
+pub mod watch;
+pub mod writer;
+
+/// Normalize a path relative to a file manager's root, so equivalent spellings such as
+/// `/a/../b` and `b` compare equal
+pub fn normalize_relative(path: &Path) -> PathBuf {
+	let cleaned = clean(Path::new("/").join(path));
+
+	cleaned
+		.strip_prefix("/")
+		.map(Path::to_path_buf)
+		.unwrap_or(cleaned)
+}
+
+/// Compile the glob that entry names of a listing must match
+pub fn listing_filter(options: &FSListOptions) -> Result<Option<GlobMatcher>, FileManagerError> {
+	options
+		.filter
+		.as_deref()
+		.map(|filter| {
+			Glob::new(filter)
+				.map(|glob| glob.compile_matcher())
+				.map_err(|err| FileManagerError::InvalidPattern(err.to_string()))
+		})
+		.transpose()
+}
+
+/// Sort the entries of a listing as `options` asks, with directories first
+pub fn sort_listing<T>(
+	entries: &mut [T],
+	options: &FSListOptions,
+	key: impl Fn(&T) -> SortKey<'_>,
+) {
+	entries.sort_by(|a, b| {
+		let (a, b) = (key(a), key(b));
+		let dirs_first = b.is_dir.cmp(&a.is_dir);
+
+		let by_field = match options.sort {
+			FSSortField::Name => Ordering::Equal,
+			FSSortField::Size => a.size.cmp(&b.size),
+			FSSortField::Modified => a.modified.cmp(&b.modified),
+			FSSortField::Created => a.created.cmp(&b.created),
+		}
+		.then_with(|| a.name.cmp(b.name));
+
+		let by_field = match options.order {
+			FSSortOrder::Asc => by_field,
+			FSSortOrder::Desc => by_field.reverse(),
+		};
+
+		dirs_first.then(by_field)
+	});
+}
+
+/// Get the range of the requested page among `total` sorted entries, and the cursor of the
+/// next page if there are more entries
+pub fn listing_page(total: usize, options: &FSListOptions) -> (Range<usize>, Option<usize>) {
+	let start = options.cursor.unwrap_or(0).min(total);
+	let end = options
+		.limit
+		.map_or(total, |limit| start.saturating_add(limit).min(total));
+
+	(start..end, (end < total).then_some(end))
+}
+
+/// Buffered, seekable stream of a file's content
+pub trait FileRead: AsyncBufRead + AsyncSeek + Send + Sync + Unpin {}
+
+impl<T: AsyncBufRead + AsyncSeek + Send + Sync + Unpin> FileRead for T {}
+
+/// Stream of new content for a file. The file is only replaced once the writer is committed;
+/// dropping the writer without committing discards the content.
+#[async_trait]
+pub trait FileWrite: AsyncWrite + Send + Sync + Unpin {
+	/// Replace the file with the written content
+	async fn commit(self: Box<Self>) -> Result<(), FileManagerError>;
+}
+
+pub type FileReader = Box<dyn FileRead>;
+pub type FileWriter = Box<dyn FileWrite>;
 
 #[async_trait]
 pub trait FileManager: Send + Sync {
 	/// Get a read buffer to a file
-	async fn read_file(&self, path: &Path) -> Result<BufReader<File>, FileManagerError>;
+	async fn read_file(&self, path: &Path) -> Result<FileReader, FileManagerError>;
 
-	/// Get a write buffer to a file
-	async fn write_file(&self, path: &Path) -> Result<BufWriter<File>, FileManagerError>;
+	/// Get a writer to a file. The file is only replaced once the writer is committed.
+	async fn write_file(&self, path: &Path) -> Result<FileWriter, FileManagerError>;
 
 	/// Delete a file or directory
 	async fn delete(&self, path: &Path) -> Result<(), FileManagerError>;
@@ -27,9 +121,68 @@ pub trait FileManager: Send + Sync {
 	/// List the contents of a directory
 	async fn list_dir(&self, path: &Path) -> Result<Vec<FSEntry>, FileManagerError>;
 
+	/// List the contents of a directory for walking a tree. Unlike `list_dir`, directories don't
+	/// have their children counted.
+	async fn walk_dir(&self, path: &Path) -> Result<Vec<FSWalkEntry>, FileManagerError> {
+		let entries = self.list_dir(path).await?;
+
+		Ok(entries
+			.into_iter()
+			.map(|entry| match entry {
+				FSEntry::File(file) => FSWalkEntry::File {
+					name: file.name,
+					size: file.size,
+				},
+				FSEntry::Dir(dir) => FSWalkEntry::Dir {
+					name: dir.name,
+					symlink: dir.symlink,
+				},
+			})
+			.collect())
+	}
+
 	/// Move a file or directory
 	async fn relocate(&self, path: &Path, new_path: &Path) -> Result<(), FileManagerError>;
 
+	/// Move a file or directory from outside of the file manager into it, replacing any existing
+	/// file at `path`. The source should be on the same filesystem so the move is atomic.
+	async fn import(&self, source: &Path, path: &Path) -> Result<(), FileManagerError>;
+
+	/// Move a file or directory out of the file manager to `destination`. The destination should
+	/// be on the same filesystem so the move is atomic.
+	async fn export(&self, path: &Path, destination: &Path) -> Result<(), FileManagerError>;
+
 	/// Get information about a file or directory.
 	async fn stat(&self, path: &Path) -> Result<FSEntry, FileManagerError>;
+
+	/// Watch a file or directory and everything below it for changes
+	async fn watch(&self, _path: &Path) -> Result<FSWatch, FileManagerError> {
+		Err(FileManagerError::Unsupported)
+	}
+
+	/// List the contents of a directory, filtered, sorted and paginated according to `options`.
+	/// Directories are always listed before files.
+	async fn list_dir_with(
+		&self,
+		path: &Path,
+		options: &FSListOptions,
+	) -> Result<FSListing, FileManagerError> {
+		let mut entries = self.list_dir(path).await?;
+
+		if let Some(matcher) = listing_filter(options)? {
+			entries.retain(|entry| matcher.is_match(entry.name()));
+		}
+
+		sort_listing(&mut entries, options, FSEntry::sort_key);
+
+		let total = entries.len();
+		let (page, next_cursor) = listing_page(total, options);
+		let entries: Vec<FSEntry> = entries.drain(page).collect();
+
+		Ok(FSListing {
+			entries,
+			total,
+			next_cursor,
+		})
+	}
 }
@@ -0,0 +1,110 @@
+use crate::config::SERVER_CONFIG_FILE_NAME;
+use crate::models::file_manager::normalize_relative;
+use crate::models::file_manager::types::FileManagerError;
+use crate::models::file_schemas::server_config::{PathAccess, PathRule};
+use globset::{Glob, GlobMatcher};
+use std::path::Path;
+use std::sync::RwLock;
+
+/// Rules that always apply, protecting files the panel manages itself. User rules can make
+/// these paths more restricted but never more accessible.
+fn builtin_rules() -> Vec<PathRule> {
+	vec![
+		// Changes must go through the config API so they are validated
+		PathRule {
+			glob: SERVER_CONFIG_FILE_NAME.to_string(),
+			access: PathAccess::ReadOnly,
+		},
+	]
+}
+
+/// Decides which paths below a file manager's root are hidden, read-only or writable
+pub struct PathPolicy {
+	builtin: Vec<(GlobMatcher, PathAccess)>,
+	rules: RwLock<Vec<(GlobMatcher, PathAccess)>>,
+}
+
+impl PathPolicy {
+	pub fn new(rules: &[PathRule]) -> Self {
+		let builtin = Self::compile_rules(&builtin_rules()).expect("Built-in path rules are valid");
+
+		let rules = Self::compile_rules(rules).unwrap_or_else(|err| {
+			tracing::warn!("Invalid path rules, only built-in rules apply: {}", err);
+			Vec::new()
+		});
+
+		Self {
+			builtin,
+			rules: RwLock::new(rules),
+		}
+	}
+
+	/// Compile a list of rules, returning an error message for the first invalid glob
+	pub fn compile_rules(rules: &[PathRule]) -> Result<Vec<(GlobMatcher, PathAccess)>, String> {
+		rules
+			.iter()
+			.map(|rule| {
+				Glob::new(&rule.glob)
+					.map(|glob| (glob.compile_matcher(), rule.access))
+					.map_err(|err| format!("{}: {}", rule.glob, err))
+			})
+			.collect()
+	}
+
+	/// Replace the user rules
+	pub fn set_rules(&self, rules: &[PathRule]) -> Result<(), String> {
+		let compiled = Self::compile_rules(rules)?;
+		*self
+			.rules
+			.write()
+			.expect("Path rules lock should not be poisoned") = compiled;
+		Ok(())
+	}
+
+	/// Get the access level of a path relative to the root. A path is at most as accessible as
+	/// its ancestors.
+	pub fn access(&self, relative_path: &Path) -> PathAccess {
+		let relative_path = normalize_relative(relative_path);
+		let rules = self
+			.rules
+			.read()
+			.expect("Path rules lock should not be poisoned");
+
+		relative_path
+			.ancestors()
+			.filter(|path| !path.as_os_str().is_empty())
+			.map(|path| {
+				// The last matching user rule wins, built-in rules can only restrict further
+				let user = rules
+					.iter()
+					.rev()
+					.find(|(glob, _)| glob.is_match(path))
+					.map_or(PathAccess::Writable, |(_, access)| *access);
+
+				self.builtin
+					.iter()
+					.filter(|(glob, _)| glob.is_match(path))
+					.map(|(_, access)| *access)
+					.fold(user, PathAccess::min)
+			})
+			.min()
+			.unwrap_or(PathAccess::Writable)
+	}
+
+	/// Ensure a path may be seen. Hidden paths are reported as not found.
+	pub fn ensure_visible(&self, relative_path: &Path) -> Result<(), FileManagerError> {
+		match self.access(relative_path) {
+			PathAccess::Hidden => Err(FileManagerError::NotFound),
+			PathAccess::ReadOnly | PathAccess::Writable => Ok(()),
+		}
+	}
+
+	/// Ensure a path may be modified
+	pub fn ensure_writable(&self, relative_path: &Path) -> Result<(), FileManagerError> {
+		match self.access(relative_path) {
+			PathAccess::Hidden => Err(FileManagerError::NotFound),
+			PathAccess::ReadOnly => Err(FileManagerError::ReadOnly),
+			PathAccess::Writable => Ok(()),
+		}
+	}
+}
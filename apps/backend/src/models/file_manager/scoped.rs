@@ -1,21 +1,79 @@
+use crate::models::file_manager::history::FileHistory;
+use crate::models::file_manager::policy::PathPolicy;
 use crate::models::file_manager::types::FileManagerError::{NoPermission, NotFound};
 use crate::models::file_manager::types::{
-	FSDirectoryEntry, FSEntry, FSFileEntry, FileManagerError,
+	FSDirectoryEntry, FSEntry, FSFileEntry, FSListOptions, FSListing, FSSortField, FSWalkEntry,
+	FileManagerError, SortKey,
 };
-use crate::models::file_manager::FileManager;
+use crate::models::file_manager::usage::DiskUsage;
+use crate::models::file_manager::watch::FSWatch;
+use crate::models::file_manager::writer::AtomicFileWriter;
+use crate::models::file_manager::{
+	listing_filter, listing_page, sort_listing, FileManager, FileReader, FileWriter,
+};
+use crate::models::file_schemas::server_config::PathAccess;
 use async_trait::async_trait;
 use path_clean::clean;
+use std::fs::Metadata;
+use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
-use tokio::fs::{create_dir, metadata, read_dir, remove_dir_all, remove_file, rename, File};
-use tokio::io::{BufReader, BufWriter};
+use std::sync::Arc;
+use std::time::SystemTime;
+use time::OffsetDateTime;
+use tokio::fs::{
+	create_dir, metadata, read_dir, remove_dir_all, remove_file, rename, symlink_metadata, File,
+};
+use tokio::io::BufReader;
+
+/// Internal: A directory entry with what is needed to sort it in a listing. The metadata is
+/// only read if the listing is sorted by it, or to follow a symlink.
+struct SortableEntry {
+	name: String,
+	path: PathBuf,
+	is_dir: bool,
+	metadata: Option<Metadata>,
+}
+
+impl SortableEntry {
+	fn key(&self) -> SortKey<'_> {
+		let metadata = self.metadata.as_ref();
+
+		SortKey {
+			name: &self.name,
+			is_dir: self.is_dir,
+			size: metadata.filter(|_| !self.is_dir).map_or(0, Metadata::len),
+			modified: metadata
+				.and_then(|metadata| ScopedFileManager::unix_timestamp(metadata.modified())),
+			created: metadata
+				.and_then(|metadata| ScopedFileManager::unix_timestamp(metadata.created())),
+		}
+	}
+}
 
 pub struct ScopedFileManager {
 	base_path: PathBuf,
+	history: Option<Arc<FileHistory>>,
+	usage: Option<Arc<DiskUsage>>,
+	policy: Option<Arc<PathPolicy>>,
 }
 
 impl ScopedFileManager {
-	pub fn new(base_path: PathBuf) -> Self {
-		Self { base_path }
+	/// Create a file manager scoped to `base_path`. If `history` is set, previous versions of
+	/// replaced files are recorded in it. If `usage` is set, its quota is enforced on writes. If
+	/// `policy` is set, hidden paths are treated as nonexistent and read-only paths can't be
+	/// modified.
+	pub fn new(
+		base_path: PathBuf,
+		history: Option<Arc<FileHistory>>,
+		usage: Option<Arc<DiskUsage>>,
+		policy: Option<Arc<PathPolicy>>,
+	) -> Self {
+		Self {
+			base_path,
+			history,
+			usage,
+			policy,
+		}
 	}
 
 	/// Ensure the provided path is under `base_path` to prevent illegal paths and normalize it
@@ -47,38 +105,237 @@ impl ScopedFileManager {
 			Ok(())
 		}
 	}
+
+	/// Get the server-relative form of a normalized path
+	fn relative(&self, path: &Path) -> PathBuf {
+		path.strip_prefix(&self.base_path)
+			.map(Path::to_path_buf)
+			.unwrap_or_default()
+	}
+
+	/// Ensure the path policy allows seeing a normalized path
+	fn ensure_visible(&self, path: &Path) -> Result<(), FileManagerError> {
+		match &self.policy {
+			Some(policy) => policy.ensure_visible(&self.relative(path)),
+			None => Ok(()),
+		}
+	}
+
+	/// Ensure the path policy allows modifying a normalized path
+	fn ensure_writable(&self, path: &Path) -> Result<(), FileManagerError> {
+		match &self.policy {
+			Some(policy) => policy.ensure_writable(&self.relative(path)),
+			None => Ok(()),
+		}
+	}
+
+	/// Ensure the path policy allows modifying a normalized path and, for directories,
+	/// everything below it
+	async fn ensure_tree_writable(&self, path: &Path) -> Result<(), FileManagerError> {
+		self.ensure_writable(path)?;
+
+		let Some(policy) = self.policy.clone() else {
+			return Ok(());
+		};
+
+		let base_path = self.base_path.clone();
+		let path = path.to_path_buf();
+
+		tokio::task::spawn_blocking(move || Self::walk_writable(&policy, &base_path, &path))
+			.await
+			.map_err(|err| FileManagerError::IoError(std::io::Error::other(err)))?
+	}
+
+	/// Internal: Check the descendants of a directory against the path policy
+	fn walk_writable(
+		policy: &PathPolicy,
+		base_path: &Path,
+		path: &Path,
+	) -> Result<(), FileManagerError> {
+		let is_dir = std::fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_dir());
+
+		if !is_dir {
+			return Ok(());
+		}
+
+		for entry in std::fs::read_dir(path).map_err(FileManagerError::IoError)? {
+			let entry_path = entry.map_err(FileManagerError::IoError)?.path();
+			let relative_path = entry_path.strip_prefix(base_path).unwrap_or(&entry_path);
+
+			if policy.access(relative_path) != PathAccess::Writable {
+				return Err(FileManagerError::ReadOnly);
+			}
+
+			Self::walk_writable(policy, base_path, &entry_path)?;
+		}
+
+		Ok(())
+	}
+
+	/// Pair the history with the server-relative form of a normalized path
+	fn history_for(&self, path: &Path) -> Option<(Arc<FileHistory>, PathBuf)> {
+		let history = self.history.clone()?;
+		let relative_path = path.strip_prefix(&self.base_path).ok()?.to_path_buf();
+
+		Some((history, relative_path))
+	}
+
+	/// Recalculate the disk usage after an unknown amount of data was removed
+	fn invalidate_usage(&self) {
+		if let Some(usage) = &self.usage {
+			usage.invalidate();
+		}
+	}
+
+	/// Build an entry for a path. Symlinks are followed to determine the entry type.
+	async fn build_entry(name: String, path: &Path) -> Result<FSEntry, FileManagerError> {
+		let link_metadata = symlink_metadata(path)
+			.await
+			.map_err(FileManagerError::IoError)?;
+		let symlink = link_metadata.file_type().is_symlink();
+
+		let metadata = if symlink {
+			metadata(path).await.map_err(|err| match err.kind() {
+				ErrorKind::NotFound => NotFound,
+				_ => FileManagerError::IoError(err),
+			})?
+		} else {
+			link_metadata
+		};
+
+		let modified = Self::unix_timestamp(metadata.modified());
+		let created = Self::unix_timestamp(metadata.created());
+
+		#[cfg(unix)]
+		let permissions = {
+			use std::os::unix::fs::PermissionsExt;
+			Some(metadata.permissions().mode() & 0o7777)
+		};
+		#[cfg(not(unix))]
+		let permissions = None;
+
+		if metadata.is_file() {
+			Ok(FSEntry::File(FSFileEntry {
+				name,
+				size: metadata.len(),
+				modified,
+				created,
+				permissions,
+				symlink,
+				etag: Self::etag(&metadata),
+			}))
+		} else if metadata.is_dir() {
+			Ok(FSEntry::Dir(FSDirectoryEntry {
+				name,
+				modified,
+				created,
+				permissions,
+				symlink,
+				children: Self::count_children(path).await,
+			}))
+		} else {
+			Err(FileManagerError::UnknownType)
+		}
+	}
+
+	/// Internal: Get what is needed to sort an entry of `read_dir`. Returns None for special
+	/// files and broken symlinks.
+	async fn sortable_entry(
+		name: String,
+		entry: &tokio::fs::DirEntry,
+		needs_metadata: bool,
+	) -> Result<Option<SortableEntry>, FileManagerError> {
+		let file_type = entry.file_type().await.map_err(FileManagerError::IoError)?;
+		let path = entry.path();
+
+		let metadata = if needs_metadata || file_type.is_symlink() {
+			match metadata(&path).await {
+				Ok(metadata) => Some(metadata),
+				Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+				Err(err) => return Err(FileManagerError::IoError(err)),
+			}
+		} else {
+			None
+		};
+
+		let (is_file, is_dir) = metadata.as_ref().map_or_else(
+			|| (file_type.is_file(), file_type.is_dir()),
+			|metadata| (metadata.is_file(), metadata.is_dir()),
+		);
+
+		if !is_file && !is_dir {
+			return Ok(None);
+		}
+
+		Ok(Some(SortableEntry {
+			name,
+			path,
+			is_dir,
+			metadata,
+		}))
+	}
+
+	/// Count the direct children of a directory
+	async fn count_children(path: &Path) -> Option<u64> {
+		let mut dir = read_dir(path).await.ok()?;
+		let mut count = 0;
+
+		while let Ok(Some(_)) = dir.next_entry().await {
+			count += 1;
+		}
+
+		Some(count)
+	}
+
+	/// Derive an entity tag from a file's size and modification time
+	fn etag(metadata: &Metadata) -> String {
+		let modified_nanos = metadata
+			.modified()
+			.ok()
+			.and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+			.map_or(0, |duration| duration.as_nanos());
+
+		format!("{:x}-{:x}", metadata.len(), modified_nanos)
+	}
+
+	fn unix_timestamp(time: std::io::Result<SystemTime>) -> Option<i64> {
+		time.ok()
+			.map(|time| OffsetDateTime::from(time).unix_timestamp())
+	}
 }
 
 #[async_trait]
 impl FileManager for ScopedFileManager {
-	async fn read_file(&self, path: &Path) -> Result<BufReader<File>, FileManagerError> {
+	async fn read_file(&self, path: &Path) -> Result<FileReader, FileManagerError> {
 		let path = self.normalize_path(path)?;
+		self.ensure_visible(&path)?;
 		Self::ensure_path_exists(&path)?;
 
 		let file = File::open(path).await.map_err(FileManagerError::IoError)?;
 
 		let buf_reader = BufReader::new(file);
 
-		Ok(buf_reader)
+		Ok(Box::new(buf_reader))
 	}
 
-	async fn write_file(&self, path: &Path) -> Result<BufWriter<File>, FileManagerError> {
+	async fn write_file(&self, path: &Path) -> Result<FileWriter, FileManagerError> {
 		let path = self.normalize_path(path)?;
+		self.ensure_writable(&path)?;
 		Self::ensure_path_exists(&path)?;
 
-		let file = File::create(path)
-			.await
-			.map_err(FileManagerError::IoError)?;
+		let history = self.history_for(&path);
 
-		let buf_writer = BufWriter::new(file);
+		let writer = AtomicFileWriter::new(path, history, self.usage.clone()).await?;
 
-		Ok(buf_writer)
+		Ok(Box::new(writer))
 	}
 
 	async fn delete(&self, path: &Path) -> Result<(), FileManagerError> {
 		let path = self.normalize_path(path)?;
+		self.ensure_visible(&path)?;
 		Self::ensure_path_exists(&path)?;
 		self.ensure_not_scoped_root(&path)?;
+		self.ensure_tree_writable(&path).await?;
 
 		if path.is_file() {
 			remove_file(path).await.map_err(FileManagerError::IoError)?;
@@ -90,11 +347,14 @@ impl FileManager for ScopedFileManager {
 			return Err(FileManagerError::UnknownType);
 		}
 
+		self.invalidate_usage();
+
 		Ok(())
 	}
 
 	async fn create_dir(&self, path: &Path) -> Result<(), FileManagerError> {
 		let path = self.normalize_path(path)?;
+		self.ensure_writable(&path)?;
 
 		create_dir(path).await.map_err(FileManagerError::IoError)?;
 
@@ -103,6 +363,7 @@ impl FileManager for ScopedFileManager {
 
 	async fn create_file(&self, path: &Path) -> Result<(), FileManagerError> {
 		let path = self.normalize_path(path)?;
+		self.ensure_writable(&path)?;
 
 		File::create(path)
 			.await
@@ -113,6 +374,7 @@ impl FileManager for ScopedFileManager {
 
 	async fn list_dir(&self, path: &Path) -> Result<Vec<FSEntry>, FileManagerError> {
 		let path = self.normalize_path(path)?;
+		self.ensure_visible(&path)?;
 		Self::ensure_path_exists(&path)?;
 
 		let mut dir = read_dir(path).await.map_err(FileManagerError::IoError)?;
@@ -120,34 +382,141 @@ impl FileManager for ScopedFileManager {
 		let mut entries = Vec::new();
 
 		while let Some(entry) = dir.next_entry().await.map_err(FileManagerError::IoError)? {
-			let file_type = entry.file_type().await.map_err(FileManagerError::IoError)?;
+			let name = entry
+				.file_name()
+				.into_string()
+				.map_err(|_| FileManagerError::EncodingError)?;
+
+			if self.ensure_visible(&entry.path()).is_err() {
+				continue;
+			}
+
+			// Skip special files and broken symlinks
+			match Self::build_entry(name, &entry.path()).await {
+				Ok(fs_entry) => entries.push(fs_entry),
+				Err(FileManagerError::UnknownType | FileManagerError::NotFound) => {}
+				Err(err) => return Err(err),
+			}
+		}
 
-			let metadata = entry.metadata().await.map_err(FileManagerError::IoError)?;
+		Ok(entries)
+	}
+
+	/// Only stats files and symlinks, as plain directories need nothing but their name
+	async fn walk_dir(&self, path: &Path) -> Result<Vec<FSWalkEntry>, FileManagerError> {
+		let path = self.normalize_path(path)?;
+		self.ensure_visible(&path)?;
+		Self::ensure_path_exists(&path)?;
 
+		let mut dir = read_dir(path).await.map_err(FileManagerError::IoError)?;
+		let mut entries = Vec::new();
+
+		while let Some(entry) = dir.next_entry().await.map_err(FileManagerError::IoError)? {
 			let name = entry
 				.file_name()
 				.into_string()
 				.map_err(|_| FileManagerError::EncodingError)?;
 
-			if file_type.is_file() {
-				entries.push(FSEntry::File(FSFileEntry {
+			if self.ensure_visible(&entry.path()).is_err() {
+				continue;
+			}
+
+			let file_type = entry.file_type().await.map_err(FileManagerError::IoError)?;
+			let symlink = file_type.is_symlink();
+
+			if file_type.is_dir() {
+				entries.push(FSWalkEntry::Dir { name, symlink });
+				continue;
+			}
+
+			// Skip special files and broken symlinks
+			let metadata = match metadata(entry.path()).await {
+				Ok(metadata) => metadata,
+				Err(err) if err.kind() == ErrorKind::NotFound => continue,
+				Err(err) => return Err(FileManagerError::IoError(err)),
+			};
+
+			if metadata.is_dir() {
+				entries.push(FSWalkEntry::Dir { name, symlink });
+			} else if metadata.is_file() {
+				entries.push(FSWalkEntry::File {
 					name,
 					size: metadata.len(),
-				}));
-			} else if file_type.is_dir() {
-				entries.push(FSEntry::Dir(FSDirectoryEntry { name }));
+				});
 			}
 		}
 
 		Ok(entries)
 	}
 
+	/// Sorts and pages entries by what `read_dir` returns, so only the entries of the page are
+	/// built and have their children counted. Entries are only stat'ed up front if the listing
+	/// is sorted by their metadata.
+	async fn list_dir_with(
+		&self,
+		path: &Path,
+		options: &FSListOptions,
+	) -> Result<FSListing, FileManagerError> {
+		let path = self.normalize_path(path)?;
+		self.ensure_visible(&path)?;
+		Self::ensure_path_exists(&path)?;
+
+		let filter = listing_filter(options)?;
+		let needs_metadata = !matches!(options.sort, FSSortField::Name);
+
+		let mut dir = read_dir(path).await.map_err(FileManagerError::IoError)?;
+		let mut listed = Vec::new();
+
+		while let Some(entry) = dir.next_entry().await.map_err(FileManagerError::IoError)? {
+			let name = entry
+				.file_name()
+				.into_string()
+				.map_err(|_| FileManagerError::EncodingError)?;
+
+			if filter
+				.as_ref()
+				.is_some_and(|filter| !filter.is_match(&name))
+				|| self.ensure_visible(&entry.path()).is_err()
+			{
+				continue;
+			}
+
+			if let Some(sortable) = Self::sortable_entry(name, &entry, needs_metadata).await? {
+				listed.push(sortable);
+			}
+		}
+
+		sort_listing(&mut listed, options, SortableEntry::key);
+
+		let total = listed.len();
+		let (page, next_cursor) = listing_page(total, options);
+		let mut entries = Vec::with_capacity(page.len());
+
+		for sortable in listed.drain(page) {
+			// Entries removed since they were listed are skipped
+			match Self::build_entry(sortable.name, &sortable.path).await {
+				Ok(fs_entry) => entries.push(fs_entry),
+				Err(FileManagerError::UnknownType | FileManagerError::NotFound) => {}
+				Err(err) => return Err(err),
+			}
+		}
+
+		Ok(FSListing {
+			entries,
+			total,
+			next_cursor,
+		})
+	}
+
 	async fn relocate(&self, path: &Path, new_path: &Path) -> Result<(), FileManagerError> {
 		let path = self.normalize_path(path)?;
+		self.ensure_visible(&path)?;
 		Self::ensure_path_exists(&path)?;
 		self.ensure_not_scoped_root(&path)?;
+		self.ensure_tree_writable(&path).await?;
 
 		let new_path = self.normalize_path(new_path)?;
+		self.ensure_writable(&new_path)?;
 
 		rename(path, new_path)
 			.await
@@ -156,14 +525,68 @@ impl FileManager for ScopedFileManager {
 		Ok(())
 	}
 
-	async fn stat(&self, path: &Path) -> Result<FSEntry, FileManagerError> {
+	async fn import(&self, source: &Path, path: &Path) -> Result<(), FileManagerError> {
 		let path = self.normalize_path(path)?;
+		self.ensure_not_scoped_root(&path)?;
+		self.ensure_writable(&path)?;
+
+		let sizes = match &self.usage {
+			Some(usage) => {
+				let added = DiskUsage::size_of(source).await?;
+				let replaced = match DiskUsage::size_of(&path).await {
+					Ok(size) => size,
+					Err(NotFound) => 0,
+					Err(err) => return Err(err),
+				};
+
+				usage.ensure_room(added, replaced).await?;
+				Some((usage, added, replaced))
+			}
+			None => None,
+		};
+
+		if let Some((history, relative_path)) = self.history_for(&path) {
+			if let Err(err) = history.snapshot(&relative_path, &path).await {
+				tracing::warn!(
+					"Failed to record history for {}: {}",
+					relative_path.display(),
+					err
+				);
+			}
+		}
+
+		rename(source, path)
+			.await
+			.map_err(FileManagerError::IoError)?;
+
+		if let Some((usage, added, replaced)) = sizes {
+			usage.adjust(added, replaced);
+		}
+
+		Ok(())
+	}
+
+	async fn export(&self, path: &Path, destination: &Path) -> Result<(), FileManagerError> {
+		let path = self.normalize_path(path)?;
+		self.ensure_visible(&path)?;
 		Self::ensure_path_exists(&path)?;
+		self.ensure_not_scoped_root(&path)?;
+		self.ensure_tree_writable(&path).await?;
 
-		let metadata = metadata(path.clone())
+		rename(path, destination)
 			.await
 			.map_err(FileManagerError::IoError)?;
 
+		self.invalidate_usage();
+
+		Ok(())
+	}
+
+	async fn stat(&self, path: &Path) -> Result<FSEntry, FileManagerError> {
+		let path = self.normalize_path(path)?;
+		self.ensure_visible(&path)?;
+		Self::ensure_path_exists(&path)?;
+
 		let name = path
 			.file_name()
 			.ok_or(FileManagerError::NoPermission)?
@@ -171,15 +594,14 @@ impl FileManager for ScopedFileManager {
 			.into_string()
 			.map_err(|_| FileManagerError::EncodingError)?;
 
-		if metadata.is_file() {
-			Ok(FSEntry::File(FSFileEntry {
-				name,
-				size: metadata.len(),
-			}))
-		} else if metadata.is_dir() {
-			Ok(FSEntry::Dir(FSDirectoryEntry { name }))
-		} else {
-			Err(FileManagerError::UnknownType)
-		}
+		Self::build_entry(name, &path).await
+	}
+
+	async fn watch(&self, path: &Path) -> Result<FSWatch, FileManagerError> {
+		let path = self.normalize_path(path)?;
+		self.ensure_visible(&path)?;
+		Self::ensure_path_exists(&path)?;
+
+		FSWatch::new(self.base_path.clone(), path, self.policy.clone()).await
 	}
 }
@@ -0,0 +1,281 @@
+use crate::config::{
+	SEARCH_MAX_FILE_SIZE, SEARCH_MAX_LINE_LENGTH, SEARCH_SNIFF_LENGTH, SEARCH_TIMEOUT,
+};
+use crate::models::file_manager::types::{FSEntry, FSWalkEntry, FileManagerError};
+use crate::models::file_manager::{normalize_relative, FileManager};
+use async_compression::tokio::bufread::GzipDecoder;
+use globset::{Glob, GlobMatcher};
+use regex::bytes::{Regex, RegexBuilder};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::time::Instant;
+use ts_rs::TS;
+
+/// Options for searching the content of files
+pub struct SearchOptions {
+	pub query: String,
+	/// Treat the query as a regular expression instead of literal text
+	pub regex: bool,
+	pub case_sensitive: bool,
+	/// Only search files whose path relative to the root matches this glob
+	pub glob: Option<String>,
+	pub max_results: usize,
+}
+
+/// A line matching a search query
+#[derive(TS, Debug, Serialize)]
+#[ts(export)]
+pub struct SearchMatch {
+	/// Path relative to the file manager's root
+	pub path: PathBuf,
+	/// Line number, starting at 1
+	pub line_number: u64,
+	pub line: String,
+}
+
+#[derive(TS, Debug, Default, Serialize)]
+#[ts(export)]
+pub struct SearchResults {
+	pub matches: Vec<SearchMatch>,
+	pub files_searched: u64,
+	/// The search stopped early because the result limit was reached
+	pub truncated: bool,
+	/// The search stopped early because it ran out of time
+	pub timed_out: bool,
+}
+
+/// Search the lines of the files at or below `path`. Gzip-compressed files are decompressed
+/// transparently; binary files, symlinked directories and files larger than
+/// `SEARCH_MAX_FILE_SIZE` are skipped.
+pub async fn search(
+	fs: &dyn FileManager,
+	path: &Path,
+	options: &SearchOptions,
+) -> Result<SearchResults, FileManagerError> {
+	let searcher = Searcher::new(options)?;
+	let mut results = SearchResults::default();
+
+	let entry = fs.stat(path).await?;
+	let path = normalize_relative(path);
+
+	let mut pending_dirs = match entry {
+		FSEntry::File(file) => {
+			searcher
+				.search_file(fs, &path, file.size, &mut results)
+				.await;
+			return Ok(results);
+		}
+		FSEntry::Dir(_) => vec![path],
+	};
+
+	while let Some(dir) = pending_dirs.pop() {
+		// Listing large trees takes time too, even when no file is searched
+		if searcher.check_deadline(&mut results) {
+			return Ok(results);
+		}
+
+		let mut entries = match fs.walk_dir(&dir).await {
+			Ok(entries) => entries,
+			Err(err) => {
+				tracing::debug!("Skipping directory {} in search: {}", dir.display(), err);
+				continue;
+			}
+		};
+
+		entries.sort_by(|a, b| a.name().cmp(b.name()));
+
+		let mut subdirs = Vec::new();
+
+		for entry in entries {
+			let entry_path = dir.join(entry.name());
+
+			match entry {
+				FSWalkEntry::Dir { symlink: false, .. } => subdirs.push(entry_path),
+				FSWalkEntry::Dir { .. } => {}
+				FSWalkEntry::File { size, .. } => {
+					if searcher.matches_glob(&entry_path) {
+						searcher
+							.search_file(fs, &entry_path, size, &mut results)
+							.await;
+					}
+				}
+			}
+
+			if results.truncated || results.timed_out {
+				return Ok(results);
+			}
+		}
+
+		// Visit subdirectories in name order
+		pending_dirs.extend(subdirs.into_iter().rev());
+	}
+
+	Ok(results)
+}
+
+struct Searcher {
+	pattern: Regex,
+	glob: Option<GlobMatcher>,
+	max_results: usize,
+	deadline: Instant,
+}
+
+impl Searcher {
+	fn new(options: &SearchOptions) -> Result<Self, FileManagerError> {
+		if options.query.is_empty() {
+			return Err(FileManagerError::InvalidPattern(
+				"Search query must not be empty".to_string(),
+			));
+		}
+
+		let pattern = if options.regex {
+			options.query.clone()
+		} else {
+			regex::escape(&options.query)
+		};
+
+		let pattern = RegexBuilder::new(&pattern)
+			.case_insensitive(!options.case_sensitive)
+			.build()
+			.map_err(|err| FileManagerError::InvalidPattern(err.to_string()))?;
+
+		let glob = options
+			.glob
+			.as_deref()
+			.map(|glob| {
+				Glob::new(glob)
+					.map(|glob| glob.compile_matcher())
+					.map_err(|err| FileManagerError::InvalidPattern(err.to_string()))
+			})
+			.transpose()?;
+
+		Ok(Self {
+			pattern,
+			glob,
+			max_results: options.max_results,
+			deadline: Instant::now() + SEARCH_TIMEOUT,
+		})
+	}
+
+	/// Mark the results as timed out once the search ran out of time
+	fn check_deadline(&self, results: &mut SearchResults) -> bool {
+		if Instant::now() >= self.deadline {
+			results.timed_out = true;
+		}
+
+		results.timed_out
+	}
+
+	fn matches_glob(&self, path: &Path) -> bool {
+		self.glob.as_ref().is_none_or(|glob| glob.is_match(path))
+	}
+
+	/// Search a single file, skipping it if it can't be read
+	async fn search_file(
+		&self,
+		fs: &dyn FileManager,
+		path: &Path,
+		size: u64,
+		results: &mut SearchResults,
+	) {
+		if size > SEARCH_MAX_FILE_SIZE || self.check_deadline(results) {
+			return;
+		}
+
+		if let Err(err) = self.search_lines(fs, path, results).await {
+			tracing::debug!("Skipping file {} in search: {}", path.display(), err);
+		}
+	}
+
+	async fn search_lines(
+		&self,
+		fs: &dyn FileManager,
+		path: &Path,
+		results: &mut SearchResults,
+	) -> Result<(), FileManagerError> {
+		let file = fs.read_file(path).await?;
+
+		let is_gzip = path
+			.extension()
+			.is_some_and(|extension| extension.eq_ignore_ascii_case("gz"));
+
+		let reader: Pin<Box<dyn AsyncBufRead + Send>> = if is_gzip {
+			Box::pin(BufReader::new(GzipDecoder::new(file)))
+		} else {
+			Box::pin(file)
+		};
+
+		// Bound the decompressed size as well
+		let mut reader = reader.take(SEARCH_MAX_FILE_SIZE);
+
+		let head = reader.fill_buf().await.map_err(FileManagerError::IoError)?;
+		if Self::is_binary(&head[..head.len().min(SEARCH_SNIFF_LENGTH)]) {
+			return Ok(());
+		}
+
+		results.files_searched += 1;
+
+		let mut line = Vec::new();
+		let mut line_number = 0;
+
+		loop {
+			line.clear();
+
+			let read = reader
+				.read_until(b'\n', &mut line)
+				.await
+				.map_err(FileManagerError::IoError)?;
+
+			if read == 0 {
+				return Ok(());
+			}
+
+			line_number += 1;
+
+			if self.check_deadline(results) {
+				return Ok(());
+			}
+
+			if !self.pattern.is_match(&line) {
+				continue;
+			}
+
+			if results.matches.len() >= self.max_results {
+				results.truncated = true;
+				return Ok(());
+			}
+
+			results.matches.push(SearchMatch {
+				path: path.to_path_buf(),
+				line_number,
+				line: Self::display_line(&line),
+			});
+		}
+	}
+
+	/// Internal: Guess whether a file is binary from its leading bytes
+	fn is_binary(head: &[u8]) -> bool {
+		if head.contains(&0) {
+			return true;
+		}
+
+		match std::str::from_utf8(head) {
+			Ok(_) => false,
+			// A multi-byte character may be cut off at the end of the sniffed bytes
+			Err(err) => err.error_len().is_some(),
+		}
+	}
+
+	/// Internal: Convert a raw line for display, shortening very long lines
+	fn display_line(line: &[u8]) -> String {
+		let line = String::from_utf8_lossy(line);
+		let line = line.trim_end_matches(['\n', '\r']);
+
+		match line.char_indices().nth(SEARCH_MAX_LINE_LENGTH) {
+			Some((end, _)) => format!("{}…", &line[..end]),
+			None => line.to_string(),
+		}
+	}
+}
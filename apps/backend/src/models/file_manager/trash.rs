@@ -0,0 +1,234 @@
+use crate::config::{TRASH_PURGE_INTERVAL, TRASH_RETENTION};
+use crate::models::file_manager::types::{FSEntry, FileManagerError};
+use crate::models::file_manager::{normalize_relative, FileManager};
+use crate::models::file_schemas::trash_entry::TrashEntry;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use time::OffsetDateTime;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+/// Holds deleted files and directories in a directory outside of the server root so they can be
+/// restored. Entries are removed permanently once they are older than `TRASH_RETENTION`, by a
+/// background task every `TRASH_PURGE_INTERVAL` and whenever the trash is used.
+pub struct Trash {
+	dir: PathBuf,
+	lock: Mutex<()>,
+}
+
+impl Trash {
+	/// Create the trash and start purging expired entries in the background. The task ends once
+	/// the trash is dropped.
+	pub fn new(dir: PathBuf) -> Arc<Self> {
+		let trash = Arc::new(Self {
+			dir,
+			lock: Mutex::new(()),
+		});
+
+		let weak = Arc::downgrade(&trash);
+
+		tokio::spawn(async move {
+			while let Some(trash) = weak.upgrade() {
+				let purged = {
+					let _lock = trash.lock.lock().await;
+					trash.purge_expired().await
+				};
+
+				if let Err(err) = purged {
+					tracing::warn!("Failed to purge expired trash entries: {}", err);
+				}
+
+				drop(trash);
+
+				tokio::time::sleep(TRASH_PURGE_INTERVAL).await;
+			}
+		});
+
+		trash
+	}
+
+	/// Move a file or directory from the file manager into the trash
+	pub async fn put(
+		&self,
+		fs: &dyn FileManager,
+		path: &Path,
+	) -> Result<TrashEntry, FileManagerError> {
+		let _lock = self.lock.lock().await;
+		self.purge_expired().await?;
+
+		let (is_dir, size) = match fs.stat(path).await? {
+			FSEntry::File(file) => (false, Some(file.size)),
+			FSEntry::Dir(_) => (true, None),
+		};
+
+		tokio::fs::create_dir_all(&self.dir)
+			.await
+			.map_err(FileManagerError::IoError)?;
+
+		let entry = TrashEntry {
+			id: Uuid::new_v4(),
+			original_path: normalize_relative(path),
+			is_dir,
+			size,
+			deleted_at: OffsetDateTime::now_utc().unix_timestamp(),
+		};
+
+		fs.export(path, &self.payload_path(entry.id)).await?;
+
+		if let Err(err) = entry.save_to_file(&self.meta_path(entry.id)) {
+			// Without metadata the entry can't be restored, so put it back
+			fs.import(&self.payload_path(entry.id), path).await?;
+			return Err(FileManagerError::IoError(std::io::Error::other(err)));
+		}
+
+		Ok(entry)
+	}
+
+	/// List the entries in the trash, most recently deleted first
+	pub async fn list(&self) -> Result<Vec<TrashEntry>, FileManagerError> {
+		let _lock = self.lock.lock().await;
+		self.purge_expired().await?;
+		self.read_entries().await
+	}
+
+	/// Move an entry back into the file manager, either to its original path or to `to`
+	pub async fn restore(
+		&self,
+		fs: &dyn FileManager,
+		id: Uuid,
+		to: Option<&Path>,
+	) -> Result<TrashEntry, FileManagerError> {
+		let _lock = self.lock.lock().await;
+
+		let entry = self.read_entry(id)?;
+		let target = to.map_or_else(|| entry.original_path.clone(), normalize_relative);
+
+		match fs.stat(&target).await {
+			Ok(_) => return Err(FileManagerError::AlreadyExists),
+			Err(FileManagerError::NotFound) => {}
+			Err(err) => return Err(err),
+		}
+
+		if let Some(parent) = target.parent() {
+			if let FSEntry::File(_) = fs.stat(parent).await? {
+				return Err(FileManagerError::NoPermission);
+			}
+		}
+
+		fs.import(&self.payload_path(id), &target).await?;
+		Self::remove_file_if_exists(&self.meta_path(id)).await?;
+
+		Ok(entry)
+	}
+
+	/// Permanently delete an entry
+	pub async fn purge(&self, id: Uuid) -> Result<(), FileManagerError> {
+		let _lock = self.lock.lock().await;
+
+		self.read_entry(id)?;
+		self.remove_entry(id).await
+	}
+
+	/// Permanently delete all entries
+	pub async fn purge_all(&self) -> Result<(), FileManagerError> {
+		let _lock = self.lock.lock().await;
+
+		for entry in self.read_entries().await? {
+			self.remove_entry(entry.id).await?;
+		}
+
+		Ok(())
+	}
+
+	/// Internal: Permanently delete entries older than the retention period
+	async fn purge_expired(&self) -> Result<(), FileManagerError> {
+		let cutoff = (OffsetDateTime::now_utc() - TRASH_RETENTION).unix_timestamp();
+
+		for entry in self.read_entries().await? {
+			if entry.deleted_at < cutoff {
+				tracing::info!(
+					"Purging expired trash entry {} ({})",
+					entry.id,
+					entry.original_path.display()
+				);
+				self.remove_entry(entry.id).await?;
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Internal: Read the metadata of all entries
+	async fn read_entries(&self) -> Result<Vec<TrashEntry>, FileManagerError> {
+		let mut dir = match tokio::fs::read_dir(&self.dir).await {
+			Ok(dir) => dir,
+			Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+			Err(err) => return Err(FileManagerError::IoError(err)),
+		};
+
+		let mut entries = Vec::new();
+
+		while let Some(dir_entry) = dir.next_entry().await.map_err(FileManagerError::IoError)? {
+			let path = dir_entry.path();
+
+			if path.extension().is_none_or(|ext| ext != "toml") {
+				continue;
+			}
+
+			match TrashEntry::load_from_file(&path) {
+				Ok(entry) => entries.push(entry),
+				Err(err) => tracing::warn!("Failed to load trash entry {:?}: {}", path, err),
+			}
+		}
+
+		entries.sort_by_key(|entry| std::cmp::Reverse(entry.deleted_at));
+
+		Ok(entries)
+	}
+
+	/// Internal: Read the metadata of an entry
+	fn read_entry(&self, id: Uuid) -> Result<TrashEntry, FileManagerError> {
+		let meta_path = self.meta_path(id);
+
+		if !meta_path.exists() {
+			return Err(FileManagerError::NotFound);
+		}
+
+		TrashEntry::load_from_file(&meta_path)
+			.map_err(|err| FileManagerError::IoError(std::io::Error::other(err)))
+	}
+
+	/// Internal: Remove an entry's payload and metadata
+	async fn remove_entry(&self, id: Uuid) -> Result<(), FileManagerError> {
+		let payload_path = self.payload_path(id);
+
+		match tokio::fs::symlink_metadata(&payload_path).await {
+			Ok(metadata) if metadata.is_dir() => tokio::fs::remove_dir_all(&payload_path)
+				.await
+				.map_err(FileManagerError::IoError)?,
+			Ok(_) => tokio::fs::remove_file(&payload_path)
+				.await
+				.map_err(FileManagerError::IoError)?,
+			Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+			Err(err) => return Err(FileManagerError::IoError(err)),
+		}
+
+		Self::remove_file_if_exists(&self.meta_path(id)).await
+	}
+
+	async fn remove_file_if_exists(path: &Path) -> Result<(), FileManagerError> {
+		match tokio::fs::remove_file(path).await {
+			Ok(()) => Ok(()),
+			Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+			Err(err) => Err(FileManagerError::IoError(err)),
+		}
+	}
+
+	fn payload_path(&self, id: Uuid) -> PathBuf {
+		self.dir.join(id.to_string())
+	}
+
+	fn meta_path(&self, id: Uuid) -> PathBuf {
+		self.dir.join(format!("{id}.toml"))
+	}
+}
@@ -1,4 +1,5 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 use thiserror::Error;
 use ts_rs::TS;
 
@@ -6,6 +7,15 @@ use ts_rs::TS;
 #[ts(export)]
 pub struct FSDirectoryEntry {
 	pub name: String,
+	/// Last modification time as a Unix timestamp in seconds
+	pub modified: Option<i64>,
+	/// Creation time as a Unix timestamp in seconds, if supported by the platform
+	pub created: Option<i64>,
+	/// Unix permission bits, if supported by the platform
+	pub permissions: Option<u32>,
+	pub symlink: bool,
+	/// Number of direct children, if the directory could be read
+	pub children: Option<u64>,
 }
 
 #[derive(TS, Debug, Serialize)]
@@ -13,6 +23,31 @@ pub struct FSDirectoryEntry {
 pub struct FSFileEntry {
 	pub name: String,
 	pub size: u64,
+	/// Last modification time as a Unix timestamp in seconds
+	pub modified: Option<i64>,
+	/// Creation time as a Unix timestamp in seconds, if supported by the platform
+	pub created: Option<i64>,
+	/// Unix permission bits, if supported by the platform
+	pub permissions: Option<u32>,
+	pub symlink: bool,
+	/// Opaque version tag that changes whenever the file's content is modified
+	pub etag: String,
+}
+
+/// An entry found while walking a directory tree. Unlike `FSEntry`, it only holds what is
+/// needed to descend into the tree.
+#[derive(Debug)]
+pub enum FSWalkEntry {
+	File { name: String, size: u64 },
+	Dir { name: String, symlink: bool },
+}
+
+impl FSWalkEntry {
+	pub fn name(&self) -> &str {
+		match self {
+			FSWalkEntry::File { name, .. } | FSWalkEntry::Dir { name, .. } => name,
+		}
+	}
 }
 
 #[derive(TS, Debug, Serialize)]
@@ -22,16 +57,148 @@ pub enum FSEntry {
 	Dir(FSDirectoryEntry),
 }
 
+impl FSEntry {
+	pub fn name(&self) -> &str {
+		match self {
+			FSEntry::File(file) => &file.name,
+			FSEntry::Dir(dir) => &dir.name,
+		}
+	}
+
+	pub fn modified(&self) -> Option<i64> {
+		match self {
+			FSEntry::File(file) => file.modified,
+			FSEntry::Dir(dir) => dir.modified,
+		}
+	}
+
+	pub fn created(&self) -> Option<i64> {
+		match self {
+			FSEntry::File(file) => file.created,
+			FSEntry::Dir(dir) => dir.created,
+		}
+	}
+
+	/// Size of the entry. Directories are always 0.
+	pub fn size(&self) -> u64 {
+		match self {
+			FSEntry::File(file) => file.size,
+			FSEntry::Dir(_) => 0,
+		}
+	}
+
+	/// What the entry is sorted by in a listing
+	pub fn sort_key(&self) -> SortKey<'_> {
+		SortKey {
+			name: self.name(),
+			is_dir: matches!(self, FSEntry::Dir(_)),
+			size: self.size(),
+			modified: self.modified(),
+			created: self.created(),
+		}
+	}
+}
+
+/// What a directory listing is sorted by
+pub struct SortKey<'a> {
+	pub name: &'a str,
+	pub is_dir: bool,
+	/// Size of the entry. Directories are always 0.
+	pub size: u64,
+	pub modified: Option<i64>,
+	pub created: Option<i64>,
+}
+
+/// Field to sort directory listings by
+#[derive(TS, Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum FSSortField {
+	#[default]
+	Name,
+	Size,
+	Modified,
+	Created,
+}
+
+#[derive(TS, Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum FSSortOrder {
+	#[default]
+	Asc,
+	Desc,
+}
+
+/// Options for sorting, filtering and paginating a directory listing
+#[derive(Debug, Clone, Default)]
+pub struct FSListOptions {
+	pub sort: FSSortField,
+	pub order: FSSortOrder,
+	/// Glob that entry names must match
+	pub filter: Option<String>,
+	/// Offset of the first entry to return, taken from a previous `next_cursor`
+	pub cursor: Option<usize>,
+	/// Maximum number of entries to return. Returns all entries if unset.
+	pub limit: Option<usize>,
+}
+
+/// A page of a directory listing
+#[derive(TS, Debug, Serialize)]
+#[ts(export)]
+pub struct FSListing {
+	pub entries: Vec<FSEntry>,
+	/// Total number of entries matching the filter
+	pub total: usize,
+	/// Cursor for the next page, if there are more entries
+	pub next_cursor: Option<usize>,
+}
+
+/// Kind of change to a watched path
+#[derive(TS, Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum FSChangeKind {
+	Created,
+	Modified,
+	Deleted,
+	Renamed,
+	/// Changes were dropped because the client fell behind; directory listings should be
+	/// reloaded
+	Overflow,
+}
+
+/// A change below a watched path
+#[derive(TS, Debug, Clone, Serialize)]
+#[ts(export)]
+pub struct FSChangeEvent {
+	pub kind: FSChangeKind,
+	/// Path relative to the file manager's root
+	pub path: PathBuf,
+	/// New path of a renamed entry
+	pub to: Option<PathBuf>,
+}
+
 #[derive(Debug, Error)]
 pub enum FileManagerError {
 	#[error("Path does not resolve to a file or directory")]
 	NotFound,
 	#[error("Path is invalid or outside of allowed directory")]
 	NoPermission,
+	#[error("A file or directory already exists at the path")]
+	AlreadyExists,
 	#[error("Unknown file type")]
 	UnknownType,
 	#[error("Non UTF-8 string encountered in file name")]
 	EncodingError,
+	#[error("Path is read-only")]
+	ReadOnly,
+	#[error("Operation is not supported by this file manager")]
+	Unsupported,
+	#[error("Disk quota exceeded")]
+	QuotaExceeded,
+	#[error("Invalid pattern: {0}")]
+	InvalidPattern(String),
 	#[error("I/O error: {0}")]
 	IoError(std::io::Error),
 }
@@ -0,0 +1,194 @@
+use crate::config::DISK_USAGE_REFRESH_INTERVAL;
+use crate::models::file_manager::types::FileManagerError;
+use serde::{Deserialize, Serialize};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tokio::sync::Notify;
+use ts_rs::TS;
+
+/// Disk usage of a server directory
+#[derive(TS, Debug, Clone, Serialize, Deserialize)]
+#[ts(export)]
+pub struct DiskUsageInfo {
+	/// Total size of the files in bytes, if it could be calculated
+	pub used: Option<u64>,
+	/// Maximum total size of the files in bytes
+	pub quota: Option<u64>,
+}
+
+/// Tracks the total size of the files under a directory and enforces an optional quota on it.
+/// The size is calculated by a background task every `DISK_USAGE_REFRESH_INTERVAL` and whenever it is
+/// invalidated, so reading it never walks the directory and changes made outside of the file
+/// manager (e.g. by the server process) are picked up periodically.
+pub struct DiskUsage {
+	root: PathBuf,
+	quota: RwLock<Option<u64>>,
+	/// Last calculated size, unset until the first calculation finished or if it failed
+	used: RwLock<Option<u64>>,
+	/// Wakes the background task to recalculate the size
+	refresh: Arc<Notify>,
+}
+
+impl DiskUsage {
+	/// Create the usage of a directory and start calculating it in the background. The task
+	/// ends once the usage is dropped.
+	pub fn new(root: PathBuf, quota: Option<u64>) -> Arc<Self> {
+		let usage = Arc::new(Self {
+			root,
+			quota: RwLock::new(quota),
+			used: RwLock::new(None),
+			refresh: Arc::new(Notify::new()),
+		});
+
+		let weak = Arc::downgrade(&usage);
+		let refresh = usage.refresh.clone();
+
+		tokio::spawn(async move {
+			while let Some(usage) = weak.upgrade() {
+				usage.recalculate().await;
+				drop(usage);
+
+				let _ = tokio::time::timeout(DISK_USAGE_REFRESH_INTERVAL, refresh.notified()).await;
+			}
+		});
+
+		usage
+	}
+
+	/// Get the quota in bytes
+	pub fn quota(&self) -> Option<u64> {
+		*self
+			.quota
+			.read()
+			.expect("Quota lock should not be poisoned")
+	}
+
+	/// Replace the quota
+	pub fn set_quota(&self, quota: Option<u64>) {
+		*self
+			.quota
+			.write()
+			.expect("Quota lock should not be poisoned") = quota;
+	}
+
+	/// Get the total size of the files in bytes. Only calculates it if the background task
+	/// hasn't yet.
+	pub async fn used(&self) -> Result<u64, FileManagerError> {
+		if let Some(bytes) = self.cached() {
+			return Ok(bytes);
+		}
+
+		let bytes = Self::size_of(&self.root).await?;
+		self.store(Some(bytes));
+
+		Ok(bytes)
+	}
+
+	/// Get the last calculated usage and the quota
+	pub fn info(&self) -> DiskUsageInfo {
+		DiskUsageInfo {
+			used: self.cached(),
+			quota: self.quota(),
+		}
+	}
+
+	/// Recalculate the size in the background, keeping the current one until then
+	pub fn invalidate(&self) {
+		self.refresh.notify_one();
+	}
+
+	/// Apply a known change to the cached size instead of recalculating it
+	pub fn adjust(&self, added: u64, removed: u64) {
+		let mut used = self
+			.used
+			.write()
+			.expect("Usage lock should not be poisoned");
+
+		if let Some(bytes) = used.as_mut() {
+			*bytes = bytes.saturating_add(added).saturating_sub(removed);
+		}
+	}
+
+	/// Internal: Calculate the size and replace the cached one
+	async fn recalculate(&self) {
+		match Self::size_of(&self.root).await {
+			Ok(bytes) => self.store(Some(bytes)),
+			Err(err) => {
+				tracing::warn!("Failed to calculate disk usage of {:?}: {}", self.root, err);
+				self.store(None);
+			}
+		}
+	}
+
+	/// Internal: Get the cached size
+	fn cached(&self) -> Option<u64> {
+		*self.used.read().expect("Usage lock should not be poisoned")
+	}
+
+	/// Internal: Replace the cached size
+	fn store(&self, bytes: Option<u64>) {
+		*self
+			.used
+			.write()
+			.expect("Usage lock should not be poisoned") = bytes;
+	}
+
+	/// Get the number of bytes that can be added after `replaced` bytes are removed, or `None`
+	/// if there is no quota
+	pub async fn available(&self, replaced: u64) -> Result<Option<u64>, FileManagerError> {
+		let Some(quota) = self.quota() else {
+			return Ok(None);
+		};
+
+		let used = self.used().await?.saturating_sub(replaced);
+
+		Ok(Some(quota.saturating_sub(used)))
+	}
+
+	/// Ensure `added` bytes fit within the quota after `replaced` bytes are removed
+	pub async fn ensure_room(&self, added: u64, replaced: u64) -> Result<(), FileManagerError> {
+		match self.available(replaced).await? {
+			Some(available) if added > available => Err(FileManagerError::QuotaExceeded),
+			_ => Ok(()),
+		}
+	}
+
+	/// Calculate the total size of a file or of the files under a directory. Symlinks are not
+	/// followed.
+	pub async fn size_of(path: &Path) -> Result<u64, FileManagerError> {
+		let path = path.to_path_buf();
+
+		tokio::task::spawn_blocking(move || Self::size_of_blocking(&path))
+			.await
+			.map_err(|err| FileManagerError::IoError(std::io::Error::other(err)))?
+			.map_err(|err| match err.kind() {
+				ErrorKind::NotFound => FileManagerError::NotFound,
+				_ => FileManagerError::IoError(err),
+			})
+	}
+
+	/// Internal: Walk a directory tree and sum the size of its files
+	fn size_of_blocking(path: &Path) -> std::io::Result<u64> {
+		let metadata = std::fs::symlink_metadata(path)?;
+
+		if !metadata.is_dir() {
+			return Ok(metadata.len());
+		}
+
+		let mut total = 0u64;
+
+		for entry in std::fs::read_dir(path)? {
+			let entry = entry?;
+
+			// Files may be removed by the server process while walking
+			match Self::size_of_blocking(&entry.path()) {
+				Ok(size) => total = total.saturating_add(size),
+				Err(err) if err.kind() == ErrorKind::NotFound => {}
+				Err(err) => return Err(err),
+			}
+		}
+
+		Ok(total)
+	}
+}
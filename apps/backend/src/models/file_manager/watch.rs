@@ -0,0 +1,208 @@
+use crate::config::{FS_WATCH_BUFFER, FS_WATCH_DEBOUNCE};
+use crate::models::file_manager::policy::PathPolicy;
+use crate::models::file_manager::types::{FSChangeEvent, FSChangeKind, FileManagerError};
+use crate::models::file_manager::writer::AtomicFileWriter;
+use crate::models::file_schemas::server_config::PathAccess;
+use notify_debouncer_full::notify::event::{ModifyKind, RenameMode};
+use notify_debouncer_full::notify::{EventKind, RecommendedWatcher, RecursiveMode};
+use notify_debouncer_full::{
+	new_debouncer, DebounceEventResult, DebouncedEvent, Debouncer, RecommendedCache,
+};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Stream of changes below a watched path. Watching stops when this is dropped.
+pub struct FSWatch {
+	_debouncer: Debouncer<RecommendedWatcher, RecommendedCache>,
+	events: mpsc::Receiver<FSChangeEvent>,
+	overflowed: Arc<AtomicBool>,
+}
+
+impl FSWatch {
+	/// Watch `path` and everything below it. Changes are reported relative to `root`, leaving
+	/// out paths hidden by `policy`.
+	pub async fn new(
+		root: PathBuf,
+		path: PathBuf,
+		policy: Option<Arc<PathPolicy>>,
+	) -> Result<Self, FileManagerError> {
+		let (sender, events) = mpsc::channel(FS_WATCH_BUFFER);
+		let overflowed = Arc::new(AtomicBool::new(false));
+		let handler_overflowed = overflowed.clone();
+
+		let handler = move |result: DebounceEventResult| match result {
+			Ok(debounced_events) => {
+				let changes = debounced_events
+					.iter()
+					.flat_map(|event| Self::convert(&root, event))
+					.filter_map(|change| Self::apply_policy(policy.as_deref(), change))
+					.collect();
+
+				for change in Self::coalesce(changes) {
+					if sender.try_send(change).is_err() {
+						handler_overflowed.store(true, Ordering::Relaxed);
+					}
+				}
+			}
+			Err(errors) => {
+				for err in errors {
+					tracing::warn!("File watcher error: {}", err);
+				}
+			}
+		};
+
+		// Registering a recursive watch walks the whole tree
+		let debouncer = tokio::task::spawn_blocking(move || {
+			let mut debouncer = new_debouncer(FS_WATCH_DEBOUNCE, None, handler)?;
+			debouncer.watch(&path, RecursiveMode::Recursive)?;
+			Ok::<_, notify_debouncer_full::notify::Error>(debouncer)
+		})
+		.await
+		.map_err(|err| FileManagerError::IoError(std::io::Error::other(err)))?
+		.map_err(|err| FileManagerError::IoError(std::io::Error::other(err)))?;
+
+		Ok(Self {
+			_debouncer: debouncer,
+			events,
+			overflowed,
+		})
+	}
+
+	/// Wait for the next change
+	pub async fn next(&mut self) -> Option<FSChangeEvent> {
+		if self.overflowed.swap(false, Ordering::Relaxed) {
+			return Some(FSChangeEvent {
+				kind: FSChangeKind::Overflow,
+				path: PathBuf::new(),
+				to: None,
+			});
+		}
+
+		self.events.recv().await
+	}
+
+	/// Internal: Translate a watcher event into changes relative to `root`
+	fn convert(root: &Path, event: &DebouncedEvent) -> Vec<FSChangeEvent> {
+		let relative = |path: &Path| path.strip_prefix(root).ok().map(Path::to_path_buf);
+
+		let change = |kind, path: &Path| {
+			relative(path).map(|path| FSChangeEvent {
+				kind,
+				path,
+				to: None,
+			})
+		};
+
+		let kind = match event.kind {
+			EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+				FSChangeKind::Created
+			}
+			EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+				FSChangeKind::Deleted
+			}
+			EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+				return Self::convert_rename(root, &event.paths)
+					.into_iter()
+					.collect();
+			}
+			EventKind::Modify(_) => FSChangeKind::Modified,
+			EventKind::Access(_) | EventKind::Any | EventKind::Other => return Vec::new(),
+		};
+
+		event
+			.paths
+			.iter()
+			.filter(|path| !AtomicFileWriter::is_temp_path(path))
+			.filter_map(|path| change(kind, path))
+			.collect()
+	}
+
+	/// Internal: Hide changes to hidden paths. Renames between hidden and visible paths appear as
+	/// creations or deletions.
+	fn apply_policy(policy: Option<&PathPolicy>, change: FSChangeEvent) -> Option<FSChangeEvent> {
+		let Some(policy) = policy else {
+			return Some(change);
+		};
+
+		let is_hidden = |path: &Path| policy.access(path) == PathAccess::Hidden;
+
+		match change.to {
+			Some(to) => match (is_hidden(&change.path), is_hidden(&to)) {
+				(false, false) => Some(FSChangeEvent {
+					to: Some(to),
+					..change
+				}),
+				(false, true) => Some(FSChangeEvent {
+					kind: FSChangeKind::Deleted,
+					path: change.path,
+					to: None,
+				}),
+				(true, false) => Some(FSChangeEvent {
+					kind: FSChangeKind::Created,
+					path: to,
+					to: None,
+				}),
+				(true, true) => None,
+			},
+			None => (!is_hidden(&change.path)).then_some(change),
+		}
+	}
+
+	/// Internal: Merge a deletion followed by a creation of the same path into a modification.
+	/// This is how replacing a file by renaming another one over it is reported.
+	fn coalesce(changes: Vec<FSChangeEvent>) -> Vec<FSChangeEvent> {
+		let mut coalesced: Vec<FSChangeEvent> = Vec::with_capacity(changes.len());
+
+		for change in changes {
+			if change.kind == FSChangeKind::Created {
+				if let Some(deleted) = coalesced.iter_mut().find(|previous| {
+					previous.kind == FSChangeKind::Deleted && previous.path == change.path
+				}) {
+					deleted.kind = FSChangeKind::Modified;
+					continue;
+				}
+			}
+
+			coalesced.push(change);
+		}
+
+		coalesced
+	}
+
+	/// Internal: Translate a rename, treating moves across the root as creations or deletions
+	fn convert_rename(root: &Path, paths: &[PathBuf]) -> Option<FSChangeEvent> {
+		let [from, to] = paths else {
+			return None;
+		};
+
+		let relative_from = from.strip_prefix(root).ok().map(Path::to_path_buf);
+		let relative_to = to.strip_prefix(root).ok().map(Path::to_path_buf);
+
+		match (relative_from, relative_to) {
+			// Files written through the panel are staged in a temporary file and renamed into place
+			(Some(_), Some(path)) if AtomicFileWriter::is_temp_path(from) => Some(FSChangeEvent {
+				kind: FSChangeKind::Modified,
+				path,
+				to: None,
+			}),
+			(Some(path), Some(to)) => Some(FSChangeEvent {
+				kind: FSChangeKind::Renamed,
+				path,
+				to: Some(to),
+			}),
+			(Some(path), None) => Some(FSChangeEvent {
+				kind: FSChangeKind::Deleted,
+				path,
+				to: None,
+			}),
+			(None, Some(path)) => Some(FSChangeEvent {
+				kind: FSChangeKind::Created,
+				path,
+				to: None,
+			}),
+			(None, None) => None,
+		}
+	}
+}
@@ -0,0 +1,177 @@
+use crate::models::file_manager::history::FileHistory;
+use crate::models::file_manager::types::FileManagerError;
+use crate::models::file_manager::usage::DiskUsage;
+use crate::models::file_manager::FileWrite;
+use async_trait::async_trait;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::fs::{metadata, rename, set_permissions, File};
+use tokio::io::{AsyncWrite, AsyncWriteExt, BufWriter};
+use uuid::Uuid;
+
+/// Writer that stages content in a temporary file next to the target and atomically replaces
+/// the target on `commit`. Dropping the writer without committing discards the staged content.
+pub struct AtomicFileWriter {
+	writer: BufWriter<File>,
+	temp_path: PathBuf,
+	target_path: PathBuf,
+	history: Option<(Arc<FileHistory>, PathBuf)>,
+	usage: Option<Arc<DiskUsage>>,
+	/// Bytes that may be written before the disk quota is exceeded
+	budget: Option<u64>,
+	/// Size of the file being replaced
+	replaced: u64,
+	written: u64,
+	committed: bool,
+}
+
+impl AtomicFileWriter {
+	/// Create a writer for `target_path`. If `history` is set, the previous content of the
+	/// target is recorded under the given server-relative path before it is replaced. If `usage`
+	/// is set, writes fail once the new content would exceed its quota.
+	pub async fn new(
+		target_path: PathBuf,
+		history: Option<(Arc<FileHistory>, PathBuf)>,
+		usage: Option<Arc<DiskUsage>>,
+	) -> Result<Self, FileManagerError> {
+		let replaced = metadata(&target_path)
+			.await
+			.map_or(0, |target_metadata| target_metadata.len());
+
+		let budget = match &usage {
+			Some(usage) => usage.available(replaced).await?,
+			None => None,
+		};
+
+		let temp_path = Self::temp_path(&target_path)?;
+		let file = File::create(&temp_path)
+			.await
+			.map_err(FileManagerError::IoError)?;
+
+		Ok(Self {
+			writer: BufWriter::new(file),
+			temp_path,
+			target_path,
+			history,
+			usage,
+			budget,
+			replaced,
+			written: 0,
+			committed: false,
+		})
+	}
+
+	/// Flush the staged content to disk and move it into place
+	pub async fn commit(mut self) -> Result<(), FileManagerError> {
+		self.writer
+			.flush()
+			.await
+			.map_err(FileManagerError::IoError)?;
+		self.writer
+			.get_ref()
+			.sync_all()
+			.await
+			.map_err(FileManagerError::IoError)?;
+
+		// Keep the permissions of the file being replaced, e.g. executable start scripts
+		if let Ok(target_metadata) = metadata(&self.target_path).await {
+			set_permissions(&self.temp_path, target_metadata.permissions())
+				.await
+				.map_err(FileManagerError::IoError)?;
+		}
+
+		if let Some((history, relative_path)) = &self.history {
+			if let Err(err) = history.snapshot(relative_path, &self.target_path).await {
+				tracing::warn!(
+					"Failed to record history for {}: {}",
+					relative_path.display(),
+					err
+				);
+			}
+		}
+
+		rename(&self.temp_path, &self.target_path)
+			.await
+			.map_err(FileManagerError::IoError)?;
+
+		self.committed = true;
+
+		if let Some(usage) = &self.usage {
+			usage.adjust(self.written, self.replaced);
+		}
+
+		Ok(())
+	}
+
+	/// Check whether a path is the temporary file of a writer
+	pub fn is_temp_path(path: &Path) -> bool {
+		path.file_name()
+			.and_then(|name| name.to_str())
+			.and_then(|name| name.strip_prefix('.'))
+			.and_then(|name| name.strip_suffix(".tmp"))
+			.and_then(|name| name.rsplit_once('.'))
+			.is_some_and(|(_, id)| Uuid::parse_str(id).is_ok())
+	}
+
+	/// Internal: Hidden temporary file in the same directory as the target, so the final rename
+	/// never crosses filesystems
+	fn temp_path(target_path: &Path) -> Result<PathBuf, FileManagerError> {
+		let file_name = target_path
+			.file_name()
+			.ok_or(FileManagerError::NoPermission)?
+			.to_string_lossy();
+
+		Ok(target_path.with_file_name(format!(".{file_name}.{}.tmp", Uuid::new_v4())))
+	}
+}
+
+#[async_trait]
+impl FileWrite for AtomicFileWriter {
+	async fn commit(self: Box<Self>) -> Result<(), FileManagerError> {
+		AtomicFileWriter::commit(*self).await
+	}
+}
+
+impl Drop for AtomicFileWriter {
+	fn drop(&mut self) {
+		if !self.committed {
+			let _ = std::fs::remove_file(&self.temp_path);
+		}
+	}
+}
+
+impl AsyncWrite for AtomicFileWriter {
+	fn poll_write(
+		mut self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+		buf: &[u8],
+	) -> Poll<io::Result<usize>> {
+		if let Some(budget) = self.budget {
+			if self.written.saturating_add(buf.len() as u64) > budget {
+				return Poll::Ready(Err(io::Error::new(
+					io::ErrorKind::StorageFull,
+					FileManagerError::QuotaExceeded,
+				)));
+			}
+		}
+
+		let result = Pin::new(&mut self.writer).poll_write(cx, buf);
+
+		if let Poll::Ready(Ok(written)) = result {
+			self.written += written as u64;
+		}
+
+		result
+	}
+
+	fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+		Pin::new(&mut self.writer).poll_flush(cx)
+	}
+
+	fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+		Pin::new(&mut self.writer).poll_shutdown(cx)
+	}
+}
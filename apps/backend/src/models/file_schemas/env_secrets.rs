@@ -0,0 +1,54 @@
+use crate::models::file_schemas::server_config::ConfigError;
+use crate::models::secrets::SecretCipher;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::path::Path;
+
+/// File in a server's metadata directory holding its secret environment variables
+pub const ENV_SECRETS_FILE_NAME: &str = "env_secrets.toml";
+
+/// Encrypted values of a server's secret environment variables, by name
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EnvSecrets {
+	values: BTreeMap<String, String>,
+}
+
+impl EnvSecrets {
+	/// Load the secrets, starting empty if the file doesn't exist yet
+	pub fn load_from_file(path: &Path) -> Result<Self, ConfigError> {
+		match std::fs::read_to_string(path) {
+			Ok(file) => Ok(toml::from_str(&file)?),
+			Err(err) if err.kind() == ErrorKind::NotFound => Ok(Self::default()),
+			Err(err) => Err(err.into()),
+		}
+	}
+
+	pub fn save_to_file(&self, path: &Path) -> Result<(), ConfigError> {
+		if let Some(parent) = path.parent() {
+			std::fs::create_dir_all(parent)?;
+		}
+
+		let toml_string = toml::to_string(self)?;
+		std::fs::write(path, toml_string)?;
+		Ok(())
+	}
+
+	pub fn contains(&self, name: &str) -> bool {
+		self.values.contains_key(name)
+	}
+
+	/// Decrypt the value of a variable
+	pub fn get(&self, name: &str, cipher: &SecretCipher) -> Option<String> {
+		cipher.decrypt(self.values.get(name)?)
+	}
+
+	pub fn set(&mut self, name: &str, value: &str, cipher: &SecretCipher) {
+		self.values.insert(name.to_string(), cipher.encrypt(value));
+	}
+
+	/// Drop the values of variables that aren't secret anymore
+	pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+		self.values.retain(|name, _| keep(name));
+	}
+}
@@ -1,2 +1,6 @@
 pub mod binaries_lockfile;
+pub mod env_secrets;
 pub mod server_config;
+pub mod server_properties;
+pub mod trash_entry;
+pub mod upload_session;
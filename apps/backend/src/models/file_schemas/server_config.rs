@@ -1,7 +1,9 @@
 use crate::models::game::Game;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use ts_rs::TS;
+use uuid::Uuid;
 
 #[derive(Debug, thiserror::Error)]
 pub enum ConfigError {
@@ -21,7 +23,21 @@ pub struct PartialServerConfig {
 	pub name: Option<String>,
 	pub game: Option<Game>,
 	pub args: Option<Vec<String>>,
+	pub jvm: Option<JvmSettings>,
+	/// Replaces all environment variables. Secrets without a value keep their stored value.
+	pub env: Option<BTreeMap<String, EnvVar>>,
+	/// An empty list removes the wrapper
+	pub wrapper: Option<Vec<String>>,
+	pub hooks: Option<ServerHooks>,
 	pub stop_command: Option<String>,
+	pub history: Option<FileHistoryConfig>,
+	/// Maximum total size of the server's files in bytes. 0 removes the quota.
+	pub disk_quota: Option<u64>,
+	/// Memory the server is expected to use in bytes. 0 falls back to the `-Xmx` argument.
+	pub memory: Option<u64>,
+	pub path_rules: Option<Vec<PathRule>>,
+	pub limits: Option<ResourceLimits>,
+	pub sandbox: Option<SandboxConfig>,
 }
 
 #[derive(TS, Debug, Clone, Deserialize, Serialize)]
@@ -30,8 +46,384 @@ pub struct PartialServerConfig {
 pub struct ServerConfig {
 	pub name: String,
 	pub game: Game,
+	/// Extra JVM arguments, added after the ones generated from `jvm`
 	pub args: Vec<String>,
+	#[serde(default)]
+	pub jvm: JvmSettings,
+	/// Environment variables of the server process
+	#[serde(default)]
+	pub env: BTreeMap<String, EnvVar>,
+	/// Command the server is launched through, e.g. `["nice", "-n", "10"]`
+	#[serde(default)]
+	pub wrapper: Vec<String>,
+	#[serde(default)]
+	pub hooks: ServerHooks,
 	pub stop_command: String,
+	#[serde(default)]
+	pub history: FileHistoryConfig,
+	/// Maximum total size of the server's files in bytes
+	#[serde(default)]
+	pub disk_quota: Option<u64>,
+	/// Memory the server is expected to use in bytes, counted against the host's memory when
+	/// starting servers. Defaults to the heap size set with `-Xmx`.
+	#[serde(default)]
+	pub memory: Option<u64>,
+	/// Access rules for paths in the server directory, later rules take precedence
+	#[serde(default)]
+	pub path_rules: Vec<PathRule>,
+	/// Who accepted the game's EULA through the panel
+	#[serde(default)]
+	pub eula: Option<EulaAcceptance>,
+	#[serde(default)]
+	pub limits: ResourceLimits,
+	#[serde(default)]
+	pub sandbox: SandboxConfig,
+}
+
+/// An environment variable of a server process
+#[derive(TS, Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[ts(export)]
+#[serde(rename_all = "snake_case")]
+pub struct EnvVar {
+	/// Never set for secrets outside of requests, as their values are stored encrypted apart from
+	/// the config
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub value: Option<String>,
+	#[serde(default)]
+	pub secret: bool,
+}
+
+/// Check the names and values of environment variables
+pub fn validate_env(env: &BTreeMap<String, EnvVar>) -> Result<(), String> {
+	for (name, var) in env {
+		let mut chars = name.chars();
+		let valid_name = chars
+			.next()
+			.is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+			&& chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+		if !valid_name {
+			return Err(format!("Invalid environment variable name: {name}"));
+		}
+
+		match &var.value {
+			Some(value) if value.contains('\0') => {
+				return Err(format!("Environment variable {name} contains a null byte"));
+			}
+			None if !var.secret => {
+				return Err(format!("Environment variable {name} has no value"));
+			}
+			_ => {}
+		}
+	}
+
+	Ok(())
+}
+
+/// Check a launch wrapper command
+pub fn validate_wrapper(wrapper: &[String]) -> Result<(), String> {
+	if wrapper
+		.first()
+		.is_some_and(|program| program.trim().is_empty())
+	{
+		return Err("Wrapper program must not be empty".to_string());
+	}
+
+	if wrapper.iter().any(|arg| arg.contains('\0')) {
+		return Err("Wrapper arguments must not contain null bytes".to_string());
+	}
+
+	Ok(())
+}
+
+/// What a lifecycle hook does
+#[derive(TS, Debug, Clone, Deserialize, Serialize)]
+#[ts(export)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HookAction {
+	/// Run a command with `sh -c` in the server directory, with only the server's environment
+	/// and in its sandbox if that is enabled
+	Shell { command: String },
+	/// Copy the files of a template from the panel's templates directory into the server
+	/// directory, replacing existing files
+	SyncTemplate { template: String },
+	/// Copy the server directory into its backups, keeping the newest ones
+	Backup { keep: usize },
+	/// Set entries of `server.properties`
+	SetProperties {
+		properties: BTreeMap<String, String>,
+	},
+}
+
+/// An action run before a server starts or after it stops
+#[derive(TS, Debug, Clone, Deserialize, Serialize)]
+#[ts(export)]
+#[serde(rename_all = "snake_case")]
+pub struct LifecycleHook {
+	pub action: HookAction,
+	/// Seconds the hook may run before it is aborted. Defaults to five minutes.
+	pub timeout: Option<u64>,
+}
+
+/// Hooks run around the server process, in order. A failing pre-start hook aborts the start.
+#[derive(TS, Debug, Clone, Default, Deserialize, Serialize)]
+#[ts(export)]
+#[serde(rename_all = "snake_case")]
+pub struct ServerHooks {
+	#[serde(default)]
+	pub pre_start: Vec<LifecycleHook>,
+	#[serde(default)]
+	pub post_stop: Vec<LifecycleHook>,
+}
+
+impl ServerHooks {
+	pub fn validate(&self) -> Result<(), String> {
+		for hook in self.pre_start.iter().chain(&self.post_stop) {
+			if hook.timeout == Some(0) {
+				return Err("Hook timeouts must be greater than 0".to_string());
+			}
+
+			match &hook.action {
+				HookAction::Shell { command } if command.trim().is_empty() => {
+					return Err("Shell hooks need a command".to_string());
+				}
+				HookAction::SyncTemplate { template }
+					if template.is_empty()
+						|| template.starts_with('.')
+						|| template.contains(['/', '\\']) =>
+				{
+					return Err(format!("Invalid template name: {template}"));
+				}
+				HookAction::Backup { keep: 0 } => {
+					return Err("Backup hooks must keep at least one backup".to_string());
+				}
+				HookAction::SetProperties { properties } => {
+					let invalid = properties
+						.keys()
+						.find(|key| key.is_empty() || key.chars().any(char::is_control));
+
+					if let Some(key) = invalid {
+						return Err(format!("Invalid property key: {key:?}"));
+					}
+				}
+				_ => {}
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// Garbage collector flags added to the JVM arguments
+#[derive(TS, Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[ts(export)]
+#[serde(rename_all = "snake_case")]
+pub enum GcPreset {
+	/// Leave the choice to the JVM and the extra arguments
+	#[default]
+	None,
+	/// The G1 collector with its default tuning
+	G1,
+	/// G1 tuned for Minecraft, see <https://docs.papermc.io/paper/aikars-flags>
+	Aikar,
+	/// The low-latency Z collector, available from Java 15
+	Zgc,
+}
+
+/// Structured JVM settings the server's command line is generated from
+#[derive(TS, Debug, Clone, Default, Deserialize, Serialize)]
+#[ts(export)]
+#[serde(rename_all = "snake_case")]
+pub struct JvmSettings {
+	/// Initial heap size in bytes, passed as `-Xms`
+	pub min_heap: Option<u64>,
+	/// Maximum heap size in bytes, passed as `-Xmx`
+	pub max_heap: Option<u64>,
+	#[serde(default)]
+	pub gc: GcPreset,
+	/// Arguments passed to the server after the jar, e.g. `--nogui`
+	#[serde(default)]
+	pub program_args: Vec<String>,
+}
+
+impl JvmSettings {
+	/// Check the settings against each other, the extra JVM arguments and the resource limits
+	pub fn validate(&self, jvm_args: &[String], limits: &ResourceLimits) -> Result<(), String> {
+		let min_size = 64 * 1024 * 1024;
+
+		if self.min_heap.is_some_and(|heap| heap < min_size)
+			|| self.max_heap.is_some_and(|heap| heap < min_size)
+		{
+			return Err("Heap sizes must be at least 64 MiB".to_string());
+		}
+
+		if let (Some(min), Some(max)) = (self.min_heap, self.max_heap) {
+			if min > max {
+				return Err("Minimum heap size must not exceed the maximum heap size".to_string());
+			}
+		}
+
+		// Aikar's flags pre-touch the whole heap, which only works with a fixed size
+		if self.gc == GcPreset::Aikar {
+			if self.max_heap.is_none() {
+				return Err("Aikar's flags require a maximum heap size".to_string());
+			}
+
+			if self.min_heap.is_some_and(|min| Some(min) != self.max_heap) {
+				return Err(
+					"Aikar's flags require the minimum heap size to equal the maximum".to_string(),
+				);
+			}
+		}
+
+		// The heap alone has to fit, the JVM needs more memory on top of it
+		if let (Some(heap), Some(limit)) = (self.max_heap, limits.memory_max) {
+			if heap >= limit {
+				return Err("Maximum heap size must be below the memory limit".to_string());
+			}
+		}
+
+		for arg in jvm_args {
+			if arg == "-jar" {
+				return Err("JVM arguments must not contain -jar".to_string());
+			}
+
+			if self.min_heap.is_some() && arg.starts_with("-Xms") {
+				return Err(format!("{arg} conflicts with the minimum heap size"));
+			}
+
+			if self.max_heap.is_some() && arg.starts_with("-Xmx") {
+				return Err(format!("{arg} conflicts with the maximum heap size"));
+			}
+
+			if self.gc != GcPreset::None && arg.starts_with("-XX:+Use") && arg.ends_with("GC") {
+				return Err(format!("{arg} conflicts with the garbage collector preset"));
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// Isolation of a server process from the host and the panel
+#[derive(TS, Debug, Clone, Default, Deserialize, Serialize)]
+#[ts(export)]
+#[serde(rename_all = "snake_case")]
+pub struct SandboxConfig {
+	/// Run the server in a bubblewrap sandbox that only exposes its own directory, the JVM and
+	/// the server binary
+	pub enabled: bool,
+	/// Unix user to run the server as. The server directory is handed over to it on start,
+	/// which requires the panel to run as root.
+	pub user: Option<String>,
+}
+
+/// Limits on the resources a server process may use. Unset limits aren't enforced.
+#[derive(TS, Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[ts(export)]
+#[serde(rename_all = "snake_case")]
+pub struct ResourceLimits {
+	/// Memory in bytes, including the page cache. The process is killed when it needs more.
+	pub memory_max: Option<u64>,
+	/// Share of CPU time relative to other servers, from 1 to 10000. Servers get 100 by default.
+	pub cpu_weight: Option<u32>,
+	/// CPU time as a percentage of one core, e.g. 200 for two cores
+	pub cpu_quota: Option<u32>,
+	/// Maximum number of processes and threads
+	pub pids_max: Option<u32>,
+	/// Share of disk bandwidth relative to other servers, from 1 to 10000
+	pub io_weight: Option<u32>,
+}
+
+impl ResourceLimits {
+	pub fn is_empty(&self) -> bool {
+		*self == Self::default()
+	}
+
+	pub fn validate(&self) -> Result<(), String> {
+		let weight_in_range = |weight: Option<u32>| weight.is_none_or(|w| (1..=10000).contains(&w));
+
+		if !weight_in_range(self.cpu_weight) {
+			return Err("CPU weight must be between 1 and 10000".to_string());
+		}
+
+		if !weight_in_range(self.io_weight) {
+			return Err("IO weight must be between 1 and 10000".to_string());
+		}
+
+		if self.cpu_quota == Some(0) {
+			return Err("CPU quota must be greater than 0".to_string());
+		}
+
+		if self.pids_max == Some(0) {
+			return Err("Process limit must be greater than 0".to_string());
+		}
+
+		// The JVM alone needs more than this to start
+		if self
+			.memory_max
+			.is_some_and(|memory| memory < 64 * 1024 * 1024)
+		{
+			return Err("Memory limit must be at least 64 MiB".to_string());
+		}
+
+		Ok(())
+	}
+}
+
+/// Record of a user accepting the Minecraft EULA for a server
+#[derive(TS, Debug, Clone, Deserialize, Serialize)]
+#[ts(export)]
+#[serde(rename_all = "snake_case")]
+pub struct EulaAcceptance {
+	pub user_id: Uuid,
+	pub username: String,
+	/// Acceptance time as a Unix timestamp in seconds
+	pub accepted_at: i64,
+}
+
+/// Access level of a path in the server directory, from most to least restricted
+#[derive(TS, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[ts(export)]
+#[serde(rename_all = "snake_case")]
+pub enum PathAccess {
+	Hidden,
+	ReadOnly,
+	Writable,
+}
+
+/// Access rule for server-relative paths matching a glob, and everything below them
+#[derive(TS, Debug, Clone, Deserialize, Serialize)]
+#[ts(export)]
+#[serde(rename_all = "snake_case")]
+pub struct PathRule {
+	pub glob: String,
+	pub access: PathAccess,
+}
+
+/// Settings for keeping previous versions of text files written through the panel
+#[derive(TS, Debug, Clone, Deserialize, Serialize)]
+#[ts(export)]
+#[serde(rename_all = "snake_case")]
+pub struct FileHistoryConfig {
+	pub enabled: bool,
+	/// Globs of server-relative paths to keep history for
+	pub globs: Vec<String>,
+	/// Number of previous versions to keep per file
+	pub max_versions: usize,
+}
+
+impl Default for FileHistoryConfig {
+	fn default() -> Self {
+		Self {
+			enabled: true,
+			globs: ["*.properties", "*.yml", "*.yaml", "*.toml", "*.json"]
+				.into_iter()
+				.map(String::from)
+				.collect(),
+			max_versions: 10,
+		}
+	}
 }
 
 impl ServerConfig {
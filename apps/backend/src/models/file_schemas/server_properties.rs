@@ -0,0 +1,434 @@
+use crate::models::file_manager::types::FileManagerError;
+use crate::models::file_manager::FileManager;
+use std::fmt::{self, Write};
+use std::path::Path;
+use std::str::Chars;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Name of the file a Minecraft server reads its settings from
+pub const SERVER_PROPERTIES_FILE_NAME: &str = "server.properties";
+/// Name of the properties file recording the acceptance of the Minecraft EULA
+pub const EULA_FILE_NAME: &str = "eula.txt";
+
+#[derive(Debug, Clone)]
+enum PropertiesLine {
+	/// Comment, blank line or any other text that isn't an entry. Kept as is.
+	Other(String),
+	Entry {
+		key: String,
+		value: String,
+		/// Original text of the entry, including continuation lines. Cleared when the value
+		/// changes, so untouched entries are written back exactly as they were.
+		raw: Option<String>,
+	},
+}
+
+/// A Java properties file that keeps comments, blank lines and the order of its entries when
+/// written back
+#[derive(Debug, Clone)]
+pub struct ServerProperties {
+	lines: Vec<PropertiesLine>,
+	line_ending: &'static str,
+	/// The file was read as ISO-8859-1 and is written back the same way
+	latin1: bool,
+}
+
+impl Default for ServerProperties {
+	fn default() -> Self {
+		Self {
+			lines: Vec::new(),
+			line_ending: "\n",
+			latin1: false,
+		}
+	}
+}
+
+impl ServerProperties {
+	/// Parse the contents of a properties file
+	pub fn parse(content: &str) -> Self {
+		let line_ending = if content.contains("\r\n") {
+			"\r\n"
+		} else {
+			"\n"
+		};
+
+		let mut lines = Vec::new();
+		let mut natural_lines = content.lines();
+
+		while let Some(line) = natural_lines.next() {
+			let trimmed = line.trim_start_matches(is_whitespace);
+
+			if trimmed.is_empty() || trimmed.starts_with(['#', '!']) {
+				lines.push(PropertiesLine::Other(line.to_string()));
+				continue;
+			}
+
+			// A line ending in an odd number of backslashes continues on the next one
+			let mut raw = line.to_string();
+			let mut logical = trimmed.to_string();
+
+			while ends_with_continuation(&logical) {
+				logical.pop();
+
+				let Some(next) = natural_lines.next() else {
+					break;
+				};
+
+				raw.push_str(line_ending);
+				raw.push_str(next);
+				logical.push_str(next.trim_start_matches(is_whitespace));
+			}
+
+			let (key, value) = split_entry(&logical);
+
+			lines.push(PropertiesLine::Entry {
+				key: unescape(key),
+				value: unescape(value),
+				raw: Some(raw),
+			});
+		}
+
+		Self {
+			lines,
+			line_ending,
+			latin1: false,
+		}
+	}
+
+	/// Load a server's properties through its file manager. A missing file has no entries.
+	pub async fn load(fs: &dyn FileManager) -> Result<Self, FileManagerError> {
+		let mut reader = match fs.read_file(Path::new(SERVER_PROPERTIES_FILE_NAME)).await {
+			Ok(reader) => reader,
+			Err(FileManagerError::NotFound) => return Ok(Self::default()),
+			Err(err) => return Err(err),
+		};
+
+		let mut bytes = Vec::new();
+		reader
+			.read_to_end(&mut bytes)
+			.await
+			.map_err(FileManagerError::IoError)?;
+
+		Ok(Self::decode(bytes))
+	}
+
+	/// Parse the raw content of a properties file
+	pub fn decode(bytes: Vec<u8>) -> Self {
+		// Older servers write the file as ISO-8859-1
+		match String::from_utf8(bytes) {
+			Ok(content) => Self::parse(&content),
+			Err(err) => {
+				let content: String = err.into_bytes().into_iter().map(char::from).collect();
+
+				Self {
+					latin1: true,
+					..Self::parse(&content)
+				}
+			}
+		}
+	}
+
+	/// Get the raw content of the properties file, in the encoding it was read with
+	pub fn encode(&self) -> Vec<u8> {
+		let content = self.to_string();
+
+		if !self.latin1 {
+			return content.into_bytes();
+		}
+
+		// Changed entries are escaped to ASCII and the rest was decoded from ISO-8859-1, so the
+		// escape is only a fallback
+		let mut bytes = Vec::with_capacity(content.len());
+
+		for c in content.chars() {
+			match u8::try_from(c) {
+				Ok(byte) => bytes.push(byte),
+				Err(_) => {
+					bytes.extend_from_slice(escape(c.encode_utf8(&mut [0; 4]), false).as_bytes());
+				}
+			}
+		}
+
+		bytes
+	}
+
+	/// Save the properties through a server's file manager, creating the file if needed
+	pub async fn save(&self, fs: &dyn FileManager) -> Result<(), FileManagerError> {
+		let path = Path::new(SERVER_PROPERTIES_FILE_NAME);
+
+		if let Err(FileManagerError::NotFound) = fs.stat(path).await {
+			fs.create_file(path).await?;
+		}
+
+		let mut writer = fs.write_file(path).await?;
+		writer
+			.write_all(&self.encode())
+			.await
+			.map_err(FileManagerError::IoError)?;
+
+		writer.commit().await
+	}
+
+	/// Get the value of a key. Like Java, the last entry wins if a key appears more than once.
+	pub fn get(&self, key: &str) -> Option<&str> {
+		self.entries()
+			.filter(|(entry_key, _)| *entry_key == key)
+			.last()
+			.map(|(_, value)| value)
+	}
+
+	/// Iterate over the entries in file order
+	pub fn entries(&self) -> impl Iterator<Item = (&str, &str)> {
+		self.lines.iter().filter_map(|line| match line {
+			PropertiesLine::Entry { key, value, .. } => Some((key.as_str(), value.as_str())),
+			PropertiesLine::Other(_) => None,
+		})
+	}
+
+	/// Set the value of a key, updating its entry in place or appending a new one
+	pub fn set(&mut self, key: &str, value: &str) {
+		let existing = self.lines.iter_mut().rev().find_map(|line| match line {
+			PropertiesLine::Entry {
+				key: entry_key,
+				value,
+				raw,
+			} if entry_key == key => Some((value, raw)),
+			_ => None,
+		});
+
+		match existing {
+			Some((entry_value, raw)) => {
+				if entry_value != value {
+					*entry_value = value.to_string();
+					*raw = None;
+				}
+			}
+			None => self.lines.push(PropertiesLine::Entry {
+				key: key.to_string(),
+				value: value.to_string(),
+				raw: None,
+			}),
+		}
+	}
+
+	/// Remove every entry of a key. Returns whether anything was removed.
+	pub fn remove(&mut self, key: &str) -> bool {
+		let count = self.lines.len();
+
+		self.lines.retain(
+			|line| !matches!(line, PropertiesLine::Entry { key: entry_key, .. } if entry_key == key),
+		);
+
+		self.lines.len() != count
+	}
+}
+
+impl fmt::Display for ServerProperties {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for line in &self.lines {
+			match line {
+				PropertiesLine::Other(text)
+				| PropertiesLine::Entry {
+					raw: Some(text), ..
+				} => f.write_str(text)?,
+				PropertiesLine::Entry {
+					key,
+					value,
+					raw: None,
+				} => write!(f, "{}={}", escape(key, true), escape(value, false))?,
+			}
+
+			f.write_str(self.line_ending)?;
+		}
+
+		Ok(())
+	}
+}
+
+fn is_whitespace(c: char) -> bool {
+	matches!(c, ' ' | '\t' | '\x0c')
+}
+
+fn ends_with_continuation(line: &str) -> bool {
+	line.chars().rev().take_while(|c| *c == '\\').count() % 2 == 1
+}
+
+/// Internal: Split a logical line into its escaped key and value. The key ends at the first
+/// unescaped `=`, `:` or whitespace.
+fn split_entry(line: &str) -> (&str, &str) {
+	let mut escaped = false;
+	let mut key_end = line.len();
+
+	for (index, c) in line.char_indices() {
+		if escaped {
+			escaped = false;
+		} else if c == '\\' {
+			escaped = true;
+		} else if c == '=' || c == ':' || is_whitespace(c) {
+			key_end = index;
+			break;
+		}
+	}
+
+	let rest = line[key_end..].trim_start_matches(is_whitespace);
+	let rest = rest
+		.strip_prefix(['=', ':'])
+		.unwrap_or(rest)
+		.trim_start_matches(is_whitespace);
+
+	(&line[..key_end], rest)
+}
+
+fn unescape(text: &str) -> String {
+	let mut result = String::with_capacity(text.len());
+	let mut chars = text.chars();
+
+	while let Some(c) = chars.next() {
+		if c != '\\' {
+			result.push(c);
+			continue;
+		}
+
+		match chars.next() {
+			Some('t') => result.push('\t'),
+			Some('n') => result.push('\n'),
+			Some('r') => result.push('\r'),
+			Some('f') => result.push('\x0c'),
+			Some('u') => match read_utf16_escape(&mut chars) {
+				Some(decoded) => result.push(decoded),
+				// Java rejects malformed escapes, keep them readable instead
+				None => result.push('u'),
+			},
+			Some(other) => result.push(other),
+			None => {}
+		}
+	}
+
+	result
+}
+
+/// Internal: Decode the hex digits of a `\u` escape, combining surrogate pairs that are written as
+/// two escapes. Leaves `chars` untouched if the escape is malformed.
+fn read_utf16_escape(chars: &mut Chars<'_>) -> Option<char> {
+	let hex_unit = |chars: &mut Chars<'_>| {
+		let digits = chars
+			.as_str()
+			.get(..4)
+			.filter(|digits| digits.bytes().all(|b| b.is_ascii_hexdigit()))?;
+		let unit = u16::from_str_radix(digits, 16).ok()?;
+		chars.nth(3);
+		Some(unit)
+	};
+
+	let mut lookahead = chars.clone();
+	let high = hex_unit(&mut lookahead)?;
+
+	let decoded = if let Some(decoded) = char::from_u32(u32::from(high)) {
+		decoded
+	} else {
+		lookahead = lookahead.as_str().strip_prefix("\\u")?.chars();
+		let low = hex_unit(&mut lookahead)?;
+		char::decode_utf16([high, low]).next()?.ok()?
+	};
+
+	*chars = lookahead;
+	Some(decoded)
+}
+
+/// Internal: Escape text the way `java.util.Properties` stores it. Characters outside of
+/// printable ASCII use `\uXXXX` escapes, which every server version reads correctly.
+fn escape(text: &str, is_key: bool) -> String {
+	let mut result = String::with_capacity(text.len());
+
+	for (index, c) in text.chars().enumerate() {
+		match c {
+			' ' if is_key || index == 0 => result.push_str("\\ "),
+			'\\' | '=' | ':' | '#' | '!' => {
+				result.push('\\');
+				result.push(c);
+			}
+			'\t' => result.push_str("\\t"),
+			'\n' => result.push_str("\\n"),
+			'\r' => result.push_str("\\r"),
+			'\x0c' => result.push_str("\\f"),
+			' '..='~' => result.push(c),
+			_ => {
+				let mut units = [0; 2];
+
+				for unit in c.encode_utf16(&mut units) {
+					let _ = write!(result, "\\u{unit:04X}");
+				}
+			}
+		}
+	}
+
+	result
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn joins_continuation_lines() {
+		let properties = ServerProperties::parse("motd=Hello \\\n    world\nkey\\\\=value\n");
+
+		assert_eq!(properties.get("motd"), Some("Hello world"));
+		// An even number of backslashes is an escaped backslash, not a continuation
+		assert_eq!(properties.get("key\\"), Some("value"));
+	}
+
+	#[test]
+	fn decodes_unicode_escapes() {
+		let properties = ServerProperties::parse("motd=\\u00e9\\uD83D\\uDE00\nbroken=\\uZZ\n");
+
+		assert_eq!(properties.get("motd"), Some("é😀"));
+		assert_eq!(properties.get("broken"), Some("uZZ"));
+	}
+
+	#[test]
+	fn keeps_untouched_lines() {
+		let content =
+			"#Minecraft server properties\r\n\r\nmotd = A \\\r\n  B\r\nlevel-name:world\r\n";
+		let mut properties = ServerProperties::parse(content);
+
+		assert_eq!(properties.to_string(), content);
+
+		properties.set("level-name", "world");
+		properties.set("max-players", "20");
+
+		assert_eq!(
+			properties.to_string(),
+			format!("{content}max-players=20\r\n")
+		);
+	}
+
+	#[test]
+	fn escapes_changed_entries() {
+		let mut properties = ServerProperties::default();
+		properties.set("motd", " Café 😀 #1");
+
+		assert_eq!(
+			properties.to_string(),
+			"motd=\\ Caf\\u00E9 \\uD83D\\uDE00 \\#1\n"
+		);
+		assert_eq!(
+			ServerProperties::parse(&properties.to_string()).get("motd"),
+			Some(" Café 😀 #1")
+		);
+	}
+
+	#[test]
+	fn writes_back_latin1() {
+		let mut properties = ServerProperties::decode(b"#caf\xe9\nmotd=\xe9\n".to_vec());
+
+		assert_eq!(properties.get("motd"), Some("é"));
+
+		properties.set("level-name", "wörld");
+
+		assert_eq!(
+			properties.encode(),
+			b"#caf\xe9\nmotd=\xe9\nlevel-name=w\\u00F6rld\n"
+		);
+	}
+}
@@ -0,0 +1,33 @@
+use crate::models::file_schemas::server_config::ConfigError;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use ts_rs::TS;
+use uuid::Uuid;
+
+/// Metadata about a file or directory that was moved to the trash
+#[derive(TS, Serialize, Deserialize, Debug, Clone)]
+#[ts(export)]
+pub struct TrashEntry {
+	pub id: Uuid,
+	/// Path the entry was deleted from, relative to the server's root
+	pub original_path: PathBuf,
+	pub is_dir: bool,
+	/// Size in bytes. Only set for files.
+	pub size: Option<u64>,
+	/// Deletion time as a Unix timestamp in seconds
+	pub deleted_at: i64,
+}
+
+impl TrashEntry {
+	pub fn load_from_file(path: &Path) -> Result<Self, ConfigError> {
+		let file = std::fs::read_to_string(path)?;
+
+		Ok(toml::from_str(&file)?)
+	}
+
+	pub fn save_to_file(&self, path: &Path) -> Result<(), ConfigError> {
+		let toml_string = toml::to_string(self)?;
+		std::fs::write(path, toml_string)?;
+		Ok(())
+	}
+}
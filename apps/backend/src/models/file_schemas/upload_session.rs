@@ -0,0 +1,31 @@
+use crate::models::file_schemas::server_config::ConfigError;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Persisted state of a resumable upload. The uploaded bytes are kept in a separate part file,
+/// so the number of received bytes is not stored here.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UploadSessionFile {
+	pub id: Uuid,
+	/// Destination path, relative to the server's root
+	pub path: PathBuf,
+	/// Expected total size in bytes, if declared by the client
+	pub size: Option<u64>,
+	/// Creation time as a Unix timestamp in seconds
+	pub created_at: i64,
+}
+
+impl UploadSessionFile {
+	pub fn load_from_file(path: &Path) -> Result<Self, ConfigError> {
+		let file = std::fs::read_to_string(path)?;
+
+		Ok(toml::from_str(&file)?)
+	}
+
+	pub fn save_to_file(&self, path: &Path) -> Result<(), ConfigError> {
+		let toml_string = toml::to_string(self)?;
+		std::fs::write(path, toml_string)?;
+		Ok(())
+	}
+}
@@ -1,4 +1,5 @@
 pub mod java;
+pub mod properties;
 
 use java::MinecraftJava;
 use serde::{Deserialize, Serialize};
@@ -18,6 +19,13 @@ impl Game {
 		}
 	}
 
+	/// Version of the game itself, without the loader
+	pub fn game_version(&self) -> &str {
+		match self {
+			Game::MinecraftJava(minecraft_java) => &minecraft_java.version,
+		}
+	}
+
 	pub fn version_string(&self) -> String {
 		match self {
 			Game::MinecraftJava(minecraft_java) => format!(
@@ -0,0 +1,433 @@
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+use ts_rs::TS;
+
+/// Typed value of a `server.properties` entry
+#[derive(TS, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[ts(export)]
+#[serde(untagged)]
+pub enum PropertyValue {
+	Boolean(bool),
+	Integer(i32),
+	String(String),
+}
+
+impl PropertyValue {
+	/// Text the value is stored as in the properties file
+	pub fn to_raw(&self) -> String {
+		match self {
+			PropertyValue::Boolean(value) => value.to_string(),
+			PropertyValue::Integer(value) => value.to_string(),
+			PropertyValue::String(value) => value.clone(),
+		}
+	}
+}
+
+/// Type of a known property and the values it accepts
+#[derive(TS, Debug, Clone, Serialize, Deserialize)]
+#[ts(export)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum PropertyKind {
+	Boolean,
+	Integer { min: i32, max: i32 },
+	String,
+	Enum { values: Vec<String> },
+}
+
+/// A property the server understands, with the game versions that read it
+#[derive(TS, Debug, Clone, Serialize, Deserialize)]
+#[ts(export)]
+pub struct PropertySchema {
+	pub key: String,
+	pub kind: PropertyKind,
+	pub default: PropertyValue,
+	pub description: String,
+	/// First release that reads the property
+	pub min_version: Option<String>,
+	/// Last release that reads the property
+	pub max_version: Option<String>,
+	/// Changes can be applied to a running server with a console command
+	pub applies_live: bool,
+	#[serde(skip)]
+	#[ts(skip)]
+	command: Option<fn(&PropertyValue) -> String>,
+}
+
+impl PropertySchema {
+	fn new(key: &str, kind: PropertyKind, default: PropertyValue, description: &str) -> Self {
+		Self {
+			key: key.to_string(),
+			kind,
+			default,
+			description: description.to_string(),
+			min_version: None,
+			max_version: None,
+			applies_live: false,
+			command: None,
+		}
+	}
+
+	fn boolean(key: &str, default: bool, description: &str) -> Self {
+		Self::new(
+			key,
+			PropertyKind::Boolean,
+			PropertyValue::Boolean(default),
+			description,
+		)
+	}
+
+	fn integer(key: &str, default: i32, min: i32, max: i32, description: &str) -> Self {
+		Self::new(
+			key,
+			PropertyKind::Integer { min, max },
+			PropertyValue::Integer(default),
+			description,
+		)
+	}
+
+	fn string(key: &str, default: &str, description: &str) -> Self {
+		Self::new(
+			key,
+			PropertyKind::String,
+			PropertyValue::String(default.to_string()),
+			description,
+		)
+	}
+
+	fn choice(key: &str, default: &str, values: &[&str], description: &str) -> Self {
+		Self::new(
+			key,
+			PropertyKind::Enum {
+				values: values.iter().map(ToString::to_string).collect(),
+			},
+			PropertyValue::String(default.to_string()),
+			description,
+		)
+	}
+
+	fn since(mut self, version: &str) -> Self {
+		self.min_version = Some(version.to_string());
+		self
+	}
+
+	fn until(mut self, version: &str) -> Self {
+		self.max_version = Some(version.to_string());
+		self
+	}
+
+	fn live(mut self, command: fn(&PropertyValue) -> String) -> Self {
+		self.applies_live = true;
+		self.command = Some(command);
+		self
+	}
+
+	/// Whether a game version reads the property. Versions that aren't releases, like
+	/// snapshots, are assumed to read every property.
+	pub fn applies_to(&self, version: &str) -> bool {
+		let Some(version) = release_version(version) else {
+			return true;
+		};
+
+		let after_min = self
+			.min_version
+			.as_deref()
+			.and_then(release_version)
+			.is_none_or(|min| version >= min);
+		let before_max = self
+			.max_version
+			.as_deref()
+			.and_then(release_version)
+			.is_none_or(|max| version <= max);
+
+		after_min && before_max
+	}
+
+	/// Interpret a value from the properties file. Returns None if it doesn't have the
+	/// property's type.
+	pub fn parse(&self, raw: &str) -> Option<PropertyValue> {
+		match self.kind {
+			// The server treats anything other than `true` as false, only accept what it writes
+			PropertyKind::Boolean => match raw.trim() {
+				"true" => Some(PropertyValue::Boolean(true)),
+				"false" => Some(PropertyValue::Boolean(false)),
+				_ => None,
+			},
+			PropertyKind::Integer { .. } => raw.trim().parse().ok().map(PropertyValue::Integer),
+			PropertyKind::String | PropertyKind::Enum { .. } => {
+				Some(PropertyValue::String(raw.to_string()))
+			}
+		}
+	}
+
+	/// Check a value against the property's type and range, returning the text to store
+	pub fn validate(&self, value: &PropertyValue) -> Result<String, String> {
+		match (&self.kind, value) {
+			(PropertyKind::Boolean, PropertyValue::Boolean(_))
+			| (PropertyKind::String, PropertyValue::String(_)) => Ok(value.to_raw()),
+			(PropertyKind::Integer { min, max }, PropertyValue::Integer(number)) => {
+				if number < min || number > max {
+					Err(format!("Must be between {min} and {max}"))
+				} else {
+					Ok(value.to_raw())
+				}
+			}
+			(PropertyKind::Enum { values }, PropertyValue::String(text)) => {
+				if values.contains(text) {
+					Ok(value.to_raw())
+				} else {
+					Err(format!("Must be one of: {}", values.join(", ")))
+				}
+			}
+			(PropertyKind::Boolean, _) => Err("Must be a boolean".to_string()),
+			(PropertyKind::Integer { .. }, _) => Err("Must be an integer".to_string()),
+			(PropertyKind::String | PropertyKind::Enum { .. }, _) => {
+				Err("Must be a string".to_string())
+			}
+		}
+	}
+
+	/// Console command that applies a new value to a running server, if there is one
+	pub fn live_command(&self, value: &PropertyValue) -> Option<String> {
+		self.command.map(|command| command(value))
+	}
+}
+
+/// Internal: Parse the release a version belongs to, so `1.20.1-rc1` counts as `1.20.1`.
+/// Returns None for snapshots like `24w14a`.
+fn release_version(version: &str) -> Option<[u32; 3]> {
+	let release = version.split(['-', ' ']).next()?;
+	let mut parts = [0; 3];
+
+	for (index, part) in release.split('.').enumerate() {
+		*parts.get_mut(index)? = part.parse().ok()?;
+	}
+
+	Some(parts)
+}
+
+static DIFFICULTIES: &[&str] = &["peaceful", "easy", "normal", "hard"];
+static GAME_MODES: &[&str] = &["survival", "creative", "adventure", "spectator"];
+
+static PROPERTY_SCHEMA: LazyLock<Vec<PropertySchema>> = LazyLock::new(|| {
+	vec![
+		PropertySchema::integer("server-port", 25565, 1, 65535, "Port the server listens on"),
+		PropertySchema::string(
+			"server-ip",
+			"",
+			"Address to bind to, empty for all interfaces",
+		),
+		PropertySchema::string(
+			"motd",
+			"A Minecraft Server",
+			"Message shown in the server list",
+		),
+		PropertySchema::integer("max-players", 20, 0, i32::MAX, "Maximum number of players"),
+		PropertySchema::boolean(
+			"online-mode",
+			true,
+			"Verify players with the Minecraft account servers",
+		),
+		PropertySchema::choice("difficulty", "easy", DIFFICULTIES, "Difficulty of the game")
+			.since("1.14")
+			.live(|value| format!("difficulty {}", value.to_raw())),
+		PropertySchema::choice(
+			"gamemode",
+			"survival",
+			GAME_MODES,
+			"Game mode of new players",
+		)
+		.since("1.14")
+		.live(|value| format!("defaultgamemode {}", value.to_raw())),
+		PropertySchema::boolean(
+			"force-gamemode",
+			false,
+			"Put players in the default game mode when they join",
+		),
+		PropertySchema::boolean(
+			"hardcore",
+			false,
+			"Ban players when they die and lock the difficulty to hard",
+		),
+		PropertySchema::boolean("pvp", true, "Allow players to damage each other"),
+		PropertySchema::integer(
+			"view-distance",
+			10,
+			3,
+			32,
+			"Distance in chunks the server sends to players",
+		),
+		PropertySchema::integer(
+			"simulation-distance",
+			10,
+			3,
+			32,
+			"Distance in chunks entities are updated in",
+		)
+		.since("1.18"),
+		PropertySchema::integer(
+			"spawn-protection",
+			16,
+			0,
+			i32::MAX,
+			"Radius around spawn only operators can build in",
+		),
+		PropertySchema::boolean(
+			"white-list",
+			false,
+			"Only allow players on the whitelist to join",
+		)
+		.live(|value| {
+			let state = if *value == PropertyValue::Boolean(true) {
+				"on"
+			} else {
+				"off"
+			};
+			format!("whitelist {state}")
+		}),
+		PropertySchema::boolean(
+			"enforce-whitelist",
+			false,
+			"Kick players that aren't on the whitelist when it is reloaded",
+		),
+		PropertySchema::string("level-name", "world", "Folder of the world to load"),
+		PropertySchema::string(
+			"level-seed",
+			"",
+			"Seed of new worlds, empty for a random one",
+		),
+		PropertySchema::string(
+			"level-type",
+			"minecraft:normal",
+			"World preset of new worlds",
+		),
+		PropertySchema::boolean(
+			"generate-structures",
+			true,
+			"Generate structures like villages in new chunks",
+		),
+		PropertySchema::integer(
+			"max-world-size",
+			29_999_984,
+			1,
+			29_999_984,
+			"Radius of the world border",
+		),
+		PropertySchema::boolean("allow-nether", true, "Allow players to enter the Nether"),
+		PropertySchema::boolean("allow-flight", false, "Don't kick players for flying"),
+		PropertySchema::boolean("spawn-monsters", true, "Spawn hostile mobs"),
+		PropertySchema::boolean("spawn-animals", true, "Spawn animals").until("1.21.1"),
+		PropertySchema::boolean("spawn-npcs", true, "Spawn villagers").until("1.21.1"),
+		PropertySchema::boolean(
+			"enable-command-block",
+			false,
+			"Allow command blocks to run commands",
+		),
+		PropertySchema::integer(
+			"op-permission-level",
+			4,
+			1,
+			4,
+			"Permission level of operators",
+		),
+		PropertySchema::integer(
+			"function-permission-level",
+			2,
+			1,
+			4,
+			"Permission level of functions",
+		)
+		.since("1.14.4"),
+		PropertySchema::integer(
+			"player-idle-timeout",
+			0,
+			0,
+			i32::MAX,
+			"Minutes until idle players are kicked, 0 to never kick them",
+		),
+		PropertySchema::integer(
+			"network-compression-threshold",
+			256,
+			-1,
+			i32::MAX,
+			"Smallest packet size in bytes that is compressed, -1 to disable compression",
+		),
+		PropertySchema::integer(
+			"rate-limit",
+			0,
+			0,
+			i32::MAX,
+			"Packets per second a player may send before being kicked, 0 to disable the limit",
+		),
+		PropertySchema::integer(
+			"max-tick-time",
+			60000,
+			-1,
+			i32::MAX,
+			"Milliseconds a tick may take before the watchdog stops the server, -1 to disable it",
+		),
+		PropertySchema::integer(
+			"entity-broadcast-range-percentage",
+			100,
+			10,
+			1000,
+			"Distance entities are sent to players at, as a percentage of the default",
+		)
+		.since("1.16"),
+		PropertySchema::boolean("sync-chunk-writes", true, "Write chunks synchronously")
+			.since("1.16"),
+		PropertySchema::boolean(
+			"enable-status",
+			true,
+			"Show the server as online in the server list",
+		)
+		.since("1.16"),
+		PropertySchema::boolean(
+			"hide-online-players",
+			false,
+			"Hide the player list in the server list",
+		)
+		.since("1.18"),
+		PropertySchema::boolean(
+			"enforce-secure-profile",
+			true,
+			"Only allow players with signed chat keys",
+		)
+		.since("1.19"),
+		PropertySchema::boolean(
+			"prevent-proxy-connections",
+			false,
+			"Kick players whose address differs from the one the account servers see",
+		),
+		PropertySchema::string(
+			"resource-pack",
+			"",
+			"URL of a resource pack players are offered",
+		),
+		PropertySchema::string("resource-pack-sha1", "", "SHA-1 hash of the resource pack"),
+		PropertySchema::boolean(
+			"require-resource-pack",
+			false,
+			"Kick players that decline the resource pack",
+		)
+		.since("1.17"),
+		PropertySchema::boolean("enable-rcon", false, "Enable remote console access"),
+		PropertySchema::integer("rcon.port", 25575, 1, 65535, "Port of the remote console"),
+		PropertySchema::string("rcon.password", "", "Password of the remote console"),
+		PropertySchema::boolean("enable-query", false, "Enable the GameSpy4 query protocol"),
+		PropertySchema::integer("query.port", 25565, 1, 65535, "Port of the query protocol"),
+	]
+});
+
+/// Properties known for a game version
+pub fn property_schema(version: &str) -> impl Iterator<Item = &'static PropertySchema> {
+	let version = version.to_string();
+
+	PROPERTY_SCHEMA
+		.iter()
+		.filter(move |property| property.applies_to(&version))
+}
+
+/// Look up a property known for a game version
+pub fn find_property(version: &str, key: &str) -> Option<&'static PropertySchema> {
+	property_schema(version).find(|property| property.key == key)
+}
@@ -2,6 +2,7 @@ use digest::DynDigest;
 use serde::{Deserialize, Serialize};
 use sha1::Sha1;
 use sha2::Sha256;
+use ts_rs::TS;
 
 use std::{io::Write, path::PathBuf};
 
@@ -10,7 +11,8 @@ use std::{io::Write, path::PathBuf};
 trait WritableDynDigest: DynDigest + Write {}
 impl<T: DynDigest + Write> WritableDynDigest for T {}
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[derive(TS, Debug, Serialize, Deserialize, Clone, Copy)]
+#[ts(export)]
 pub enum HashAlgorithm {
 	Sha256,
 	Sha1,
@@ -0,0 +1,279 @@
+use crate::config::{
+	server_meta_dir, HOOK_DEFAULT_TIMEOUT, SANDBOX_PATH, SERVER_CONSOLE_MAX_LINE_LENGTH,
+	TEMPLATES_DIRECTORY,
+};
+use crate::models::console_reader::ConsoleReader;
+use crate::models::file_schemas::server_config::{HookAction, LifecycleHook};
+use crate::models::file_schemas::server_properties::{
+	ServerProperties, SERVER_PROPERTIES_FILE_NAME,
+};
+use crate::models::sandbox::{
+	find_executable, read_no_follow, replace_file, sandboxed_command, SandboxError, UnixUser,
+};
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use std::time::Duration;
+use thiserror::Error;
+use time::OffsetDateTime;
+use tokio::io::AsyncRead;
+use tokio::process::Command;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+#[derive(Debug, Error)]
+pub enum HookError {
+	#[error("Timed out after {0} seconds")]
+	TimedOut(u64),
+	#[error("Command exited with {0}")]
+	Failed(ExitStatus),
+	#[error("Failed to sandbox hook: {0}")]
+	Sandbox(#[from] SandboxError),
+	#[error("Template {0} does not exist")]
+	NoSuchTemplate(String),
+	#[error("I/O error: {0}")]
+	Io(#[from] std::io::Error),
+}
+
+/// What hooks of a server run with
+pub struct HookContext {
+	pub server_id: Uuid,
+	pub server_dir: PathBuf,
+	/// Environment variables of the server, with secrets decrypted
+	pub env: Vec<(String, String)>,
+	/// User the server runs as, which shell hooks run as as well
+	pub user: Option<UnixUser>,
+	/// Run shell hooks in the server's sandbox
+	pub sandboxed: bool,
+}
+
+/// Run a hook, sending its output to `output` line by line
+pub async fn run_hook(
+	hook: &LifecycleHook,
+	context: &HookContext,
+	output: &mpsc::UnboundedSender<String>,
+) -> Result<(), HookError> {
+	let timeout = hook
+		.timeout
+		.map_or(HOOK_DEFAULT_TIMEOUT, Duration::from_secs);
+
+	let run = async {
+		match &hook.action {
+			HookAction::Shell { command } => run_shell(command, context, output).await,
+			HookAction::SyncTemplate { template } => sync_template(template, context, output).await,
+			HookAction::Backup { keep } => backup(*keep, context, output).await,
+			HookAction::SetProperties { properties } => {
+				set_properties(properties, context, output).await
+			}
+		}
+	};
+
+	// Dropping the future kills a running command
+	tokio::time::timeout(timeout, run)
+		.await
+		.map_err(|_| HookError::TimedOut(timeout.as_secs()))?
+}
+
+/// Internal: Run a shell command, forwarding what it prints. Like the server, it runs in the
+/// sandbox if that is enabled, and only gets the server's environment either way.
+async fn run_shell(
+	command: &str,
+	context: &HookContext,
+	output: &mpsc::UnboundedSender<String>,
+) -> Result<(), HookError> {
+	let mut cmd = if context.sandboxed {
+		let sh = find_executable("sh")?;
+		let args = ["-c".into(), command.into()];
+		sandboxed_command(&sh, &args, &context.server_dir, &[])?
+	} else {
+		let mut cmd = Command::new("sh");
+		cmd.arg("-c").arg(command);
+		cmd.env_clear();
+		cmd.env("PATH", SANDBOX_PATH);
+		cmd.env("HOME", &context.server_dir);
+		cmd
+	};
+
+	cmd.current_dir(&context.server_dir);
+	cmd.envs(context.env.iter().cloned());
+	cmd.stdin(Stdio::null());
+	cmd.stdout(Stdio::piped());
+	cmd.stderr(Stdio::piped());
+	cmd.kill_on_drop(true);
+
+	if let Some(user) = context.user {
+		user.apply(&mut cmd);
+	}
+
+	let mut child = cmd.spawn()?;
+	let stdout = child.stdout.take();
+	let stderr = child.stderr.take();
+
+	let (status, (), ()) = tokio::join!(
+		child.wait(),
+		forward_lines(stdout, output),
+		forward_lines(stderr, output)
+	);
+	let status = status?;
+
+	if status.success() {
+		Ok(())
+	} else {
+		Err(HookError::Failed(status))
+	}
+}
+
+/// Internal: Send each line read from a pipe to the output
+async fn forward_lines(
+	reader: Option<impl AsyncRead + Unpin>,
+	output: &mpsc::UnboundedSender<String>,
+) {
+	let Some(reader) = reader else {
+		return;
+	};
+
+	let mut lines = ConsoleReader::new(reader, SERVER_CONSOLE_MAX_LINE_LENGTH);
+	while let Ok(Some(line)) = lines.next_line().await {
+		let _ = output.send(line);
+	}
+}
+
+/// Internal: Copy a template over the server directory
+async fn sync_template(
+	template: &str,
+	context: &HookContext,
+	output: &mpsc::UnboundedSender<String>,
+) -> Result<(), HookError> {
+	let source = Path::new(&*TEMPLATES_DIRECTORY).join(template);
+
+	if !source.is_dir() {
+		return Err(HookError::NoSuchTemplate(template.to_string()));
+	}
+
+	let target = context.server_dir.clone();
+	let copied = tokio::task::spawn_blocking(move || copy_dir(&source, &target))
+		.await
+		.map_err(std::io::Error::other)??;
+
+	let _ = output.send(format!("Copied {copied} files from template {template}"));
+	Ok(())
+}
+
+/// Internal: Copy the server directory into a new backup and remove the oldest ones
+async fn backup(
+	keep: usize,
+	context: &HookContext,
+	output: &mpsc::UnboundedSender<String>,
+) -> Result<(), HookError> {
+	let backups_dir = server_meta_dir(context.server_id).join("backups");
+	let name = OffsetDateTime::now_utc().unix_timestamp().to_string();
+	let source = context.server_dir.clone();
+	let target = backups_dir.join(&name);
+
+	let copied = tokio::task::spawn_blocking(move || -> std::io::Result<u64> {
+		let copied = copy_dir(&source, &target)?;
+
+		// Backups are named by their creation time
+		let mut backups: Vec<(i64, PathBuf)> = std::fs::read_dir(&backups_dir)?
+			.filter_map(Result::ok)
+			.filter_map(|entry| {
+				let time = entry.file_name().to_str()?.parse().ok()?;
+				Some((time, entry.path()))
+			})
+			.collect();
+		backups.sort_unstable_by_key(|(time, _)| std::cmp::Reverse(*time));
+
+		for (_, path) in backups.iter().skip(keep) {
+			std::fs::remove_dir_all(path)?;
+		}
+
+		Ok(copied)
+	})
+	.await
+	.map_err(std::io::Error::other)??;
+
+	let _ = output.send(format!("Backed up {copied} files as {name}"));
+	Ok(())
+}
+
+/// Internal: Set entries of `server.properties`, keeping the rest of the file as is
+async fn set_properties(
+	properties: &BTreeMap<String, String>,
+	context: &HookContext,
+	output: &mpsc::UnboundedSender<String>,
+) -> Result<(), HookError> {
+	let path = context.server_dir.join(SERVER_PROPERTIES_FILE_NAME);
+
+	let mut file = read_no_follow(&path)
+		.await?
+		.map(ServerProperties::decode)
+		.unwrap_or_default();
+
+	for (key, value) in properties {
+		file.set(key, value);
+	}
+
+	replace_file(&path, &file.encode()).await?;
+
+	let _ = output.send(format!(
+		"Set {} entries of {SERVER_PROPERTIES_FILE_NAME}",
+		properties.len()
+	));
+	Ok(())
+}
+
+/// Internal: Recursively copy a directory, replacing existing files. Symlinks are skipped, so
+/// nothing outside of the source is copied. Returns the number of copied files.
+fn copy_dir(source: &Path, target: &Path) -> std::io::Result<u64> {
+	std::fs::create_dir_all(target)?;
+	copy_entries(source, target)
+}
+
+/// Internal: Copy the contents of a directory into an existing one. Symlinks in the target are
+/// replaced instead of followed, so the copy can't be redirected outside of it.
+fn copy_entries(source: &Path, target: &Path) -> std::io::Result<u64> {
+	let mut copied = 0;
+
+	for entry in std::fs::read_dir(source)? {
+		let entry = entry?;
+		let file_type = entry.file_type()?;
+		let target = target.join(entry.file_name());
+
+		if file_type.is_dir() {
+			match std::fs::symlink_metadata(&target) {
+				Ok(metadata) if metadata.is_dir() => {}
+				Ok(metadata) if metadata.is_symlink() => {
+					std::fs::remove_file(&target)?;
+					std::fs::create_dir(&target)?;
+				}
+				_ => std::fs::create_dir(&target)?,
+			}
+
+			copied += copy_entries(&entry.path(), &target)?;
+		} else if file_type.is_file() {
+			copy_file(&entry.path(), &target)?;
+			copied += 1;
+		}
+	}
+
+	Ok(copied)
+}
+
+/// Internal: Copy a file with its permissions. An existing file, symlink or hard link at the
+/// target is removed first and the copy is created as a new file, so it isn't written through.
+fn copy_file(source: &Path, target: &Path) -> std::io::Result<()> {
+	match std::fs::remove_file(target) {
+		Err(err) if err.kind() != ErrorKind::NotFound => return Err(err),
+		_ => {}
+	}
+
+	let mut reader = std::fs::File::open(source)?;
+	let mut writer = std::fs::OpenOptions::new()
+		.write(true)
+		.create_new(true)
+		.open(target)?;
+
+	std::io::copy(&mut reader, &mut writer)?;
+	writer.set_permissions(reader.metadata()?.permissions())
+}
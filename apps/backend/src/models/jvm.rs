@@ -0,0 +1,121 @@
+use crate::models::file_schemas::server_config::{GcPreset, JvmSettings, ServerConfig};
+use crate::models::game::Game;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+/// Aikar's flags switch to larger young generation settings from this heap size
+const AIKAR_LARGE_HEAP: u64 = 12 * 1024 * 1024 * 1024;
+
+/// The command line a server is launched with
+#[derive(TS, Debug, Clone, Serialize, Deserialize)]
+#[ts(export)]
+pub struct LaunchCommand {
+	/// Command the program is run through, if any
+	pub wrapper: Vec<String>,
+	pub program: String,
+	pub args: Vec<String>,
+}
+
+impl LaunchCommand {
+	/// Resolve the command line from a server's config. JVM arguments generated from the
+	/// structured settings come first, so the extra arguments can fine-tune them.
+	pub fn resolve(config: &ServerConfig, binary_path: &str) -> Self {
+		match config.game {
+			Game::MinecraftJava(_) => {
+				let mut args = jvm_args(&config.jvm);
+				args.extend(config.args.iter().cloned());
+				args.extend(["-jar".to_string(), binary_path.to_string()]);
+				args.extend(config.jvm.program_args.iter().cloned());
+
+				Self {
+					wrapper: config.wrapper.clone(),
+					program: "java".to_string(),
+					args,
+				}
+			}
+		}
+	}
+}
+
+/// Internal: Generate the JVM arguments for the heap and garbage collector settings
+fn jvm_args(jvm: &JvmSettings) -> Vec<String> {
+	let mut args = Vec::new();
+
+	// Aikar's flags expect a fixed heap size
+	let min_heap = match jvm.gc {
+		GcPreset::Aikar => jvm.min_heap.or(jvm.max_heap),
+		_ => jvm.min_heap,
+	};
+
+	if let Some(heap) = min_heap {
+		args.push(format!("-Xms{}", format_java_size(heap)));
+	}
+
+	if let Some(heap) = jvm.max_heap {
+		args.push(format!("-Xmx{}", format_java_size(heap)));
+	}
+
+	let gc_flags: &[&str] = match jvm.gc {
+		GcPreset::None => &[],
+		GcPreset::G1 => &["-XX:+UseG1GC"],
+		GcPreset::Zgc => &["-XX:+UseZGC"],
+		GcPreset::Aikar => &[
+			"-XX:+UseG1GC",
+			"-XX:+ParallelRefProcEnabled",
+			"-XX:MaxGCPauseMillis=200",
+			"-XX:+UnlockExperimentalVMOptions",
+			"-XX:+DisableExplicitGC",
+			"-XX:+AlwaysPreTouch",
+			"-XX:G1HeapWastePercent=5",
+			"-XX:G1MixedGCCountTarget=4",
+			"-XX:G1MixedGCLiveThresholdPercent=90",
+			"-XX:G1RSetUpdatingPauseTimePercent=5",
+			"-XX:SurvivorRatio=32",
+			"-XX:+PerfDisableSharedMem",
+			"-XX:MaxTenuringThreshold=1",
+			"-Dusing.aikars.flags=https://mcflags.emc.gs",
+			"-Daikars.new.flags=true",
+		],
+	};
+
+	args.extend(gc_flags.iter().map(ToString::to_string));
+
+	if jvm.gc == GcPreset::Aikar {
+		let large_heap = jvm.max_heap.is_some_and(|heap| heap >= AIKAR_LARGE_HEAP);
+
+		let sizing: [&str; 5] = if large_heap {
+			[
+				"-XX:G1NewSizePercent=40",
+				"-XX:G1MaxNewSizePercent=50",
+				"-XX:G1HeapRegionSize=16M",
+				"-XX:G1ReservePercent=15",
+				"-XX:InitiatingHeapOccupancyPercent=20",
+			]
+		} else {
+			[
+				"-XX:G1NewSizePercent=30",
+				"-XX:G1MaxNewSizePercent=40",
+				"-XX:G1HeapRegionSize=8M",
+				"-XX:G1ReservePercent=20",
+				"-XX:InitiatingHeapOccupancyPercent=15",
+			]
+		};
+
+		args.extend(sizing.iter().map(ToString::to_string));
+	}
+
+	args
+}
+
+/// Internal: Format a size in bytes with the largest unit that represents it exactly
+fn format_java_size(bytes: u64) -> String {
+	const UNITS: [(u64, &str); 3] = [(1024 * 1024 * 1024, "G"), (1024 * 1024, "M"), (1024, "K")];
+
+	UNITS
+		.iter()
+		.find(|(size, _)| bytes.is_multiple_of(*size))
+		.map_or_else(
+			|| bytes.to_string(),
+			|(size, unit)| format!("{}{unit}", bytes / size),
+		)
+}
@@ -0,0 +1,91 @@
+use crate::models::file_schemas::server_config::ServerConfig;
+use crate::models::server::ServerStateInfo;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use uuid::Uuid;
+
+/// Memory of the host, as reported by the kernel
+#[derive(TS, Debug, Clone, Copy, Serialize, Deserialize)]
+#[ts(export)]
+pub struct HostMemory {
+	/// Physical memory in bytes
+	pub total: u64,
+	/// Memory in bytes that can be used without swapping, including reclaimable caches
+	pub available: u64,
+}
+
+impl HostMemory {
+	/// Read the host's memory from `/proc/meminfo`. Only available on Linux.
+	pub fn read() -> Option<Self> {
+		let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+
+		let field = |name: &str| {
+			meminfo.lines().find_map(|line| {
+				let value = line.strip_prefix(name)?.strip_prefix(':')?;
+				let kib: u64 = value.trim().strip_suffix("kB")?.trim().parse().ok()?;
+				Some(kib * 1024)
+			})
+		};
+
+		let total = field("MemTotal")?;
+
+		Some(Self {
+			total,
+			available: field("MemAvailable").unwrap_or(total),
+		})
+	}
+}
+
+/// Memory a server is expected to use
+#[derive(TS, Clone, Serialize, Deserialize)]
+#[ts(export)]
+pub struct ServerMemoryUsage {
+	pub id: Uuid,
+	/// Memory in bytes
+	pub memory: u64,
+	pub state: ServerStateInfo,
+	pub queued: bool,
+}
+
+/// Memory of the host compared to what the servers are expected to use
+#[derive(TS, Clone, Serialize, Deserialize)]
+#[ts(export)]
+pub struct MemoryUsage {
+	pub host: HostMemory,
+	/// Memory in bytes running servers may use in total
+	pub budget: u64,
+	/// Memory in bytes the running and starting servers are expected to use
+	pub allocated: u64,
+	pub servers: Vec<ServerMemoryUsage>,
+}
+
+/// Parse a JVM memory size such as `4G`, `512m` or `1048576`
+pub fn parse_java_size(size: &str) -> Option<u64> {
+	let (digits, multiplier) = match size.chars().last()?.to_ascii_lowercase() {
+		'k' => (&size[..size.len() - 1], 1024),
+		'm' => (&size[..size.len() - 1], 1024 * 1024),
+		'g' => (&size[..size.len() - 1], 1024 * 1024 * 1024),
+		't' => (&size[..size.len() - 1], 1024 * 1024 * 1024 * 1024),
+		_ => (size, 1),
+	};
+
+	digits.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+/// Get the maximum heap size set with `-Xmx` in JVM arguments. The JVM uses the last one.
+pub fn max_heap_size(args: &[String]) -> Option<u64> {
+	args.iter()
+		.rev()
+		.find_map(|arg| arg.strip_prefix("-Xmx"))
+		.and_then(parse_java_size)
+}
+
+/// Memory in bytes a server is expected to use. Uses the configured memory, or else the maximum
+/// heap size. Without either, the JVM defaults to a quarter of the host's memory.
+pub fn server_memory(config: &ServerConfig, host_total: u64) -> u64 {
+	config
+		.memory
+		.or(config.jvm.max_heap)
+		.or_else(|| max_heap_size(&config.args))
+		.unwrap_or(host_total / 4)
+}
@@ -1,6 +1,17 @@
+pub mod cgroup;
+pub mod console;
+pub mod console_reader;
+pub mod console_style;
 pub mod file_manager;
 pub mod file_schemas;
 pub mod game;
 pub mod hash;
+pub mod hooks;
+pub mod jvm;
+pub mod memory;
+pub mod ports;
+pub mod sandbox;
 pub mod secrets;
 pub mod server;
+pub mod ssh;
+pub mod upload;
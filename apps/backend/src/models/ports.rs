@@ -0,0 +1,220 @@
+use crate::models::file_schemas::server_properties::{
+	ServerProperties, SERVER_PROPERTIES_FILE_NAME,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::net::{Ipv4Addr, TcpListener, UdpSocket};
+use std::path::Path;
+use std::sync::Mutex;
+use thiserror::Error;
+use ts_rs::TS;
+use uuid::Uuid;
+
+/// Port Minecraft servers listen on when `server.properties` doesn't set one
+pub const DEFAULT_GAME_PORT: u16 = 25565;
+const DEFAULT_RCON_PORT: u16 = 25575;
+/// Config file of Velocity proxies, which are run like any other server jar
+const VELOCITY_CONFIG_FILE_NAME: &str = "velocity.toml";
+
+/// What a server uses a port for
+#[derive(TS, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[ts(export)]
+#[serde(rename_all = "snake_case")]
+pub enum PortKind {
+	Game,
+	Query,
+	Rcon,
+	Proxy,
+}
+
+impl fmt::Display for PortKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			PortKind::Game => "game",
+			PortKind::Query => "query",
+			PortKind::Rcon => "RCON",
+			PortKind::Proxy => "proxy",
+		})
+	}
+}
+
+/// A port a server listens on
+#[derive(TS, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[ts(export)]
+pub struct PortBinding {
+	pub kind: PortKind,
+	pub port: u16,
+}
+
+impl PortBinding {
+	/// The query protocol is the only one served over UDP
+	fn is_udp(self) -> bool {
+		self.kind == PortKind::Query
+	}
+
+	/// Check that no other process on the host is listening on the port
+	pub fn check_host(self) -> Result<(), PortError> {
+		let address = (Ipv4Addr::UNSPECIFIED, self.port);
+
+		let available = if self.is_udp() {
+			UdpSocket::bind(address).is_ok()
+		} else {
+			TcpListener::bind(address).is_ok()
+		};
+
+		if available {
+			Ok(())
+		} else {
+			Err(PortError::UsedByProcess(self))
+		}
+	}
+}
+
+impl fmt::Display for PortBinding {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{} ({})", self.port, self.kind)
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum PortError {
+	#[error("Port {0} is used by server {1}")]
+	UsedByServer(PortBinding, Uuid),
+	#[error("Port {0} is used by another process")]
+	UsedByProcess(PortBinding),
+	#[error("No free port left in the range {0}-{1}")]
+	RangeExhausted(u16, u16),
+}
+
+/// Ports held by the running servers
+#[derive(Default)]
+pub struct PortRegistry {
+	claims: Mutex<HashMap<Uuid, Vec<PortBinding>>>,
+}
+
+impl PortRegistry {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Claim ports for a starting server. Fails without claiming anything if another server
+	/// holds one of them.
+	pub fn claim(&self, server_id: Uuid, bindings: &[PortBinding]) -> Result<(), PortError> {
+		let mut claims = self.claims.lock().unwrap();
+
+		for (other_id, other_bindings) in claims.iter() {
+			if *other_id == server_id {
+				continue;
+			}
+
+			let conflict = bindings.iter().find(|binding| {
+				other_bindings
+					.iter()
+					.any(|other| other.port == binding.port && other.is_udp() == binding.is_udp())
+			});
+
+			if let Some(binding) = conflict {
+				return Err(PortError::UsedByServer(*binding, *other_id));
+			}
+		}
+
+		claims.insert(server_id, bindings.to_vec());
+		Ok(())
+	}
+
+	/// Release the ports of a server that stopped
+	pub fn release(&self, server_id: Uuid) {
+		self.claims.lock().unwrap().remove(&server_id);
+	}
+
+	/// Pick the first port in a range that isn't in `taken`, held by a running server, or used by
+	/// another process
+	pub fn allocate(&self, range: (u16, u16), taken: &HashSet<u16>) -> Result<u16, PortError> {
+		let claimed: HashSet<u16> = self
+			.claims
+			.lock()
+			.unwrap()
+			.values()
+			.flatten()
+			.map(|binding| binding.port)
+			.collect();
+
+		(range.0..=range.1)
+			.filter(|port| !taken.contains(port) && !claimed.contains(port))
+			.find(|port| {
+				let binding = PortBinding {
+					kind: PortKind::Game,
+					port: *port,
+				};
+				binding.check_host().is_ok()
+			})
+			.ok_or(PortError::RangeExhausted(range.0, range.1))
+	}
+}
+
+/// Read the ports a server is configured to listen on from the files in its directory
+pub fn server_ports(server_dir: &Path) -> Vec<PortBinding> {
+	let properties = std::fs::read_to_string(server_dir.join(SERVER_PROPERTIES_FILE_NAME))
+		.ok()
+		.map(|content| ServerProperties::parse(&content));
+	let proxy_port = velocity_port(server_dir);
+
+	let mut bindings = Vec::new();
+
+	if let Some(port) = proxy_port {
+		bindings.push(PortBinding {
+			kind: PortKind::Proxy,
+			port,
+		});
+	}
+
+	// Proxies don't read `server.properties`, game servers fall back to defaults without it
+	if properties.is_none() && proxy_port.is_some() {
+		return bindings;
+	}
+
+	let properties = properties.unwrap_or_default();
+
+	let port = |key: &str, default: u16| {
+		properties
+			.get(key)
+			.and_then(|value| value.trim().parse().ok())
+			.unwrap_or(default)
+	};
+	let enabled = |key: &str| {
+		properties
+			.get(key)
+			.is_some_and(|value| value.trim() == "true")
+	};
+
+	bindings.push(PortBinding {
+		kind: PortKind::Game,
+		port: port("server-port", DEFAULT_GAME_PORT),
+	});
+
+	if enabled("enable-query") {
+		bindings.push(PortBinding {
+			kind: PortKind::Query,
+			port: port("query.port", DEFAULT_GAME_PORT),
+		});
+	}
+
+	if enabled("enable-rcon") {
+		bindings.push(PortBinding {
+			kind: PortKind::Rcon,
+			port: port("rcon.port", DEFAULT_RCON_PORT),
+		});
+	}
+
+	bindings
+}
+
+/// Internal: Read the port a Velocity proxy binds to, e.g. `bind = "0.0.0.0:25577"`
+fn velocity_port(server_dir: &Path) -> Option<u16> {
+	let content = std::fs::read_to_string(server_dir.join(VELOCITY_CONFIG_FILE_NAME)).ok()?;
+	let config: toml::Table = toml::from_str(&content).ok()?;
+	let bind = config.get("bind")?.as_str()?;
+
+	bind.rsplit_once(':')?.1.parse().ok()
+}
@@ -0,0 +1,250 @@
+use crate::config::{SANDBOX_BWRAP_BINARY, SANDBOX_PATH, SANDBOX_SYSTEM_PATHS};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::Command;
+use uuid::Uuid;
+
+#[derive(Debug, Error)]
+pub enum SandboxError {
+	#[error("{0} was not found, install bubblewrap to sandbox servers")]
+	BwrapNotFound(&'static str),
+	#[error("{0} was not found")]
+	ExecutableNotFound(String),
+	#[error("No such user: {0}")]
+	NoSuchUser(String),
+	#[error("Servers can't run as {0}, which is root or the panel's own user")]
+	PrivilegedUser(String),
+	#[error("Failed to hand the server directory to {0}: {1}")]
+	HandOver(String, std::io::Error),
+}
+
+/// A Unix user a server process runs as
+#[derive(Debug, Clone, Copy)]
+pub struct UnixUser {
+	pub uid: u32,
+	pub gid: u32,
+}
+
+impl UnixUser {
+	/// Look up a user by name in `/etc/passwd`
+	pub fn lookup(name: &str) -> Result<Self, SandboxError> {
+		let passwd = std::fs::read_to_string("/etc/passwd")
+			.map_err(|_| SandboxError::NoSuchUser(name.to_string()))?;
+
+		passwd
+			.lines()
+			.find_map(|line| {
+				let mut fields = line.split(':');
+
+				if fields.next()? != name {
+					return None;
+				}
+
+				let uid = fields.nth(1)?.parse().ok()?;
+				let gid = fields.next()?.parse().ok()?;
+
+				Some(Self { uid, gid })
+			})
+			.ok_or_else(|| SandboxError::NoSuchUser(name.to_string()))
+	}
+
+	/// Look up a user a server may run as. Root and the panel's own user would give the server
+	/// access to everything the panel can reach.
+	pub fn lookup_unprivileged(name: &str) -> Result<Self, SandboxError> {
+		let user = Self::lookup(name)?;
+
+		if user.uid == 0 || current_uid() == Some(user.uid) {
+			return Err(SandboxError::PrivilegedUser(name.to_string()));
+		}
+
+		Ok(user)
+	}
+
+	/// Give the user ownership of everything in a directory that it doesn't own yet, so the
+	/// server can write to files created through the panel
+	#[cfg(unix)]
+	pub fn hand_over(self, dir: &Path) -> std::io::Result<()> {
+		use std::os::unix::fs::{lchown, MetadataExt};
+
+		let metadata = std::fs::symlink_metadata(dir)?;
+
+		if metadata.uid() != self.uid || metadata.gid() != self.gid {
+			lchown(dir, Some(self.uid), Some(self.gid))?;
+		}
+
+		if metadata.is_dir() {
+			for entry in std::fs::read_dir(dir)? {
+				self.hand_over(&entry?.path())?;
+			}
+		}
+
+		Ok(())
+	}
+
+	#[cfg(not(unix))]
+	pub fn hand_over(self, _dir: &Path) -> std::io::Result<()> {
+		Err(std::io::ErrorKind::Unsupported.into())
+	}
+
+	/// Run a command as the user. Users are only looked up on Unix.
+	pub fn apply(self, cmd: &mut Command) {
+		#[cfg(unix)]
+		{
+			cmd.uid(self.uid);
+			cmd.gid(self.gid);
+		}
+	}
+}
+
+/// Internal: Effective user ID of the panel, which owns its `/proc` entry
+#[cfg(unix)]
+fn current_uid() -> Option<u32> {
+	use std::os::unix::fs::MetadataExt;
+
+	std::fs::metadata("/proc/self")
+		.ok()
+		.map(|metadata| metadata.uid())
+}
+
+#[cfg(not(unix))]
+fn current_uid() -> Option<u32> {
+	None
+}
+
+/// Read a file the panel edits in a server directory. The server can replace the file, so a
+/// symlink at the path isn't followed to keep files outside of the directory from being read.
+/// Returns `None` if the file doesn't exist or is a symlink.
+pub async fn read_no_follow(path: &Path) -> std::io::Result<Option<Vec<u8>>> {
+	let mut options = tokio::fs::OpenOptions::new();
+	options.read(true);
+	#[cfg(unix)]
+	options.custom_flags(libc::O_NOFOLLOW);
+
+	let mut file = match options.open(path).await {
+		Ok(file) => file,
+		Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+		#[cfg(unix)]
+		Err(err) if err.raw_os_error() == Some(libc::ELOOP) => return Ok(None),
+		Err(err) => return Err(err),
+	};
+
+	let mut content = Vec::new();
+	file.read_to_end(&mut content).await?;
+	Ok(Some(content))
+}
+
+/// Replace a file the panel writes in a server directory. The content goes to a new file next
+/// to it, which then replaces whatever is at the path, so a symlink or hard link planted by the
+/// server isn't written through.
+pub async fn replace_file(path: &Path, content: &[u8]) -> std::io::Result<()> {
+	let name = path
+		.file_name()
+		.ok_or_else(|| std::io::Error::from(std::io::ErrorKind::InvalidInput))?;
+	let mut temp_name = OsString::from(".");
+	temp_name.push(name);
+	temp_name.push(format!(".{}.tmp", Uuid::new_v4()));
+	let temp_path = path.with_file_name(temp_name);
+
+	let mut file = tokio::fs::OpenOptions::new()
+		.write(true)
+		.create_new(true)
+		.open(&temp_path)
+		.await?;
+
+	let result = async {
+		file.write_all(content).await?;
+		file.flush().await?;
+		tokio::fs::rename(&temp_path, path).await
+	}
+	.await;
+
+	if result.is_err() {
+		let _ = tokio::fs::remove_file(&temp_path).await;
+	}
+
+	result
+}
+
+/// Find an executable in `PATH`, resolving symlinks to its actual location
+pub fn find_executable(name: &str) -> Result<PathBuf, SandboxError> {
+	std::env::var_os("PATH")
+		.iter()
+		.flat_map(std::env::split_paths)
+		.map(|dir| dir.join(name))
+		.find(|path| path.is_file())
+		.and_then(|path| path.canonicalize().ok())
+		.ok_or_else(|| SandboxError::ExecutableNotFound(name.to_string()))
+}
+
+/// Build a command that runs a program in a bubblewrap sandbox. The sandbox has its own user,
+/// mount and pid namespaces and no capabilities. Only system libraries, the `read_only` paths
+/// and the server directory are visible, so the panel's data stays out of reach. The network is
+/// shared with the host, as players need to reach the server.
+pub fn sandboxed_command(
+	program: &Path,
+	args: &[OsString],
+	server_dir: &Path,
+	read_only: &[PathBuf],
+) -> Result<Command, SandboxError> {
+	let bwrap = find_executable(SANDBOX_BWRAP_BINARY)
+		.map_err(|_| SandboxError::BwrapNotFound(SANDBOX_BWRAP_BINARY))?;
+
+	let mut cmd = Command::new(bwrap);
+
+	cmd.args([
+		"--unshare-user",
+		"--unshare-pid",
+		"--unshare-ipc",
+		"--unshare-uts",
+		"--unshare-cgroup-try",
+		"--die-with-parent",
+		"--new-session",
+		"--cap-drop",
+		"ALL",
+		"--proc",
+		"/proc",
+		"--dev",
+		"/dev",
+		"--tmpfs",
+		"/tmp",
+	]);
+
+	for path in SANDBOX_SYSTEM_PATHS
+		.iter()
+		.map(PathBuf::from)
+		.chain(java_config_dirs())
+	{
+		cmd.arg("--ro-bind-try").arg(&path).arg(&path);
+	}
+
+	for path in read_only {
+		cmd.arg("--ro-bind").arg(path).arg(path);
+	}
+
+	cmd.arg("--bind").arg(server_dir).arg(server_dir);
+	cmd.arg("--chdir").arg(server_dir);
+
+	// Nothing from the panel's environment is passed on
+	cmd.env_clear();
+	cmd.env("PATH", SANDBOX_PATH);
+	cmd.env("HOME", server_dir);
+
+	cmd.arg("--").arg(program).args(args);
+
+	Ok(cmd)
+}
+
+/// Internal: Distributions like Debian keep parts of the JVM's configuration in `/etc/java-*`
+fn java_config_dirs() -> Vec<PathBuf> {
+	let Ok(entries) = std::fs::read_dir("/etc") else {
+		return Vec::new();
+	};
+
+	entries
+		.filter_map(Result::ok)
+		.filter(|entry| entry.file_name().to_string_lossy().starts_with("java"))
+		.map(|entry| entry.path())
+		.collect()
+}
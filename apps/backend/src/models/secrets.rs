@@ -1,5 +1,10 @@
 use std::path::Path;
 
+use aes_gcm::{
+	aead::{Aead, AeadCore, KeyInit, OsRng as AeadOsRng},
+	Aes256Gcm, Key, Nonce,
+};
+use base64::{engine::general_purpose, Engine};
 use jsonwebtoken;
 use pem::Pem;
 use rsa::{
@@ -70,3 +75,91 @@ impl Secrets {
 		Secrets { jwt_enc, jwt_dec }
 	}
 }
+
+/// Length of the nonce prepended to encrypted values
+const NONCE_LENGTH: usize = 12;
+
+/// Encrypts secrets the panel stores on disk, such as secret environment variables of servers
+pub struct SecretCipher {
+	cipher: Aes256Gcm,
+}
+
+impl SecretCipher {
+	/// Load the encryption key from the secrets directory, creating it on first use
+	pub fn new(base_dir: &Path) -> SecretCipher {
+		let secrets_dir = base_dir.join("secrets/");
+		if !secrets_dir.exists() {
+			std::fs::create_dir_all(&secrets_dir).expect("Read/write should be available");
+		}
+
+		let key_path = secrets_dir.join("secrets.key");
+
+		let key = match std::fs::read(&key_path) {
+			Ok(key) if key.len() == 32 => *Key::<Aes256Gcm>::from_slice(&key),
+			Ok(_) => panic!("Secret key {} is corrupted", key_path.display()),
+			Err(_) => {
+				let key = Aes256Gcm::generate_key(AeadOsRng);
+				std::fs::write(&key_path, key).expect("Should have write access");
+				key
+			}
+		};
+
+		SecretCipher {
+			cipher: Aes256Gcm::new(&key),
+		}
+	}
+
+	/// Encrypt a value into base64 text
+	pub fn encrypt(&self, plaintext: &str) -> String {
+		let nonce = Aes256Gcm::generate_nonce(AeadOsRng);
+		let ciphertext = self
+			.cipher
+			.encrypt(&nonce, plaintext.as_bytes())
+			.expect("Encryption should not fail");
+
+		let mut data = nonce.to_vec();
+		data.extend(ciphertext);
+
+		general_purpose::STANDARD.encode(data)
+	}
+
+	/// Decrypt a value produced by `encrypt`. Returns None if it was tampered with or encrypted
+	/// with another key.
+	pub fn decrypt(&self, encrypted: &str) -> Option<String> {
+		let data = general_purpose::STANDARD.decode(encrypted).ok()?;
+
+		if data.len() < NONCE_LENGTH {
+			return None;
+		}
+
+		let (nonce, ciphertext) = data.split_at(NONCE_LENGTH);
+		let plaintext = self
+			.cipher
+			.decrypt(Nonce::from_slice(nonce), ciphertext)
+			.ok()?;
+
+		String::from_utf8(plaintext).ok()
+	}
+}
+
+/// Load the host key of the SSH server from the secrets directory, creating it on first use
+pub fn load_ssh_host_key(base_dir: &Path) -> ssh_key::PrivateKey {
+	let secrets_dir = base_dir.join("secrets/");
+	if !secrets_dir.exists() {
+		std::fs::create_dir_all(&secrets_dir).expect("Read/write should be available");
+	}
+
+	let key_path = secrets_dir.join("ssh_host_ed25519_key");
+
+	if key_path.exists() {
+		return ssh_key::PrivateKey::read_openssh_file(&key_path)
+			.unwrap_or_else(|_| panic!("SSH host key {} is corrupted", key_path.display()));
+	}
+
+	let key = ssh_key::PrivateKey::random(&mut OsRng, ssh_key::Algorithm::Ed25519)
+		.expect("Arguments should be sufficient");
+	key.write_openssh_file(&key_path, ssh_key::LineEnding::LF)
+		.expect("Should have write access");
+
+	key
+}
@@ -1,26 +1,59 @@
 use crate::config;
 use crate::config::server_dir;
+use crate::config::server_meta_dir;
 use crate::config::SERVER_CONFIG_FILE_NAME;
+use crate::config::SERVER_CONSOLE_BROADCAST_CAPACITY;
+use crate::config::SERVER_CONSOLE_DRAIN_TIMEOUT;
 use crate::config::SERVER_CONSOLE_MAX_LINES;
+use crate::config::SERVER_CONSOLE_MAX_LINE_LENGTH;
 use crate::config::SERVER_WATCHER_TICK;
-use crate::models::file_manager::{scoped::ScopedFileManager, FileManager};
+use crate::db::models::user::User;
+use crate::models::cgroup::{CgroupError, ServerCgroup};
+use crate::models::console::{ConsoleFilter, LogFields, LogParser};
+use crate::models::console_reader::ConsoleReader;
+use crate::models::console_style::{parse_styled, ConsoleFormat, StyledSpan};
+use crate::models::file_manager::{
+	history::FileHistory,
+	policy::PathPolicy,
+	scoped::ScopedFileManager,
+	trash::Trash,
+	usage::{DiskUsage, DiskUsageInfo},
+	FileManager,
+};
+use crate::models::file_schemas::env_secrets::{EnvSecrets, ENV_SECRETS_FILE_NAME};
+use crate::models::file_schemas::server_config::EulaAcceptance;
 use crate::models::file_schemas::server_config::PartialServerConfig;
 use crate::models::file_schemas::server_config::ServerConfig;
-use crate::models::game::Game;
+use crate::models::file_schemas::server_config::{
+	validate_env, validate_wrapper, ConfigError, EnvVar, LifecycleHook,
+};
+use crate::models::file_schemas::server_properties::{ServerProperties, EULA_FILE_NAME};
+use crate::models::hooks::{run_hook, HookContext};
+use crate::models::jvm::LaunchCommand;
+use crate::models::memory::server_memory;
+use crate::models::ports::{server_ports, PortError, PortRegistry};
+use crate::models::sandbox::{
+	find_executable, read_no_follow, replace_file, sandboxed_command, SandboxError, UnixUser,
+};
+use crate::models::secrets::SecretCipher;
+use crate::models::upload::UploadManager;
 use crate::services::binary::BinaryService;
 use serde::Deserialize;
 use serde::Serialize;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
 use std::sync::atomic::AtomicU64;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use thiserror::Error;
-use tokio::io::AsyncBufReadExt;
+use time::OffsetDateTime;
 use tokio::io::AsyncRead;
 use tokio::io::AsyncWriteExt;
-use tokio::io::BufReader;
 use tokio::process::Child;
 use tokio::process::Command;
+use tokio::sync::broadcast;
 use tokio::sync::mpsc;
 use tokio::sync::watch;
 use tokio::sync::RwLock;
@@ -46,14 +79,206 @@ pub enum ServerError {
 	NotRunning,
 	#[error("No such server: {0}")]
 	NoSuchServer(String),
+	#[error("Invalid server config: {0}")]
+	InvalidConfig(String),
+	#[error("The EULA has not been accepted")]
+	EulaNotAccepted,
+	#[error("Failed to accept the EULA: {0}")]
+	EulaError(String),
+	#[error("Port unavailable: {0}")]
+	PortUnavailable(PortError),
+	#[error("Failed to apply resource limits: {0}")]
+	ResourceLimits(CgroupError),
+	#[error("Failed to sandbox server: {0}")]
+	Sandbox(SandboxError),
+	#[error(
+		"Not enough memory: the server needs {} MiB, but only {} MiB are left for servers",
+		required / (1024 * 1024),
+		available / (1024 * 1024)
+	)]
+	InsufficientMemory { required: u64, available: u64 },
+	#[error("Failed to access secrets: {0}")]
+	Secrets(String),
+	#[error("Failed to save server config: {0}")]
+	SaveConfig(ConfigError),
+	#[error("{0}")]
+	HookFailed(String),
 }
 
+/// Internal: Build the command a server is launched with, through its wrapper and sandbox
+fn build_command(
+	config: &ServerConfig,
+	binary_path: &str,
+	server_dir: &Path,
+) -> Result<Command, ServerError> {
+	let launch = LaunchCommand::resolve(config, binary_path);
+	let sandbox = &config.sandbox;
+
+	// The sandbox only exposes the JVM's own installation
+	let (java, read_only) = if sandbox.enabled {
+		let java = find_executable(&launch.program).map_err(ServerError::Sandbox)?;
+		let java_home = java
+			.parent()
+			.and_then(Path::parent)
+			.unwrap_or(&java)
+			.to_path_buf();
+
+		(
+			java.into_os_string(),
+			vec![java_home, PathBuf::from(binary_path)],
+		)
+	} else {
+		(OsString::from(&launch.program), Vec::new())
+	};
+
+	// A wrapper is run in place of the JVM and starts it in turn
+	let mut command_line: Vec<OsString> = launch.wrapper.iter().map(OsString::from).collect();
+	command_line.push(java);
+	command_line.extend(launch.args.iter().map(OsString::from));
+
+	let (program, args) = command_line
+		.split_first()
+		.expect("Command line should contain the JVM");
+
+	if sandbox.enabled {
+		sandboxed_command(Path::new(program), args, server_dir, &read_only)
+			.map_err(ServerError::Sandbox)
+	} else {
+		let mut cmd = Command::new(program);
+		cmd.args(args);
+		Ok(cmd)
+	}
+}
+
+/// Internal: Encrypt the values of secret environment variables into the secrets file and remove
+/// them from the variables. Secrets without a value keep their stored one.
+fn store_env_secrets(
+	path: &Path,
+	cipher: &SecretCipher,
+	env: &mut BTreeMap<String, EnvVar>,
+) -> Result<(), ServerError> {
+	let mut secrets =
+		EnvSecrets::load_from_file(path).map_err(|err| ServerError::Secrets(err.to_string()))?;
+
+	encrypt_env_secrets(&mut secrets, cipher, env)?;
+	secrets
+		.save_to_file(path)
+		.map_err(|err| ServerError::Secrets(err.to_string()))
+}
+
+/// Internal: Move the values of secret environment variables into the secrets, dropping
+/// secrets of variables that are gone or not secret anymore
+fn encrypt_env_secrets(
+	secrets: &mut EnvSecrets,
+	cipher: &SecretCipher,
+	env: &mut BTreeMap<String, EnvVar>,
+) -> Result<(), ServerError> {
+	for (name, var) in env.iter_mut().filter(|(_, var)| var.secret) {
+		match var.value.take() {
+			Some(value) => secrets.set(name, &value, cipher),
+			None if !secrets.contains(name) => {
+				return Err(ServerError::InvalidConfig(format!(
+					"Secret environment variable {name} has no value"
+				)));
+			}
+			None => {}
+		}
+	}
+
+	secrets.retain(|name| env.get(name).is_some_and(|var| var.secret));
+	Ok(())
+}
+
+/// Internal: Validate the changed parts of a config before any of them are applied
+fn validate_config_update(
+	config: &ServerConfig,
+	new_config: &PartialServerConfig,
+) -> Result<(), ServerError> {
+	// JVM settings depend on the extra arguments and the memory limit
+	if new_config.jvm.is_some() || new_config.args.is_some() || new_config.limits.is_some() {
+		let jvm = new_config.jvm.as_ref().unwrap_or(&config.jvm);
+		let args = new_config.args.as_ref().unwrap_or(&config.args);
+		let limits = new_config.limits.as_ref().unwrap_or(&config.limits);
+
+		jvm.validate(args, limits)
+			.map_err(ServerError::InvalidConfig)?;
+	}
+
+	if let Some(wrapper) = &new_config.wrapper {
+		validate_wrapper(wrapper).map_err(ServerError::InvalidConfig)?;
+	}
+
+	if let Some(hooks) = &new_config.hooks {
+		hooks.validate().map_err(ServerError::InvalidConfig)?;
+	}
+
+	if let Some(limits) = &new_config.limits {
+		limits.validate().map_err(ServerError::InvalidConfig)?;
+	}
+
+	if let Some(user) = new_config.sandbox.as_ref().and_then(|s| s.user.as_deref()) {
+		UnixUser::lookup_unprivileged(user)
+			.map_err(|err| ServerError::InvalidConfig(err.to_string()))?;
+	}
+
+	if let Some(env) = &new_config.env {
+		validate_env(env).map_err(ServerError::InvalidConfig)?;
+	}
+
+	if let Some(history) = &new_config.history {
+		FileHistory::compile_globs(&history.globs).map_err(ServerError::InvalidConfig)?;
+	}
+
+	if let Some(path_rules) = &new_config.path_rules {
+		PathPolicy::compile_rules(path_rules).map_err(ServerError::InvalidConfig)?;
+	}
+
+	Ok(())
+}
+
+/// Header Minecraft writes to a new `eula.txt`
+const EULA_HEADER: &str = "#By changing the setting below to TRUE you are indicating your \
+	agreement to our EULA (https://aka.ms/MinecraftEULA).";
+
 #[derive(Clone, Serialize, Deserialize, ts_rs::TS)]
 #[ts(export)]
 pub struct ConsoleLine {
 	pub num: u64,
 	pub stream: ConsoleStreamType,
 	pub line: String,
+	/// The line without escape sequences and formatting codes
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub plain: Option<String>,
+	/// The line split into styled spans
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub spans: Option<Vec<StyledSpan>>,
+	#[serde(flatten)]
+	#[ts(flatten)]
+	pub log: LogFields,
+	/// Number of the first line of the log entry this line continues, e.g. for stack traces
+	pub continues: Option<u64>,
+}
+
+impl ConsoleLine {
+	/// The line without escape sequences and formatting codes
+	pub fn plain(&self) -> &str {
+		self.plain.as_deref().unwrap_or(&self.line)
+	}
+
+	/// Copy the line with only the representations the format includes
+	pub fn formatted(&self, format: ConsoleFormat) -> Self {
+		let mut line = self.clone();
+
+		if format == ConsoleFormat::Raw {
+			line.plain = None;
+		}
+
+		if format != ConsoleFormat::Rich {
+			line.spans = None;
+		}
+
+		line
+	}
 }
 
 #[derive(Clone, Copy, Serialize, Deserialize, ts_rs::TS)]
@@ -61,6 +286,8 @@ pub struct ConsoleLine {
 pub enum ConsoleStreamType {
 	Stdout,
 	Stderr,
+	/// Output of lifecycle hooks
+	Hook,
 }
 
 #[derive(Debug, Clone)]
@@ -72,6 +299,8 @@ pub enum ProcessCommand {
 pub struct ServerRuntime {
 	pub command_tx: mpsc::Sender<ProcessCommand>,
 	pub running_rx: watch::Receiver<bool>,
+	/// Cgroup enforcing the server's resource limits, if it has any
+	pub cgroup: Option<Arc<ServerCgroup>>,
 }
 
 impl ServerRuntime {
@@ -101,6 +330,8 @@ pub enum ServerProcessState {
 	Stopped,
 	Starting,
 	Running(Arc<ServerRuntime>),
+	/// The process has exited and the post-stop hooks are running
+	Stopping,
 }
 
 impl ServerProcessState {
@@ -109,6 +340,7 @@ impl ServerProcessState {
 			ServerProcessState::Stopped => ServerStateInfo::Stopped,
 			ServerProcessState::Starting => ServerStateInfo::Starting,
 			ServerProcessState::Running(_) => ServerStateInfo::Running,
+			ServerProcessState::Stopping => ServerStateInfo::Stopping,
 		}
 	}
 }
@@ -120,6 +352,59 @@ pub enum ServerStateInfo {
 	Stopped,
 	Running,
 	Starting,
+	Stopping,
+}
+
+/// Why a server process ended
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, TS, PartialEq)]
+#[ts(export)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerExitReason {
+	/// Exited with code 0, e.g. after the stop command
+	Exited,
+	/// Exited with a non-zero code
+	Crashed,
+	/// Ended by a signal
+	Killed,
+	/// Killed by the kernel for exceeding its memory limit
+	OutOfMemory,
+}
+
+/// How the last run of a server ended
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct ServerExitInfo {
+	pub reason: ServerExitReason,
+	pub code: Option<i32>,
+	pub signal: Option<i32>,
+	/// Exit time as a Unix timestamp in seconds
+	pub exited_at: i64,
+}
+
+impl ServerExitInfo {
+	fn new(status: ExitStatus, oom_kills: u64) -> Self {
+		#[cfg(unix)]
+		let signal = std::os::unix::process::ExitStatusExt::signal(&status);
+		#[cfg(not(unix))]
+		let signal = None;
+
+		let reason = if oom_kills > 0 {
+			ServerExitReason::OutOfMemory
+		} else if signal.is_some() {
+			ServerExitReason::Killed
+		} else if status.success() {
+			ServerExitReason::Exited
+		} else {
+			ServerExitReason::Crashed
+		};
+
+		Self {
+			reason,
+			code: status.code(),
+			signal,
+			exited_at: OffsetDateTime::now_utc().unix_timestamp(),
+		}
+	}
 }
 
 /// Information about a server instance for listing purposes
@@ -129,6 +414,8 @@ pub struct ServerInfo {
 	pub id: Uuid,
 	pub config: ServerConfig,
 	pub state: ServerStateInfo,
+	pub disk: DiskUsageInfo,
+	pub last_exit: Option<ServerExitInfo>,
 }
 
 /// Server instance representation
@@ -138,26 +425,74 @@ pub struct Server {
 	process: RwLock<ServerProcessState>,
 	console_lines: RwLock<VecDeque<ConsoleLine>>,
 	next_line_num: AtomicU64,
+	/// Sends each console line as it is pushed
+	console_tx: broadcast::Sender<ConsoleLine>,
 	vfs: Arc<dyn FileManager>,
+	history: Arc<FileHistory>,
+	disk_usage: Arc<DiskUsage>,
+	path_policy: Arc<PathPolicy>,
+	trash: Arc<Trash>,
+	uploads: UploadManager,
+	ports: Arc<PortRegistry>,
+	last_exit: RwLock<Option<ServerExitInfo>>,
+	cipher: Arc<SecretCipher>,
 }
 
 impl Server {
 	/// Create a server instance from an ID by loading its config file. Returns None if loading fails.
-	pub fn new(uuid: Uuid) -> Result<Self, String> {
+	pub fn new(
+		uuid: Uuid,
+		ports: Arc<PortRegistry>,
+		cipher: Arc<SecretCipher>,
+	) -> Result<Self, String> {
 		let server_dir = server_dir(uuid)
 			.canonicalize()
 			.map_err(|err| err.to_string())?;
 
 		let config_path = server_dir.join(SERVER_CONFIG_FILE_NAME);
 
-		let server_config = match ServerConfig::load_from_file(config_path.clone()) {
+		let mut server_config = match ServerConfig::load_from_file(config_path.clone()) {
 			Ok(cfg) => cfg,
 			Err(e) => {
 				return Err(e.to_string());
 			}
 		};
 
-		let vfs = ScopedFileManager::new(server_dir);
+		// Secrets added to the config file by hand are moved out of it
+		if server_config
+			.env
+			.values()
+			.any(|var| var.secret && var.value.is_some())
+		{
+			let secrets_path = server_meta_dir(uuid).join(ENV_SECRETS_FILE_NAME);
+
+			let stored = store_env_secrets(&secrets_path, &cipher, &mut server_config.env)
+				.map_err(|err| err.to_string())
+				.and_then(|()| {
+					server_config
+						.save_to_file(config_path.clone())
+						.map_err(|err| err.to_string())
+				});
+
+			if let Err(err) = stored {
+				tracing::warn!("Failed to encrypt the secrets of server {}: {}", uuid, err);
+			}
+		}
+
+		let history = Arc::new(FileHistory::new(
+			server_meta_dir(uuid).join("history"),
+			server_config.history.clone(),
+		));
+		let disk_usage = DiskUsage::new(server_dir.clone(), server_config.disk_quota);
+		let path_policy = Arc::new(PathPolicy::new(&server_config.path_rules));
+		let vfs = ScopedFileManager::new(
+			server_dir,
+			Some(history.clone()),
+			Some(disk_usage.clone()),
+			Some(path_policy.clone()),
+		);
+		let trash = Trash::new(server_meta_dir(uuid).join("trash"));
+		let uploads = UploadManager::new(server_meta_dir(uuid).join("uploads"));
 
 		Ok(Self {
 			id: uuid,
@@ -165,7 +500,16 @@ impl Server {
 			process: RwLock::new(ServerProcessState::Stopped),
 			console_lines: RwLock::new(VecDeque::new()),
 			next_line_num: AtomicU64::new(0),
+			console_tx: broadcast::channel(SERVER_CONSOLE_BROADCAST_CAPACITY).0,
 			vfs: Arc::new(vfs),
+			history,
+			disk_usage,
+			path_policy,
+			trash,
+			uploads,
+			ports,
+			last_exit: RwLock::new(None),
+			cipher,
 		})
 	}
 
@@ -179,11 +523,15 @@ impl Server {
 	pub async fn get_server_info(&self) -> ServerInfo {
 		let config = self.config.read().await;
 		let state = self.process.read().await.info();
+		let disk = self.disk_usage.info();
+		let last_exit = self.last_exit.read().await.clone();
 
 		ServerInfo {
 			id: self.id,
 			config: config.clone(),
 			state,
+			disk,
+			last_exit,
 		}
 	}
 
@@ -195,9 +543,9 @@ impl Server {
 		let process_guard = self.process.read().await;
 
 		match &*process_guard {
-			ServerProcessState::Starting | ServerProcessState::Stopped => {
-				Err(ServerError::NotRunning)
-			}
+			ServerProcessState::Starting
+			| ServerProcessState::Stopped
+			| ServerProcessState::Stopping => Err(ServerError::NotRunning),
 			ServerProcessState::Running(runtime) => runtime
 				.send_line(command.to_string())
 				.await
@@ -205,7 +553,19 @@ impl Server {
 		}
 	}
 
-	/// Start the server instance
+	/// Mark a stopped server as starting. From then on it counts as using its memory, so memory
+	/// admission can let go before the pre-start hooks run. Must be followed by `start`.
+	pub async fn reserve_start(&self) -> Result<(), ServerError> {
+		let mut process_guard = self.process.write().await;
+		if !matches!(*process_guard, ServerProcessState::Stopped) {
+			return Err(ServerError::AlreadyRunning);
+		}
+
+		*process_guard = ServerProcessState::Starting;
+		Ok(())
+	}
+
+	/// Start the server instance after `reserve_start`. It is stopped again if the start fails.
 	#[instrument(name = "Server.StartServer", skip(self, binary_service))]
 	pub async fn start(
 		self: &Arc<Self>,
@@ -213,16 +573,144 @@ impl Server {
 	) -> Result<(), ServerError> {
 		tracing::info!("Starting server instance");
 
-		let mut process_guard = self.process.write().await;
-		if let ServerProcessState::Running(_) = *process_guard {
-			return Err(ServerError::AlreadyRunning);
+		// Reset console buffer and line number, so the output of hooks is part of the new run
+		self.next_line_num.store(0, Ordering::Relaxed);
+		self.console_lines.write().await.clear();
+
+		if let Err(err) = self.launch(binary_service).await {
+			*self.process.write().await = ServerProcessState::Stopped;
+			return Err(err);
 		}
 
-		*process_guard = ServerProcessState::Starting;
-		drop(process_guard);
+		Ok(())
+	}
+
+	/// Internal: Run the pre-start hooks, check that the server can start and spawn its process
+	async fn launch(
+		self: &Arc<Self>,
+		binary_service: Arc<BinaryService>,
+	) -> Result<(), ServerError> {
+		let hooks = self.config.read().await.hooks.pre_start.clone();
+		self.run_hooks("pre-start", &hooks).await?;
+
+		// Without an accepted EULA the server only writes `eula.txt` and exits
+		if !self.is_eula_accepted().await {
+			return Err(ServerError::EulaNotAccepted);
+		}
+
+		// Refuse to start instead of letting the server fail to bind deep in its console
+		let ports = server_ports(&server_dir(self.id));
+		self.ports
+			.claim(self.id, &ports)
+			.map_err(ServerError::PortUnavailable)?;
+
+		if let Some(err) = ports.iter().find_map(|binding| binding.check_host().err()) {
+			self.ports.release(self.id);
+			return Err(ServerError::PortUnavailable(err));
+		}
+
+		if let Err(err) = self.spawn_process(binary_service).await {
+			self.ports.release(self.id);
+			return Err(err);
+		}
+
+		Ok(())
+	}
+
+	/// Internal: Run hooks in order, writing their output to the console. Stops at the first hook
+	/// that fails.
+	async fn run_hooks(&self, stage: &str, hooks: &[LifecycleHook]) -> Result<(), ServerError> {
+		if hooks.is_empty() {
+			return Ok(());
+		}
+
+		let context = {
+			let config = self.config.read().await;
+			let user = config
+				.sandbox
+				.user
+				.as_deref()
+				.map(UnixUser::lookup_unprivileged)
+				.transpose()
+				.map_err(ServerError::Sandbox)?;
+
+			HookContext {
+				server_id: self.id,
+				server_dir: server_dir(self.id),
+				env: self.resolve_env(&config.env)?,
+				user,
+				sandboxed: config.sandbox.enabled,
+			}
+		};
+
+		let (output_tx, mut output_rx) = mpsc::unbounded_channel();
+
+		let run = async move {
+			for (index, hook) in hooks.iter().enumerate() {
+				let _ = output_tx.send(format!("Running {stage} hook {}", index + 1));
 
+				if let Err(err) = run_hook(hook, &context, &output_tx).await {
+					let message = format!("The {stage} hook {} failed: {err}", index + 1);
+					let _ = output_tx.send(message.clone());
+					return Err(ServerError::HookFailed(message));
+				}
+			}
+
+			Ok(())
+		};
+
+		let forward = async {
+			while let Some(line) = output_rx.recv().await {
+				self.push_console_line(ConsoleStreamType::Hook, line, None)
+					.await;
+			}
+		};
+
+		let (result, ()) = tokio::join!(run, forward);
+		result
+	}
+
+	/// Internal: Path of the file holding the server's encrypted environment variables
+	fn env_secrets_path(&self) -> PathBuf {
+		server_meta_dir(self.id).join(ENV_SECRETS_FILE_NAME)
+	}
+
+	/// Internal: Get the environment variables of the server process, decrypting secrets
+	fn resolve_env(
+		&self,
+		env: &BTreeMap<String, EnvVar>,
+	) -> Result<Vec<(String, String)>, ServerError> {
+		let secrets = EnvSecrets::load_from_file(&self.env_secrets_path())
+			.map_err(|err| ServerError::Secrets(err.to_string()))?;
+
+		env.iter()
+			.map(|(name, var)| {
+				let value = if var.secret {
+					secrets.get(name, &self.cipher).ok_or_else(|| {
+						ServerError::Secrets(format!("Failed to decrypt the value of {name}"))
+					})?
+				} else {
+					var.value.clone().unwrap_or_default()
+				};
+
+				Ok((name.clone(), value))
+			})
+			.collect()
+	}
+
+	/// Internal: Launch the server process and start watching it
+	async fn spawn_process(
+		self: &Arc<Self>,
+		binary_service: Arc<BinaryService>,
+	) -> Result<(), ServerError> {
 		let config_guard = self.config.read().await;
 
+		// Catches invalid settings from config files edited by hand
+		config_guard
+			.jvm
+			.validate(&config_guard.args, &config_guard.limits)
+			.map_err(ServerError::InvalidConfig)?;
+
 		// Build absolute paths for server binary and directory
 		let binary_path = binary_service
 			.ensure_binary(&config_guard.game)
@@ -239,34 +727,59 @@ impl Server {
 			.map_err(|e| ServerError::StartError(format!("Invalid server directory path: {e}")))?;
 
 		// Create command to start the server process
-		let mut cmd = match config_guard.game {
-			Game::MinecraftJava(_) => {
-				let mut cmd = Command::new("java");
+		let mut cmd = build_command(&config_guard, binary_path, &server_dir)?;
+		let sandbox = &config_guard.sandbox;
 
-				cmd.arg("-jar");
-				cmd.arg(binary_path);
+		cmd.envs(self.resolve_env(&config_guard.env)?);
 
-				for arg in &config_guard.args {
-					cmd.arg(arg);
-				}
+		if let Some(user) = &sandbox.user {
+			let unix_user = UnixUser::lookup_unprivileged(user).map_err(ServerError::Sandbox)?;
+			let dir = server_dir.clone();
 
-				cmd
-			}
-		};
+			tokio::task::spawn_blocking(move || unix_user.hand_over(&dir))
+				.await
+				.map_err(|err| ServerError::StartError(err.to_string()))?
+				.map_err(|err| ServerError::Sandbox(SandboxError::HandOver(user.clone(), err)))?;
+
+			unix_user.apply(&mut cmd);
+		}
 
 		cmd.current_dir(&server_dir);
 		cmd.stdin(std::process::Stdio::piped());
 		cmd.stdout(std::process::Stdio::piped());
 		cmd.stderr(std::process::Stdio::piped());
 
+		let cgroup = if config_guard.limits.is_empty() {
+			None
+		} else {
+			let cgroup = ServerCgroup::create(self.id, &config_guard.limits)
+				.map_err(ServerError::ResourceLimits)?;
+			Some(Arc::new(cgroup))
+		};
+
 		// Spawn child and create runtime
-		let mut child = cmd
-			.spawn()
-			.map_err(|e| ServerError::StartError(e.to_string()))?;
+		let spawned = match &cgroup {
+			Some(cgroup) => cgroup
+				.attach(&mut cmd)
+				.map_err(ServerError::ResourceLimits)
+				.and_then(|()| {
+					cmd.spawn()
+						.map_err(|e| ServerError::StartError(e.to_string()))
+				}),
+			None => cmd
+				.spawn()
+				.map_err(|e| ServerError::StartError(e.to_string())),
+		};
 
-		let stdin = child.stdin.take();
-		let stdout = child.stdout.take();
-		let stderr = child.stderr.take();
+		let child = match spawned {
+			Ok(child) => child,
+			Err(err) => {
+				if let Some(cgroup) = &cgroup {
+					cgroup.remove();
+				}
+				return Err(err);
+			}
+		};
 
 		let (command_tx, command_rx) = mpsc::channel::<ProcessCommand>(64);
 		let (running_tx, running_rx) = watch::channel(true);
@@ -274,6 +787,7 @@ impl Server {
 		let runtime = Arc::new(ServerRuntime {
 			command_tx,
 			running_rx,
+			cgroup,
 		});
 
 		// Set state to running
@@ -281,19 +795,7 @@ impl Server {
 		*process_guard = ServerProcessState::Running(runtime.clone());
 		drop(process_guard);
 
-		// Reset console buffer and line number
-		self.next_line_num.store(0, Ordering::Relaxed);
-		self.console_lines.write().await.clear();
-
-		Self::start_watcher(
-			self,
-			child,
-			running_tx.clone(),
-			command_rx,
-			stdin,
-			stdout,
-			stderr,
-		);
+		Self::start_watcher(self, child, &runtime, running_tx.clone(), command_rx);
 
 		Ok(())
 	}
@@ -321,9 +823,9 @@ impl Server {
 		let process_guard = self.process.read().await;
 
 		match &*process_guard {
-			ServerProcessState::Stopped | ServerProcessState::Starting => {
-				Err(ServerError::NotRunning)
-			}
+			ServerProcessState::Stopped
+			| ServerProcessState::Starting
+			| ServerProcessState::Stopping => Err(ServerError::NotRunning),
 			ServerProcessState::Running(runtime) => {
 				runtime.kill().await.map_err(ServerError::StopError)
 			}
@@ -336,39 +838,217 @@ impl Server {
 		Ok(server_state.info())
 	}
 
+	/// Get the memory in bytes the server is expected to use, and its state. Unlike the full
+	/// server info, this is cheap enough for memory admission.
+	pub async fn memory_info(&self, host_total: u64) -> (u64, ServerStateInfo) {
+		let memory = server_memory(&*self.config.read().await, host_total);
+		let state = self.process.read().await.info();
+
+		(memory, state)
+	}
+
+	/// Check whether `eula.txt` accepts the EULA
+	pub async fn is_eula_accepted(&self) -> bool {
+		let eula_path = server_dir(self.id).join(EULA_FILE_NAME);
+
+		let Ok(content) = tokio::fs::read_to_string(eula_path).await else {
+			return false;
+		};
+
+		// The server parses the value like a Java boolean
+		ServerProperties::parse(&content)
+			.get("eula")
+			.is_some_and(|value| value.trim().eq_ignore_ascii_case("true"))
+	}
+
+	/// Accept the EULA on behalf of a user. Writes `eula.txt` and records who accepted it in the
+	/// server's config.
+	#[instrument(name = "Server.AcceptEula", skip(self))]
+	pub async fn accept_eula(&self, user: &User) -> Result<EulaAcceptance, ServerError> {
+		let server_dir = server_dir(self.id);
+		let eula_path = server_dir.join(EULA_FILE_NAME);
+
+		let mut eula = match read_no_follow(&eula_path).await {
+			Ok(Some(content)) => ServerProperties::decode(content),
+			Ok(None) => ServerProperties::parse(EULA_HEADER),
+			Err(err) => return Err(ServerError::EulaError(err.to_string())),
+		};
+
+		eula.set("eula", "true");
+
+		replace_file(&eula_path, &eula.encode())
+			.await
+			.map_err(|err| ServerError::EulaError(err.to_string()))?;
+
+		let acceptance = EulaAcceptance {
+			user_id: user.id,
+			username: user.username.clone(),
+			accepted_at: OffsetDateTime::now_utc().unix_timestamp(),
+		};
+
+		let mut config_guard = self.config.write().await;
+		config_guard.eula = Some(acceptance.clone());
+		config_guard
+			.save_to_file(server_dir.join(SERVER_CONFIG_FILE_NAME))
+			.map_err(|err| ServerError::EulaError(err.to_string()))?;
+
+		tracing::info!("EULA accepted by {}", user.username);
+
+		Ok(acceptance)
+	}
+
 	/// Update server config
 	#[instrument(name = "Server.UpdateConfig", skip(self))]
 	pub async fn update_config(&self, new_config: PartialServerConfig) -> Result<(), ServerError> {
 		let mut config_guard = self.config.write().await;
+		validate_config_update(&config_guard, &new_config)?;
+
+		let mut updated = config_guard.clone();
+		let mut secrets = None;
+
+		if let Some(mut env) = new_config.env {
+			let previous = EnvSecrets::load_from_file(&self.env_secrets_path())
+				.map_err(|err| ServerError::Secrets(err.to_string()))?;
+			let mut stored = previous.clone();
+			encrypt_env_secrets(&mut stored, &self.cipher, &mut env)?;
+			secrets = Some((stored, previous));
+			updated.env = env;
+		}
+
+		if let Some(wrapper) = new_config.wrapper {
+			updated.wrapper = wrapper;
+		}
+
+		if let Some(hooks) = new_config.hooks {
+			updated.hooks = hooks;
+		}
 
 		if let Some(name) = new_config.name {
-			config_guard.name = name;
+			updated.name = name;
 		}
 
 		if let Some(game) = new_config.game {
-			config_guard.game = game;
+			updated.game = game;
 		}
 
 		if let Some(args) = new_config.args {
-			config_guard.args = args;
+			updated.args = args;
+		}
+
+		if let Some(jvm) = new_config.jvm {
+			updated.jvm = jvm;
 		}
 
 		if let Some(stop_command) = new_config.stop_command {
-			config_guard.stop_command = stop_command;
+			updated.stop_command = stop_command;
+		}
+
+		if let Some(memory) = new_config.memory {
+			updated.memory = (memory > 0).then_some(memory);
+		}
+
+		// Takes effect on the next start
+		if let Some(sandbox) = new_config.sandbox {
+			updated.sandbox = sandbox;
+		}
+
+		if let Some(limits) = new_config.limits {
+			// Running servers get the new limits right away, others when they are started
+			if let ServerProcessState::Running(runtime) = &*self.process.read().await {
+				if let Some(cgroup) = &runtime.cgroup {
+					cgroup
+						.apply(&limits)
+						.map_err(|err| ServerError::InvalidConfig(err.to_string()))?;
+				}
+			}
+
+			updated.limits = limits;
+		}
+
+		let history = new_config.history;
+		let path_rules = new_config.path_rules;
+		let disk_quota = new_config
+			.disk_quota
+			.map(|disk_quota| (disk_quota > 0).then_some(disk_quota));
+
+		if let Some(history) = &history {
+			updated.history = history.clone();
 		}
 
+		if let Some(path_rules) = &path_rules {
+			updated.path_rules.clone_from(path_rules);
+		}
+
+		if let Some(disk_quota) = disk_quota {
+			updated.disk_quota = disk_quota;
+		}
+
+		self.save_config(&updated, secrets)?;
+
+		if let Some(history) = history {
+			self.history
+				.set_config(history)
+				.map_err(ServerError::InvalidConfig)?;
+		}
+
+		if let Some(path_rules) = path_rules {
+			self.path_policy
+				.set_rules(&path_rules)
+				.map_err(ServerError::InvalidConfig)?;
+		}
+
+		if let Some(disk_quota) = disk_quota {
+			self.disk_usage.set_quota(disk_quota);
+		}
+
+		*config_guard = updated;
 		Ok(())
 	}
 
-	/// Get a snapshot of the server's console output
+	/// Internal: Write the config file and, with the new and previous secrets, the secrets file.
+	/// The previous secrets are restored if the config can't be written, so a restart never
+	/// finds secret variables without values.
+	fn save_config(
+		&self,
+		config: &ServerConfig,
+		secrets: Option<(EnvSecrets, EnvSecrets)>,
+	) -> Result<(), ServerError> {
+		let secrets_path = self.env_secrets_path();
+
+		if let Some((stored, _)) = &secrets {
+			stored
+				.save_to_file(&secrets_path)
+				.map_err(|err| ServerError::Secrets(err.to_string()))?;
+		}
+
+		if let Err(err) = config.save_to_file(server_dir(self.id).join(SERVER_CONFIG_FILE_NAME)) {
+			if let Some((_, previous)) = secrets {
+				if let Err(err) = previous.save_to_file(&secrets_path) {
+					tracing::error!(
+						"Failed to restore the secrets of server {}: {}",
+						self.id,
+						err
+					);
+				}
+			}
+
+			return Err(ServerError::SaveConfig(err));
+		}
+
+		Ok(())
+	}
+
+	/// Get a snapshot of the server's console output, with only the lines matching the filter
 	pub async fn get_console_snapshot(
 		&self,
 		since_line: Option<u64>,
+		filter: &ConsoleFilter,
 	) -> Result<Vec<ConsoleLine>, ServerError> {
 		let lines = self.console_lines.read().await;
 		let iter = lines
 			.iter()
-			.filter(|l| since_line.is_none_or(|s| l.num > s));
+			.filter(|l| since_line.is_none_or(|s| l.num > s))
+			.filter(|l| filter.matches(l));
 
 		Ok(iter.take(SERVER_CONSOLE_MAX_LINES).cloned().collect())
 	}
@@ -378,6 +1058,48 @@ impl Server {
 		self.vfs.clone()
 	}
 
+	/// Get the server's file history
+	pub fn get_history(&self) -> &FileHistory {
+		&self.history
+	}
+
+	/// Get the server's disk usage tracker
+	pub fn get_disk_usage(&self) -> &DiskUsage {
+		&self.disk_usage
+	}
+
+	/// Get the server's path access rules
+	pub fn get_path_policy(&self) -> &PathPolicy {
+		&self.path_policy
+	}
+
+	/// Get the server's trash
+	pub fn get_trash(&self) -> &Trash {
+		&self.trash
+	}
+
+	/// Receive console lines as they are written. Lines of all runs are received, until the
+	/// server is dropped.
+	pub fn subscribe_console(&self) -> broadcast::Receiver<ConsoleLine> {
+		self.console_tx.subscribe()
+	}
+
+	/// Record the last run of the server as crashed, e.g. when its output shows it is broken
+	/// although the process kept running. Running out of memory is the more specific reason
+	/// and is kept.
+	pub async fn mark_crashed(&self) {
+		if let Some(exit) = self.last_exit.write().await.as_mut() {
+			if exit.reason != ServerExitReason::OutOfMemory {
+				exit.reason = ServerExitReason::Crashed;
+			}
+		}
+	}
+
+	/// Get the server's upload session manager
+	pub fn get_uploads(&self) -> &UploadManager {
+		&self.uploads
+	}
+
 	/// Internal: Generic reader task for stdout/stderr of a server process
 	fn reader_task<R: AsyncRead + Unpin + Send + 'static>(
 		server: Arc<Server>,
@@ -385,48 +1107,133 @@ impl Server {
 		stream: ConsoleStreamType,
 	) -> JoinHandle<()> {
 		tokio::spawn(async move {
-			let mut lines = BufReader::new(reader).lines();
-			while let Ok(Some(line)) = lines.next_line().await {
-				// Push line to console buffer and increase line number
-				let num = server.next_line_num.fetch_add(1, Ordering::Relaxed);
-				let mut buf = server.console_lines.write().await;
-
-				if buf.len() >= SERVER_CONSOLE_MAX_LINES {
-					buf.pop_front();
+			let mut reader = ConsoleReader::new(reader, SERVER_CONSOLE_MAX_LINE_LENGTH);
+			let mut parser = LogParser::default();
+
+			loop {
+				match reader.next_line().await {
+					Ok(Some(line)) => {
+						server
+							.push_console_line(stream, line, Some(&mut parser))
+							.await;
+					}
+					Ok(None) => break,
+					Err(err) => {
+						tracing::warn!("Failed to read console output: {}", err);
+						break;
+					}
 				}
-
-				buf.push_back(ConsoleLine { num, stream, line });
 			}
 		})
 	}
 
+	/// Internal: Push a line to the console buffer and increase the line number. Lines of the
+	/// server's output are parsed as log lines with the parser of their stream.
+	async fn push_console_line(
+		&self,
+		stream: ConsoleStreamType,
+		line: String,
+		parser: Option<&mut LogParser>,
+	) {
+		let mut buf = self.console_lines.write().await;
+		let num = self.next_line_num.fetch_add(1, Ordering::Relaxed);
+
+		if buf.len() >= SERVER_CONSOLE_MAX_LINES {
+			buf.pop_front();
+		}
+
+		let spans = parse_styled(&line);
+		let plain: String = spans.iter().map(|span| span.text.as_str()).collect();
+
+		// Paper may color the log layout as well
+		let (log, continues) = parser
+			.map(|parser| parser.parse(num, &plain))
+			.unwrap_or_default();
+
+		let line = ConsoleLine {
+			num,
+			stream,
+			line,
+			plain: Some(plain),
+			spans: Some(spans),
+			log,
+			continues,
+		};
+
+		// Nobody listening is fine
+		let _ = self.console_tx.send(line.clone());
+		buf.push_back(line);
+	}
+
 	/// Internal: Spawns a watcher task for a server process.
 	fn start_watcher(
 		server: &Arc<Server>,
-		child: Child,
+		mut child: Child,
+		runtime: &ServerRuntime,
 		running_tx: watch::Sender<bool>,
 		command_rx: mpsc::Receiver<ProcessCommand>,
-		stdin: Option<tokio::process::ChildStdin>,
-		stdout: Option<tokio::process::ChildStdout>,
-		stderr: Option<tokio::process::ChildStderr>,
 	) {
-		let stdout_reader =
+		let stdin = child.stdin.take();
+		let stdout = child.stdout.take();
+		let stderr = child.stderr.take();
+		let cgroup = runtime.cgroup.clone();
+
+		let mut stdout_reader =
 			Self::reader_task(server.clone(), stdout.unwrap(), ConsoleStreamType::Stdout);
-		let stderr_reader =
+		let mut stderr_reader =
 			Self::reader_task(server.clone(), stderr.unwrap(), ConsoleStreamType::Stderr);
 
 		let server_for_watcher = server.clone();
 		let watcher = async move {
-			Self::watcher_loop(child, command_rx, stdin).await;
+			let status = Self::watcher_loop(child, command_rx, stdin).await;
 
-			// End readers once the process has exited
-			stdout_reader.abort();
-			stderr_reader.abort();
+			// Let readers finish what the process wrote before exiting. Its children may keep the
+			// pipes open, so end readers that don't finish in time.
+			let drain = async { tokio::join!(&mut stdout_reader, &mut stderr_reader) };
 
-			// Update state to stopped
+			if tokio::time::timeout(SERVER_CONSOLE_DRAIN_TIMEOUT, drain)
+				.await
+				.is_err()
+			{
+				stdout_reader.abort();
+				stderr_reader.abort();
+			}
+
+			let oom_kills = cgroup.as_ref().map_or(0, |cgroup| cgroup.oom_kills());
+
+			if let Some(cgroup) = &cgroup {
+				cgroup.remove();
+			}
+
+			if let Some(status) = status {
+				let exit = ServerExitInfo::new(status, oom_kills);
+
+				if exit.reason == ServerExitReason::OutOfMemory {
+					tracing::warn!("Server process was killed for exceeding its memory limit");
+				}
+
+				*server_for_watcher.last_exit.write().await = Some(exit);
+			}
+
+			// The server can't be started again until its post-stop hooks are done
 			let _ = running_tx.send(false);
-			let mut guard = server_for_watcher.process.write().await;
-			*guard = ServerProcessState::Stopped;
+			*server_for_watcher.process.write().await = ServerProcessState::Stopping;
+			server_for_watcher.ports.release(server_for_watcher.id);
+
+			let hooks = server_for_watcher
+				.config
+				.read()
+				.await
+				.hooks
+				.post_stop
+				.clone();
+
+			if let Err(err) = server_for_watcher.run_hooks("post-stop", &hooks).await {
+				tracing::warn!("{}", err);
+			}
+
+			// Update state to stopped
+			*server_for_watcher.process.write().await = ServerProcessState::Stopped;
 		};
 
 		tokio::spawn(watcher.instrument(
@@ -435,12 +1242,12 @@ impl Server {
 	}
 
 	/// Internal: Watcher loop function that handles process monitoring and command execution.
-	/// Exits on process termination or on error.
+	/// Exits on process termination or on error, returning the exit status if there is one.
 	async fn watcher_loop(
 		mut child: Child,
 		mut command_rx: mpsc::Receiver<ProcessCommand>,
 		mut stdin: Option<tokio::process::ChildStdin>,
-	) {
+	) -> Option<ExitStatus> {
 		let mut ticker = tokio::time::interval(SERVER_WATCHER_TICK);
 
 		loop {
@@ -471,7 +1278,7 @@ impl Server {
 					match child.try_wait() {
 						Ok(Some(status)) => {
 							tracing::info!("Server process exited: {}", status);
-							break;
+							return Some(status);
 						}
 						Ok(None) => {}
 						Err(e) => {
@@ -482,5 +1289,7 @@ impl Server {
 				}
 			}
 		}
+
+		None
 	}
 }
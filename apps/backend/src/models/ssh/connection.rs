@@ -0,0 +1,574 @@
+use crate::config::{SSH_CHANNEL_WINDOW, SSH_LOGIN_TIMEOUT, SSH_MAX_AUTH_ATTEMPTS};
+use crate::models::ssh::transport::Transport;
+use crate::models::ssh::wire::{Reader, Writer};
+use crate::models::ssh::{disconnect, msg, SshError};
+use async_trait::async_trait;
+use bytes::{Buf, BytesMut};
+use rsa::signature::Verifier;
+use ssh_key::{PublicKey, Signature};
+use std::sync::Arc;
+use tokio::io::{
+	AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream, ReadHalf, WriteHalf,
+};
+
+/// Largest channel data packet the server accepts and sends
+const CHANNEL_MAX_PACKET: u32 = 32 * 1024;
+/// ID of the only channel of a connection
+const CHANNEL_ID: u32 = 0;
+const AUTH_METHODS: &str = "publickey,password";
+
+/// Authenticates users of an SSH server and serves its subsystems
+#[async_trait]
+pub trait SshHandler: Send + Sync {
+	/// What a connection is authenticated as
+	type Identity: Send + Sync;
+
+	/// Authenticate a user with a password
+	async fn auth_password(&self, username: &str, password: &str) -> Option<Self::Identity>;
+
+	/// Authenticate a user with a key. This is also used to check whether a key would be
+	/// accepted before the client proves that it holds the private key.
+	async fn auth_public_key(&self, username: &str, key: &PublicKey) -> Option<Self::Identity>;
+
+	/// Start a subsystem such as `sftp` for a channel, returning the stream it is served on
+	fn subsystem(&self, identity: &Self::Identity, name: &str) -> Option<DuplexStream>;
+}
+
+/// Serve a client that connected, until it disconnects
+pub async fn serve<S, H>(
+	stream: S,
+	host_key: Arc<ssh_key::PrivateKey>,
+	handler: &H,
+) -> Result<(), SshError>
+where
+	S: AsyncRead + AsyncWrite + Unpin,
+	H: SshHandler,
+{
+	let login = async {
+		let mut transport = Transport::accept(stream, host_key).await?;
+
+		match authenticate(&mut transport, handler).await {
+			Ok(identity) => Ok((transport, identity)),
+			Err(err) => {
+				transport
+					.disconnect(err.disconnect_reason(), &err.to_string())
+					.await;
+				Err(err)
+			}
+		}
+	};
+
+	let (mut transport, identity) = tokio::time::timeout(SSH_LOGIN_TIMEOUT, login)
+		.await
+		.map_err(|_| SshError::Protocol("Login timed out"))??;
+
+	match run_channel(&mut transport, handler, &identity).await {
+		Ok(()) | Err(SshError::Disconnected) => Ok(()),
+		Err(err) => {
+			transport
+				.disconnect(err.disconnect_reason(), &err.to_string())
+				.await;
+			Err(err)
+		}
+	}
+}
+
+/// Internal: Run the `ssh-userauth` service until the client authenticated
+async fn authenticate<S, H>(
+	transport: &mut Transport<S>,
+	handler: &H,
+) -> Result<H::Identity, SshError>
+where
+	S: AsyncRead + AsyncWrite + Unpin,
+	H: SshHandler,
+{
+	let mut service_accepted = false;
+	let mut failures = 0;
+
+	loop {
+		let message = transport.next_message().await?;
+		let mut reader = Reader::new(&message[1..]);
+
+		match message[0] {
+			msg::KEXINIT => transport.exchange_keys(Some(message)).await?,
+			msg::SERVICE_REQUEST => {
+				if reader.utf8()? != "ssh-userauth" {
+					transport
+						.disconnect(disconnect::SERVICE_NOT_AVAILABLE, "Unknown service")
+						.await;
+					return Err(SshError::Disconnected);
+				}
+
+				service_accepted = true;
+				let accept = Writer::message(msg::SERVICE_ACCEPT)
+					.string("ssh-userauth")
+					.finish();
+				transport.send(&accept).await?;
+			}
+			msg::USERAUTH_REQUEST if service_accepted => {
+				let session_id = transport.session_id().to_vec();
+
+				match auth_request(&mut reader, &session_id, handler).await? {
+					AuthOutcome::Success(identity) => {
+						transport.send(&[msg::USERAUTH_SUCCESS]).await?;
+						return Ok(identity);
+					}
+					AuthOutcome::KeyAccepted(reply) => transport.send(&reply).await?,
+					AuthOutcome::Failure { counts } => {
+						if counts {
+							failures += 1;
+						}
+
+						if failures >= SSH_MAX_AUTH_ATTEMPTS {
+							return Err(SshError::AuthenticationFailed);
+						}
+
+						let failure = Writer::message(msg::USERAUTH_FAILURE)
+							.string(AUTH_METHODS)
+							.bool(false)
+							.finish();
+						transport.send(&failure).await?;
+					}
+				}
+			}
+			_ => {
+				return Err(SshError::Protocol(
+					"Unexpected message before authentication",
+				))
+			}
+		}
+	}
+}
+
+enum AuthOutcome<I> {
+	Success(I),
+	/// The client may use the key, and has to sign a request with it next
+	KeyAccepted(Vec<u8>),
+	/// Failed attempts count towards the limit, while a client asking for the methods with
+	/// `none` or trying unsupported ones doesn't
+	Failure {
+		counts: bool,
+	},
+}
+
+/// Internal: Handle an `SSH_MSG_USERAUTH_REQUEST` of RFC 4252
+async fn auth_request<H: SshHandler>(
+	reader: &mut Reader<'_>,
+	session_id: &[u8],
+	handler: &H,
+) -> Result<AuthOutcome<H::Identity>, SshError> {
+	let username = reader.utf8()?;
+	let service = reader.utf8()?;
+	let method = reader.utf8()?;
+
+	if service != "ssh-connection" {
+		return Ok(AuthOutcome::Failure { counts: true });
+	}
+
+	match method {
+		"password" => {
+			// Changing expired passwords isn't supported
+			if reader.bool()? {
+				return Ok(AuthOutcome::Failure { counts: true });
+			}
+
+			let password = reader.utf8()?;
+
+			Ok(match handler.auth_password(username, password).await {
+				Some(identity) => AuthOutcome::Success(identity),
+				None => AuthOutcome::Failure { counts: true },
+			})
+		}
+		"publickey" => {
+			let signed = reader.bool()?;
+			let algorithm = reader.utf8()?;
+			let key_blob = reader.string()?;
+
+			let Ok(key) = PublicKey::from_bytes(key_blob) else {
+				return Ok(AuthOutcome::Failure { counts: true });
+			};
+
+			if !signed {
+				let accepted = handler.auth_public_key(username, &key).await.is_some();
+
+				return Ok(if accepted {
+					AuthOutcome::KeyAccepted(
+						Writer::message(msg::USERAUTH_PK_OK)
+							.string(algorithm)
+							.string(key_blob)
+							.finish(),
+					)
+				} else {
+					AuthOutcome::Failure { counts: true }
+				});
+			}
+
+			let Ok(signature) = Signature::try_from(reader.string()?) else {
+				return Ok(AuthOutcome::Failure { counts: true });
+			};
+
+			let signed_data = Writer::default()
+				.string(session_id)
+				.u8(msg::USERAUTH_REQUEST)
+				.string(username)
+				.string(service)
+				.string(method)
+				.bool(true)
+				.string(algorithm)
+				.string(key_blob)
+				.finish();
+
+			if signature.algorithm().as_str() != algorithm
+				|| Verifier::verify(&key, &signed_data, &signature).is_err()
+			{
+				return Ok(AuthOutcome::Failure { counts: true });
+			}
+
+			Ok(match handler.auth_public_key(username, &key).await {
+				Some(identity) => AuthOutcome::Success(identity),
+				None => AuthOutcome::Failure { counts: true },
+			})
+		}
+		_ => Ok(AuthOutcome::Failure { counts: false }),
+	}
+}
+
+/// A session channel, the only kind of channel the server supports
+struct Channel {
+	remote_id: u32,
+	/// Bytes the server may still send
+	remote_window: u32,
+	remote_max_packet: u32,
+	/// Bytes the client may still send
+	local_window: u32,
+	/// Output of the subsystem
+	reader: Option<ReadHalf<DuplexStream>>,
+	/// Input of the subsystem
+	writer: Option<WriteHalf<DuplexStream>>,
+	/// Data received from the client that the subsystem didn't read yet
+	pending: BytesMut,
+	eof_received: bool,
+	close_sent: bool,
+}
+
+enum Event {
+	Message(Vec<u8>),
+	/// The subsystem wrote output into the buffer
+	Output(usize),
+	/// The subsystem read input
+	Input(usize),
+}
+
+/// Internal: Run the `ssh-connection` service with a single session channel
+async fn run_channel<S, H>(
+	transport: &mut Transport<S>,
+	handler: &H,
+	identity: &H::Identity,
+) -> Result<(), SshError>
+where
+	S: AsyncRead + AsyncWrite + Unpin,
+	H: SshHandler,
+{
+	let mut channel: Option<Channel> = None;
+	let mut output = vec![0; CHANNEL_MAX_PACKET as usize];
+
+	loop {
+		let event = match channel.as_mut() {
+			Some(channel) => {
+				let limit = channel.remote_window.min(channel.remote_max_packet) as usize;
+				let limit = limit.min(output.len());
+
+				tokio::select! {
+					message = transport.next_message() => Event::Message(message?),
+					read = read_output(channel.reader.as_mut(), &mut output[..limit]) => {
+						Event::Output(read?)
+					}
+					written = write_input(channel.writer.as_mut(), &channel.pending) => {
+						Event::Input(written?)
+					}
+				}
+			}
+			None => Event::Message(transport.next_message().await?),
+		};
+
+		match event {
+			Event::Message(message) => {
+				handle_message(transport, handler, identity, &mut channel, message).await?;
+			}
+			Event::Output(read) => {
+				let Some(channel) = channel.as_mut() else {
+					continue;
+				};
+
+				if read == 0 {
+					// The subsystem ended
+					channel.reader = None;
+					close_channel(transport, channel).await?;
+					continue;
+				}
+
+				let data = Writer::message(msg::CHANNEL_DATA)
+					.u32(channel.remote_id)
+					.string(&output[..read])
+					.finish();
+				transport.send(&data).await?;
+				channel.remote_window -= u32::try_from(read).expect("Read is limited by window");
+			}
+			Event::Input(written) => {
+				let Some(channel) = channel.as_mut() else {
+					continue;
+				};
+
+				channel.pending.advance(written);
+				adjust_window(transport, channel).await?;
+				shutdown_input(channel).await;
+			}
+		}
+	}
+}
+
+/// Internal: Handle a message of the connection protocol of RFC 4254
+async fn handle_message<S, H>(
+	transport: &mut Transport<S>,
+	handler: &H,
+	identity: &H::Identity,
+	channel: &mut Option<Channel>,
+	message: Vec<u8>,
+) -> Result<(), SshError>
+where
+	S: AsyncRead + AsyncWrite + Unpin,
+	H: SshHandler,
+{
+	let mut reader = Reader::new(&message[1..]);
+
+	match message[0] {
+		msg::KEXINIT => transport.exchange_keys(Some(message)).await?,
+		msg::GLOBAL_REQUEST => {
+			let _name = reader.string()?;
+			if reader.bool()? {
+				transport.send(&[msg::REQUEST_FAILURE]).await?;
+			}
+		}
+		msg::CHANNEL_OPEN => {
+			let reply = open_session(&mut reader, channel)?;
+			transport.send(&reply).await?;
+		}
+		msg::CHANNEL_REQUEST => {
+			let channel = open_channel(channel, reader.u32()?)?;
+
+			if let Some(reply) = channel_request(&mut reader, handler, identity, channel)? {
+				transport.send(&reply).await?;
+			}
+		}
+		msg::CHANNEL_DATA | msg::CHANNEL_EXTENDED_DATA => {
+			let channel = open_channel(channel, reader.u32()?)?;
+			let extended = message[0] == msg::CHANNEL_EXTENDED_DATA;
+			if extended {
+				reader.u32()?;
+			}
+			let data = reader.string()?;
+
+			let length = u32::try_from(data.len()).map_err(|_| SshError::Malformed)?;
+			if length > channel.local_window || channel.eof_received {
+				return Err(SshError::Protocol("Client exceeded the channel window"));
+			}
+			channel.local_window -= length;
+
+			// Only the subsystem's standard input is used
+			if !extended && channel.writer.is_some() {
+				channel.pending.extend_from_slice(data);
+			} else {
+				adjust_window(transport, channel).await?;
+			}
+		}
+		msg::CHANNEL_WINDOW_ADJUST => {
+			let channel = open_channel(channel, reader.u32()?)?;
+			channel.remote_window = channel.remote_window.saturating_add(reader.u32()?);
+		}
+		msg::CHANNEL_EOF => {
+			let channel = open_channel(channel, reader.u32()?)?;
+			channel.eof_received = true;
+			shutdown_input(channel).await;
+		}
+		msg::CHANNEL_CLOSE => {
+			let mut closed = channel
+				.take()
+				.ok_or(SshError::Protocol("No channel is open"))?;
+			close_channel(transport, &mut closed).await?;
+		}
+		_ => {
+			let unimplemented = Writer::message(msg::UNIMPLEMENTED)
+				.u32(transport.last_seq())
+				.finish();
+			transport.send(&unimplemented).await?;
+		}
+	}
+
+	Ok(())
+}
+
+/// Internal: Handle an `SSH_MSG_CHANNEL_OPEN`, returning the reply
+fn open_session(
+	reader: &mut Reader<'_>,
+	channel: &mut Option<Channel>,
+) -> Result<Vec<u8>, SshError> {
+	let channel_type = reader.utf8()?;
+	let sender = reader.u32()?;
+	let window = reader.u32()?;
+	let max_packet = reader.u32()?;
+
+	if channel_type != "session" || channel.is_some() {
+		return Ok(Writer::message(msg::CHANNEL_OPEN_FAILURE)
+			.u32(sender)
+			// SSH_OPEN_ADMINISTRATIVELY_PROHIBITED
+			.u32(1)
+			.string("Only a single session channel is supported")
+			.string("")
+			.finish());
+	}
+
+	*channel = Some(Channel {
+		remote_id: sender,
+		remote_window: window,
+		remote_max_packet: max_packet,
+		local_window: SSH_CHANNEL_WINDOW,
+		reader: None,
+		writer: None,
+		pending: BytesMut::new(),
+		eof_received: false,
+		close_sent: false,
+	});
+
+	Ok(Writer::message(msg::CHANNEL_OPEN_CONFIRMATION)
+		.u32(sender)
+		.u32(CHANNEL_ID)
+		.u32(SSH_CHANNEL_WINDOW)
+		.u32(CHANNEL_MAX_PACKET)
+		.finish())
+}
+
+/// Internal: Handle an `SSH_MSG_CHANNEL_REQUEST`, returning the reply if the client wants one.
+/// Only subsystems are supported, there are no shells or commands.
+fn channel_request<H: SshHandler>(
+	reader: &mut Reader<'_>,
+	handler: &H,
+	identity: &H::Identity,
+	channel: &mut Channel,
+) -> Result<Option<Vec<u8>>, SshError> {
+	let request = reader.utf8()?;
+	let want_reply = reader.bool()?;
+
+	let started = request == "subsystem" && channel.reader.is_none() && {
+		let name = reader.utf8()?;
+
+		match handler.subsystem(identity, name) {
+			Some(stream) => {
+				let (reader, writer) = tokio::io::split(stream);
+				channel.reader = Some(reader);
+				channel.writer = Some(writer);
+				true
+			}
+			None => false,
+		}
+	};
+
+	if !want_reply {
+		return Ok(None);
+	}
+
+	let reply = if started {
+		msg::CHANNEL_SUCCESS
+	} else {
+		msg::CHANNEL_FAILURE
+	};
+
+	Ok(Some(Writer::message(reply).u32(channel.remote_id).finish()))
+}
+
+/// Internal: Get the channel a message is for
+fn open_channel(channel: &mut Option<Channel>, id: u32) -> Result<&mut Channel, SshError> {
+	channel
+		.as_mut()
+		.filter(|_| id == CHANNEL_ID)
+		.ok_or(SshError::Protocol("No such channel"))
+}
+
+/// Internal: Let the client send more data once the subsystem read enough of it
+async fn adjust_window<S>(
+	transport: &mut Transport<S>,
+	channel: &mut Channel,
+) -> Result<(), SshError>
+where
+	S: AsyncRead + AsyncWrite + Unpin,
+{
+	let pending = u32::try_from(channel.pending.len()).expect("Pending data fits the window");
+	let free = SSH_CHANNEL_WINDOW - channel.local_window - pending;
+
+	if free >= SSH_CHANNEL_WINDOW / 2 && !channel.eof_received {
+		let adjust = Writer::message(msg::CHANNEL_WINDOW_ADJUST)
+			.u32(channel.remote_id)
+			.u32(free)
+			.finish();
+		transport.send(&adjust).await?;
+		channel.local_window += free;
+	}
+
+	Ok(())
+}
+
+/// Internal: Close the subsystem's input once the client sent EOF and everything was passed on
+async fn shutdown_input(channel: &mut Channel) {
+	if channel.eof_received && channel.pending.is_empty() {
+		if let Some(mut writer) = channel.writer.take() {
+			let _ = writer.shutdown().await;
+		}
+	}
+}
+
+/// Internal: Send EOF and close the channel, unless that was done already
+async fn close_channel<S>(
+	transport: &mut Transport<S>,
+	channel: &mut Channel,
+) -> Result<(), SshError>
+where
+	S: AsyncRead + AsyncWrite + Unpin,
+{
+	if channel.close_sent {
+		return Ok(());
+	}
+
+	channel.close_sent = true;
+
+	let eof = Writer::message(msg::CHANNEL_EOF)
+		.u32(channel.remote_id)
+		.finish();
+	transport.send(&eof).await?;
+
+	let close = Writer::message(msg::CHANNEL_CLOSE)
+		.u32(channel.remote_id)
+		.finish();
+	transport.send(&close).await
+}
+
+/// Internal: Read output of the subsystem, waiting forever if there is none or no room to
+/// send it
+async fn read_output(
+	reader: Option<&mut ReadHalf<DuplexStream>>,
+	buf: &mut [u8],
+) -> std::io::Result<usize> {
+	match reader {
+		Some(reader) if !buf.is_empty() => reader.read(buf).await,
+		_ => std::future::pending().await,
+	}
+}
+
+/// Internal: Pass pending input to the subsystem, waiting forever if there is none
+async fn write_input(
+	writer: Option<&mut WriteHalf<DuplexStream>>,
+	pending: &[u8],
+) -> std::io::Result<usize> {
+	match writer {
+		Some(writer) if !pending.is_empty() => writer.write(pending).await,
+		_ => std::future::pending().await,
+	}
+}
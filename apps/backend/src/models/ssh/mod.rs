@@ -0,0 +1,79 @@
+//! A minimal SSH server, just enough to serve subsystems such as SFTP to authenticated users
+
+pub mod connection;
+pub mod transport;
+pub mod wire;
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SshError {
+	#[error("Malformed message")]
+	Malformed,
+	#[error("Protocol error: {0}")]
+	Protocol(&'static str),
+	#[error("No common {0} algorithm")]
+	NoCommonAlgorithm(&'static str),
+	#[error("Message authentication failed")]
+	Decryption,
+	#[error("Authentication failed")]
+	AuthenticationFailed,
+	#[error("Connection closed")]
+	Disconnected,
+	#[error("I/O error: {0}")]
+	Io(#[from] std::io::Error),
+}
+
+impl SshError {
+	/// Reason code sent to the client when the connection is closed because of the error
+	pub fn disconnect_reason(&self) -> u32 {
+		match self {
+			SshError::NoCommonAlgorithm(_) => disconnect::KEY_EXCHANGE_FAILED,
+			SshError::Decryption => disconnect::MAC_ERROR,
+			SshError::AuthenticationFailed => disconnect::NO_MORE_AUTH_METHODS_AVAILABLE,
+			_ => disconnect::PROTOCOL_ERROR,
+		}
+	}
+}
+
+/// Message numbers of RFC 4250, section 4.1
+pub mod msg {
+	pub const DISCONNECT: u8 = 1;
+	pub const IGNORE: u8 = 2;
+	pub const UNIMPLEMENTED: u8 = 3;
+	pub const DEBUG: u8 = 4;
+	pub const SERVICE_REQUEST: u8 = 5;
+	pub const SERVICE_ACCEPT: u8 = 6;
+	pub const EXT_INFO: u8 = 7;
+	pub const KEXINIT: u8 = 20;
+	pub const NEWKEYS: u8 = 21;
+	pub const KEX_ECDH_INIT: u8 = 30;
+	pub const KEX_ECDH_REPLY: u8 = 31;
+	pub const USERAUTH_REQUEST: u8 = 50;
+	pub const USERAUTH_FAILURE: u8 = 51;
+	pub const USERAUTH_SUCCESS: u8 = 52;
+	pub const USERAUTH_PK_OK: u8 = 60;
+	pub const GLOBAL_REQUEST: u8 = 80;
+	pub const REQUEST_FAILURE: u8 = 82;
+	pub const CHANNEL_OPEN: u8 = 90;
+	pub const CHANNEL_OPEN_CONFIRMATION: u8 = 91;
+	pub const CHANNEL_OPEN_FAILURE: u8 = 92;
+	pub const CHANNEL_WINDOW_ADJUST: u8 = 93;
+	pub const CHANNEL_DATA: u8 = 94;
+	pub const CHANNEL_EXTENDED_DATA: u8 = 95;
+	pub const CHANNEL_EOF: u8 = 96;
+	pub const CHANNEL_CLOSE: u8 = 97;
+	pub const CHANNEL_REQUEST: u8 = 98;
+	pub const CHANNEL_SUCCESS: u8 = 99;
+	pub const CHANNEL_FAILURE: u8 = 100;
+}
+
+/// Reason codes of `SSH_MSG_DISCONNECT`, RFC 4250 section 4.2.2
+pub mod disconnect {
+	pub const PROTOCOL_ERROR: u32 = 2;
+	pub const KEY_EXCHANGE_FAILED: u32 = 3;
+	pub const MAC_ERROR: u32 = 5;
+	pub const SERVICE_NOT_AVAILABLE: u32 = 7;
+	pub const BY_APPLICATION: u32 = 11;
+	pub const NO_MORE_AUTH_METHODS_AVAILABLE: u32 = 14;
+}
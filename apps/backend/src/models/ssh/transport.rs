@@ -0,0 +1,528 @@
+use crate::config::{SSH_MAX_PACKET_LENGTH, SSH_SERVER_VERSION};
+use crate::models::ssh::wire::{Reader, Writer};
+use crate::models::ssh::{msg, SshError};
+use aes_gcm::aead::AeadInPlace;
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce, Tag};
+use bytes::{Buf, BytesMut};
+use rand::RngCore;
+use rsa::signature::Signer;
+use sha2::{Digest, Sha256};
+use ssh_key::{PrivateKey, Signature};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use x25519_dalek::{EphemeralSecret, PublicKey as X25519PublicKey};
+
+const KEX_ALGORITHMS: &[&str] = &["curve25519-sha256", "curve25519-sha256@libssh.org"];
+const HOST_KEY_ALGORITHM: &str = "ssh-ed25519";
+const CIPHER: &str = "aes256-gcm@openssh.com";
+/// Only advertised, as the MAC is part of the AEAD cipher
+const MAC: &str = "hmac-sha2-256";
+const COMPRESSION: &str = "none";
+/// Asks the client for `SSH_MSG_EXT_INFO`, see RFC 8308
+const EXT_INFO_CLIENT: &str = "ext-info-c";
+/// Strict key exchange of OpenSSH, which prevents the Terrapin prefix truncation attack
+const STRICT_KEX_CLIENT: &str = "kex-strict-c-v00@openssh.com";
+const STRICT_KEX_SERVER: &str = "kex-strict-s-v00@openssh.com";
+
+/// Signature algorithms of user keys the server verifies
+pub const USER_KEY_ALGORITHMS: &str = "ssh-ed25519,ecdsa-sha2-nistp256,rsa-sha2-256,rsa-sha2-512";
+
+const GCM_TAG_LENGTH: usize = 16;
+const GCM_BLOCK_SIZE: usize = 16;
+const PLAIN_BLOCK_SIZE: usize = 8;
+const MIN_PADDING: usize = 4;
+/// Longest line a client may send before its version
+const MAX_BANNER_LINE: usize = 255;
+/// Lines a client may send before its version
+const MAX_BANNER_LINES: usize = 32;
+
+/// Encrypts or decrypts the packets of one direction with `aes256-gcm@openssh.com`
+struct PacketCipher {
+	cipher: Aes256Gcm,
+	/// Fixed part of the nonce
+	fixed: [u8; 4],
+	/// Invocation counter, the rest of the nonce, which is incremented for every packet
+	counter: u64,
+}
+
+impl PacketCipher {
+	fn new(key: &[u8], iv: &[u8]) -> Self {
+		Self {
+			cipher: Aes256Gcm::new_from_slice(key).expect("Derived key has the cipher's length"),
+			fixed: [iv[0], iv[1], iv[2], iv[3]],
+			counter: u64::from_be_bytes(iv[4..12].try_into().expect("IV has 12 bytes")),
+		}
+	}
+
+	fn next_nonce(&mut self) -> [u8; 12] {
+		let mut nonce = [0; 12];
+		nonce[..4].copy_from_slice(&self.fixed);
+		nonce[4..].copy_from_slice(&self.counter.to_be_bytes());
+		self.counter = self.counter.wrapping_add(1);
+		nonce
+	}
+}
+
+/// Algorithm choices of a key exchange that matter after it
+struct Negotiated {
+	/// The client's guessed first key exchange packet has to be ignored
+	ignore_guess: bool,
+	ext_info: bool,
+	strict: bool,
+}
+
+/// The SSH transport layer protocol of RFC 4253, on the server side. It exchanges versions and
+/// keys and encrypts and decrypts packets. Messages of the layers above are passed through.
+pub struct Transport<S> {
+	stream: S,
+	/// Data read from the stream that isn't part of a complete packet yet
+	read_buf: BytesMut,
+	host_key: Arc<PrivateKey>,
+	client_version: Vec<u8>,
+	session_id: Option<Vec<u8>>,
+	incoming: Option<PacketCipher>,
+	outgoing: Option<PacketCipher>,
+	recv_seq: u32,
+	send_seq: u32,
+	strict: bool,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Transport<S> {
+	/// Exchange versions and keys with a client that connected
+	pub async fn accept(stream: S, host_key: Arc<PrivateKey>) -> Result<Self, SshError> {
+		let mut transport = Self {
+			stream,
+			read_buf: BytesMut::new(),
+			host_key,
+			client_version: Vec::new(),
+			session_id: None,
+			incoming: None,
+			outgoing: None,
+			recv_seq: 0,
+			send_seq: 0,
+			strict: false,
+		};
+
+		transport
+			.stream
+			.write_all(format!("{SSH_SERVER_VERSION}\r\n").as_bytes())
+			.await?;
+		transport.client_version = transport.read_version().await?;
+		transport.exchange_keys(None).await?;
+
+		Ok(transport)
+	}
+
+	/// The exchange hash of the first key exchange, which identifies the session
+	pub fn session_id(&self) -> &[u8] {
+		self.session_id
+			.as_deref()
+			.expect("Session ID is set by the first key exchange")
+	}
+
+	/// Sequence number of the last received packet
+	pub fn last_seq(&self) -> u32 {
+		self.recv_seq.wrapping_sub(1)
+	}
+
+	/// Receive the next message, skipping those that carry no information. `SSH_MSG_KEXINIT`
+	/// is returned as well and must be passed to `exchange_keys`. This is cancel safe, so it
+	/// can be used in `tokio::select!`.
+	pub async fn next_message(&mut self) -> Result<Vec<u8>, SshError> {
+		loop {
+			let payload = self.read_packet().await?;
+
+			match payload.first() {
+				Some(&msg::DISCONNECT) => return Err(SshError::Disconnected),
+				Some(&(msg::IGNORE | msg::DEBUG | msg::UNIMPLEMENTED)) => {}
+				Some(_) => return Ok(payload),
+				None => return Err(SshError::Malformed),
+			}
+		}
+	}
+
+	/// Send a message
+	pub async fn send(&mut self, payload: &[u8]) -> Result<(), SshError> {
+		let block_size = if self.outgoing.is_some() {
+			GCM_BLOCK_SIZE
+		} else {
+			PLAIN_BLOCK_SIZE
+		};
+
+		// The length isn't encrypted with GCM, so it doesn't count towards the block size
+		let counted = if self.outgoing.is_some() { 1 } else { 5 } + payload.len();
+		let mut padding = block_size - counted % block_size;
+		if padding < MIN_PADDING {
+			padding += block_size;
+		}
+
+		let packet_length = u32::try_from(1 + payload.len() + padding)
+			.map_err(|_| SshError::Protocol("Message is too long"))?;
+
+		let mut packet = Vec::with_capacity(4 + packet_length as usize + GCM_TAG_LENGTH);
+		packet.extend_from_slice(&packet_length.to_be_bytes());
+		packet.push(u8::try_from(padding).expect("Padding is shorter than two blocks"));
+		packet.extend_from_slice(payload);
+
+		let mut random_padding = [0; 2 * GCM_BLOCK_SIZE];
+		rand::rng().fill_bytes(&mut random_padding[..padding]);
+		packet.extend_from_slice(&random_padding[..padding]);
+
+		if let Some(cipher) = &mut self.outgoing {
+			let nonce = cipher.next_nonce();
+			let (length, body) = packet.split_at_mut(4);
+			let tag = cipher
+				.cipher
+				.encrypt_in_place_detached(Nonce::from_slice(&nonce), length, body)
+				.map_err(|_| SshError::Protocol("Encryption failed"))?;
+			packet.extend_from_slice(&tag);
+		}
+
+		self.stream.write_all(&packet).await?;
+		self.send_seq = self.send_seq.wrapping_add(1);
+
+		Ok(())
+	}
+
+	/// Tell the client why the connection is closed. Errors are ignored, as the connection is
+	/// dropped either way.
+	pub async fn disconnect(&mut self, reason: u32, description: &str) {
+		let payload = Writer::message(msg::DISCONNECT)
+			.u32(reason)
+			.string(description)
+			.string("")
+			.finish();
+
+		let _ = self.send(&payload).await;
+		let _ = self.stream.shutdown().await;
+	}
+
+	/// Run a key exchange. The first one is started by the server, later ones by a client that
+	/// sends `SSH_MSG_KEXINIT`, which is passed as `client_kexinit`.
+	pub async fn exchange_keys(&mut self, client_kexinit: Option<Vec<u8>>) -> Result<(), SshError> {
+		let server_kexinit = self.kexinit();
+		self.send(&server_kexinit).await?;
+
+		let client_kexinit = match client_kexinit {
+			Some(kexinit) => kexinit,
+			None => self.read_kex_message(msg::KEXINIT).await?,
+		};
+
+		let negotiated = self.negotiate(&client_kexinit)?;
+
+		if negotiated.ignore_guess {
+			self.read_packet().await?;
+		}
+
+		let ecdh_init = self.read_kex_message(msg::KEX_ECDH_INIT).await?;
+		let mut reader = Reader::new(&ecdh_init[1..]);
+		let client_public: [u8; 32] = reader
+			.string()?
+			.try_into()
+			.map_err(|_| SshError::Protocol("Invalid Curve25519 public key"))?;
+
+		let secret = EphemeralSecret::random_from_rng(rsa::rand_core::OsRng);
+		let server_public = X25519PublicKey::from(&secret);
+		let shared = secret.diffie_hellman(&X25519PublicKey::from(client_public));
+
+		if !shared.was_contributory() {
+			return Err(SshError::Protocol("Invalid Curve25519 public key"));
+		}
+
+		let host_key_blob = self
+			.host_key
+			.public_key()
+			.to_bytes()
+			.map_err(|_| SshError::Protocol("Failed to encode host key"))?;
+
+		let hash = Sha256::digest(
+			Writer::default()
+				.string(&self.client_version)
+				.string(SSH_SERVER_VERSION)
+				.string(&client_kexinit)
+				.string(&server_kexinit)
+				.string(&host_key_blob)
+				.string(client_public)
+				.string(server_public.as_bytes())
+				.mpint(shared.as_bytes())
+				.finish(),
+		)
+		.to_vec();
+
+		let signature: Signature = self
+			.host_key
+			.try_sign(&hash)
+			.map_err(|_| SshError::Protocol("Failed to sign exchange hash"))?;
+		let signature = Vec::<u8>::try_from(signature)
+			.map_err(|_| SshError::Protocol("Failed to encode signature"))?;
+
+		let reply = Writer::message(msg::KEX_ECDH_REPLY)
+			.string(&host_key_blob)
+			.string(server_public.as_bytes())
+			.string(&signature)
+			.finish();
+		self.send(&reply).await?;
+
+		let first = self.session_id.is_none();
+		let session_id = self.session_id.get_or_insert_with(|| hash.clone()).clone();
+		let shared_mpint = Writer::default().mpint(shared.as_bytes()).finish();
+		let derive = |letter: u8, length: usize| {
+			derive_key(&shared_mpint, &hash, letter, &session_id, length)
+		};
+
+		self.send(&[msg::NEWKEYS]).await?;
+		self.outgoing = Some(PacketCipher::new(&derive(b'D', 32), &derive(b'B', 12)));
+		if negotiated.strict {
+			self.send_seq = 0;
+		}
+
+		self.read_kex_message(msg::NEWKEYS).await?;
+		self.incoming = Some(PacketCipher::new(&derive(b'C', 32), &derive(b'A', 12)));
+		if negotiated.strict {
+			self.recv_seq = 0;
+		}
+
+		if first && negotiated.ext_info {
+			let ext_info = Writer::message(msg::EXT_INFO)
+				.u32(1)
+				.string("server-sig-algs")
+				.string(USER_KEY_ALGORITHMS)
+				.finish();
+			self.send(&ext_info).await?;
+		}
+
+		Ok(())
+	}
+
+	/// Internal: Build the server's `SSH_MSG_KEXINIT`
+	fn kexinit(&self) -> Vec<u8> {
+		let mut cookie = [0; 16];
+		rand::rng().fill_bytes(&mut cookie);
+
+		// Strict key exchange is only offered in the first key exchange
+		let mut kex_algorithms = KEX_ALGORITHMS.to_vec();
+		if self.session_id.is_none() {
+			kex_algorithms.push(STRICT_KEX_SERVER);
+		}
+
+		Writer::message(msg::KEXINIT)
+			.raw(&cookie)
+			.string(kex_algorithms.join(","))
+			.string(HOST_KEY_ALGORITHM)
+			.string(CIPHER)
+			.string(CIPHER)
+			.string(MAC)
+			.string(MAC)
+			.string(COMPRESSION)
+			.string(COMPRESSION)
+			.string("")
+			.string("")
+			.bool(false)
+			.u32(0)
+			.finish()
+	}
+
+	/// Internal: Check that the client supports the algorithms of the server
+	fn negotiate(&mut self, client_kexinit: &[u8]) -> Result<Negotiated, SshError> {
+		let mut reader = Reader::new(&client_kexinit[1..]);
+		reader.skip(16)?;
+
+		let kex = reader.name_list()?;
+		let host_key = reader.name_list()?;
+		let cipher_c2s = reader.name_list()?;
+		let cipher_s2c = reader.name_list()?;
+		let _mac_c2s = reader.name_list()?;
+		let _mac_s2c = reader.name_list()?;
+		let compression_c2s = reader.name_list()?;
+		let compression_s2c = reader.name_list()?;
+		let _languages_c2s = reader.name_list()?;
+		let _languages_s2c = reader.name_list()?;
+		let guess_follows = reader.bool()?;
+
+		let chosen_kex = kex
+			.iter()
+			.find(|name| KEX_ALGORITHMS.contains(name))
+			.ok_or(SshError::NoCommonAlgorithm("key exchange"))?;
+
+		if !host_key.contains(&HOST_KEY_ALGORITHM) {
+			return Err(SshError::NoCommonAlgorithm("host key"));
+		}
+
+		if !cipher_c2s.contains(&CIPHER) || !cipher_s2c.contains(&CIPHER) {
+			return Err(SshError::NoCommonAlgorithm("cipher"));
+		}
+
+		if !compression_c2s.contains(&COMPRESSION) || !compression_s2c.contains(&COMPRESSION) {
+			return Err(SshError::NoCommonAlgorithm("compression"));
+		}
+
+		let first_kex = self.session_id.is_none();
+		let strict = self.strict || (first_kex && kex.contains(&STRICT_KEX_CLIENT));
+		self.strict = strict;
+
+		// With strict key exchange, the client's `SSH_MSG_KEXINIT` must be its first packet
+		if strict && first_kex && self.recv_seq != 1 {
+			return Err(SshError::Protocol("Unexpected message before key exchange"));
+		}
+
+		Ok(Negotiated {
+			// A guess is only right if the client's preferred algorithms are the chosen ones
+			ignore_guess: guess_follows
+				&& (kex.first() != Some(chosen_kex)
+					|| host_key.first() != Some(&HOST_KEY_ALGORITHM)),
+			ext_info: first_kex && kex.contains(&EXT_INFO_CLIENT),
+			strict,
+		})
+	}
+
+	/// Internal: Receive a message of the key exchange. Strict key exchange doesn't allow any
+	/// other messages in the first key exchange.
+	async fn read_kex_message(&mut self, expected: u8) -> Result<Vec<u8>, SshError> {
+		loop {
+			let payload = self.read_packet().await?;
+			let in_first_kex = self.incoming.is_none();
+
+			match payload.first() {
+				Some(&number) if number == expected => return Ok(payload),
+				Some(&msg::DISCONNECT) => return Err(SshError::Disconnected),
+				Some(&(msg::IGNORE | msg::DEBUG | msg::UNIMPLEMENTED))
+					if !(self.strict && in_first_kex) => {}
+				_ => return Err(SshError::Protocol("Unexpected message during key exchange")),
+			}
+		}
+	}
+
+	/// Internal: Read the client's version line, skipping lines sent before it
+	async fn read_version(&mut self) -> Result<Vec<u8>, SshError> {
+		for _ in 0..MAX_BANNER_LINES {
+			let line = loop {
+				if let Some(end) = self.read_buf.iter().position(|byte| *byte == b'\n') {
+					let mut line = self.read_buf.split_to(end + 1).to_vec();
+					line.pop();
+					if line.last() == Some(&b'\r') {
+						line.pop();
+					}
+					break line;
+				}
+
+				if self.read_buf.len() > MAX_BANNER_LINE {
+					return Err(SshError::Protocol("Version line is too long"));
+				}
+
+				self.fill_buf().await?;
+			};
+
+			if line.starts_with(b"SSH-2.0-") {
+				return Ok(line);
+			}
+
+			if line.starts_with(b"SSH-") {
+				return Err(SshError::Protocol("Unsupported protocol version"));
+			}
+		}
+
+		Err(SshError::Protocol("No version received"))
+	}
+
+	/// Internal: Receive the payload of a packet, decrypting and authenticating it once keys
+	/// have been exchanged. Data is only taken from the buffer once a whole packet was read,
+	/// which keeps this cancel safe.
+	async fn read_packet(&mut self) -> Result<Vec<u8>, SshError> {
+		loop {
+			if let Some(payload) = self.parse_packet()? {
+				self.recv_seq = self.recv_seq.wrapping_add(1);
+				return Ok(payload);
+			}
+
+			self.fill_buf().await?;
+		}
+	}
+
+	/// Internal: Take a packet from the read buffer if it has been read completely
+	fn parse_packet(&mut self) -> Result<Option<Vec<u8>>, SshError> {
+		if self.read_buf.len() < 4 {
+			return Ok(None);
+		}
+
+		let packet_length = u32::from_be_bytes(self.read_buf[..4].try_into().expect("4 bytes"));
+		let packet_length = packet_length as usize;
+
+		if packet_length > SSH_MAX_PACKET_LENGTH {
+			return Err(SshError::Protocol("Packet is too long"));
+		}
+
+		let tag_length = if self.incoming.is_some() {
+			GCM_TAG_LENGTH
+		} else {
+			0
+		};
+
+		if self.read_buf.len() < 4 + packet_length + tag_length {
+			return Ok(None);
+		}
+
+		let mut packet = self.read_buf.split_to(4 + packet_length + tag_length);
+
+		if let Some(cipher) = &mut self.incoming {
+			if !packet_length.is_multiple_of(GCM_BLOCK_SIZE) {
+				return Err(SshError::Malformed);
+			}
+
+			let nonce = cipher.next_nonce();
+			let tag = Tag::clone_from_slice(&packet[4 + packet_length..]);
+			let (length, body) = packet[..4 + packet_length].split_at_mut(4);
+			cipher
+				.cipher
+				.decrypt_in_place_detached(Nonce::from_slice(&nonce), length, body, &tag)
+				.map_err(|_| SshError::Decryption)?;
+		}
+
+		packet.advance(4);
+		packet.truncate(packet_length);
+
+		let padding = usize::from(*packet.first().ok_or(SshError::Malformed)?);
+		if padding < MIN_PADDING || padding + 1 > packet_length {
+			return Err(SshError::Malformed);
+		}
+
+		Ok(Some(packet[1..packet_length - padding].to_vec()))
+	}
+
+	/// Internal: Read more data from the stream into the read buffer
+	async fn fill_buf(&mut self) -> Result<(), SshError> {
+		if self.stream.read_buf(&mut self.read_buf).await? == 0 {
+			return Err(SshError::Disconnected);
+		}
+
+		Ok(())
+	}
+}
+
+/// Internal: Derive key material from the shared secret as described in RFC 4253, section 7.2
+fn derive_key(
+	shared_mpint: &[u8],
+	hash: &[u8],
+	letter: u8,
+	session_id: &[u8],
+	length: usize,
+) -> Vec<u8> {
+	let mut key = Sha256::new()
+		.chain_update(shared_mpint)
+		.chain_update(hash)
+		.chain_update([letter])
+		.chain_update(session_id)
+		.finalize()
+		.to_vec();
+
+	while key.len() < length {
+		let next = Sha256::new()
+			.chain_update(shared_mpint)
+			.chain_update(hash)
+			.chain_update(&key)
+			.finalize();
+		key.extend_from_slice(&next);
+	}
+
+	key.truncate(length);
+	key
+}
@@ -0,0 +1,122 @@
+use crate::models::ssh::SshError;
+
+/// Builds an SSH message from the data types of RFC 4251
+#[derive(Default)]
+pub struct Writer {
+	buf: Vec<u8>,
+}
+
+impl Writer {
+	/// Start a message with its message number
+	pub fn message(number: u8) -> Self {
+		Self { buf: vec![number] }
+	}
+
+	pub fn u8(mut self, value: u8) -> Self {
+		self.buf.push(value);
+		self
+	}
+
+	pub fn bool(self, value: bool) -> Self {
+		self.u8(u8::from(value))
+	}
+
+	pub fn u32(mut self, value: u32) -> Self {
+		self.buf.extend_from_slice(&value.to_be_bytes());
+		self
+	}
+
+	pub fn raw(mut self, bytes: &[u8]) -> Self {
+		self.buf.extend_from_slice(bytes);
+		self
+	}
+
+	pub fn string(self, bytes: impl AsRef<[u8]>) -> Self {
+		let bytes = bytes.as_ref();
+		let length = u32::try_from(bytes.len()).expect("SSH strings are shorter than 4 GiB");
+		self.u32(length).raw(bytes)
+	}
+
+	/// Write an unsigned big-endian integer as an `mpint`
+	pub fn mpint(self, bytes: &[u8]) -> Self {
+		let start = bytes
+			.iter()
+			.position(|byte| *byte != 0)
+			.unwrap_or(bytes.len());
+		let bytes = &bytes[start..];
+
+		// A set high bit would make the number negative
+		if bytes.first().is_some_and(|byte| byte & 0x80 != 0) {
+			let mut padded = Vec::with_capacity(bytes.len() + 1);
+			padded.push(0);
+			padded.extend_from_slice(bytes);
+			self.string(padded)
+		} else {
+			self.string(bytes)
+		}
+	}
+
+	pub fn finish(self) -> Vec<u8> {
+		self.buf
+	}
+}
+
+/// Reads the data types of RFC 4251 from an SSH message
+pub struct Reader<'a> {
+	buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+	pub fn new(buf: &'a [u8]) -> Self {
+		Self { buf }
+	}
+
+	pub fn u8(&mut self) -> Result<u8, SshError> {
+		Ok(self.take(1)?[0])
+	}
+
+	pub fn bool(&mut self) -> Result<bool, SshError> {
+		Ok(self.u8()? != 0)
+	}
+
+	pub fn u32(&mut self) -> Result<u32, SshError> {
+		let bytes = self.take(4)?;
+		Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+	}
+
+	pub fn string(&mut self) -> Result<&'a [u8], SshError> {
+		let length = self.u32()? as usize;
+		self.take(length)
+	}
+
+	pub fn utf8(&mut self) -> Result<&'a str, SshError> {
+		std::str::from_utf8(self.string()?).map_err(|_| SshError::Malformed)
+	}
+
+	/// Read a comma separated `name-list`
+	pub fn name_list(&mut self) -> Result<Vec<&'a str>, SshError> {
+		let list = self.utf8()?;
+
+		Ok(if list.is_empty() {
+			Vec::new()
+		} else {
+			list.split(',').collect()
+		})
+	}
+
+	/// Skip a number of bytes, such as the random cookie of a key exchange
+	pub fn skip(&mut self, length: usize) -> Result<(), SshError> {
+		self.take(length).map(|_| ())
+	}
+
+	/// Internal: Split off the next bytes of the message
+	fn take(&mut self, length: usize) -> Result<&'a [u8], SshError> {
+		if self.buf.len() < length {
+			return Err(SshError::Malformed);
+		}
+
+		let (bytes, rest) = self.buf.split_at(length);
+		self.buf = rest;
+		Ok(bytes)
+	}
+}
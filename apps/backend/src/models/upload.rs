@@ -0,0 +1,335 @@
+use crate::config::UPLOAD_SESSION_EXPIRY;
+use crate::models::file_manager::types::FileManagerError;
+use crate::models::file_manager::FileManager;
+use crate::models::file_schemas::upload_session::UploadSessionFile;
+use crate::models::hash::{compute_file_hash, HashAlgorithm};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+use time::OffsetDateTime;
+use tokio::fs::OpenOptions;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, BufWriter};
+use tokio::sync::{Mutex, RwLock};
+use ts_rs::TS;
+use uuid::Uuid;
+
+#[derive(Debug, Error)]
+pub enum UploadError {
+	#[error("No such upload session: {0}")]
+	NoSuchSession(Uuid),
+	#[error("Chunk offset does not match the received length of {0} bytes")]
+	OffsetMismatch(u64),
+	#[error("Upload exceeds its declared size of {0} bytes")]
+	SizeExceeded(u64),
+	#[error("Upload is incomplete: received {received} of {size} bytes")]
+	Incomplete { received: u64, size: u64 },
+	#[error("Checksum mismatch: expected {expected}, got {actual}")]
+	ChecksumMismatch { expected: String, actual: String },
+	#[error("File manager error: {0}")]
+	FileManager(#[from] FileManagerError),
+	#[error("I/O error: {0}")]
+	IoError(#[from] std::io::Error),
+	#[error("Failed to persist upload session: {0}")]
+	Persist(String),
+}
+
+/// Checksum that a finished upload must match
+#[derive(TS, Debug, Clone, Serialize, Deserialize)]
+#[ts(export)]
+pub struct UploadChecksum {
+	pub algorithm: HashAlgorithm,
+	pub hash: String,
+}
+
+/// Progress information about an upload session
+#[derive(TS, Debug, Clone, Serialize, Deserialize)]
+#[ts(export)]
+pub struct UploadInfo {
+	pub id: Uuid,
+	pub path: PathBuf,
+	pub size: Option<u64>,
+	pub received: u64,
+	/// Creation time as a Unix timestamp in seconds
+	pub created_at: i64,
+}
+
+/// Manages resumable upload sessions for a server. Chunks are written to a part file outside
+/// of the server directory and only moved into place once the upload is finalized.
+pub struct UploadManager {
+	dir: PathBuf,
+	sessions: RwLock<HashMap<Uuid, Arc<Mutex<UploadSessionFile>>>>,
+}
+
+impl UploadManager {
+	/// Create an upload manager storing sessions in `dir`, restoring any unexpired sessions left
+	/// over from a previous run.
+	pub fn new(dir: PathBuf) -> Self {
+		let mut sessions = HashMap::new();
+
+		if let Ok(entries) = std::fs::read_dir(&dir) {
+			for entry in entries.flatten() {
+				let path = entry.path();
+
+				if path.extension().is_none_or(|ext| ext != "toml") {
+					continue;
+				}
+
+				let session = match UploadSessionFile::load_from_file(&path) {
+					Ok(session) => session,
+					Err(err) => {
+						tracing::warn!("Failed to load upload session {:?}: {}", path, err);
+						continue;
+					}
+				};
+
+				if Self::is_expired(&dir, session.id) {
+					Self::remove_session_files(&dir, session.id);
+					continue;
+				}
+
+				sessions.insert(session.id, Arc::new(Mutex::new(session)));
+			}
+		}
+
+		Self {
+			dir,
+			sessions: RwLock::new(sessions),
+		}
+	}
+
+	/// Start a new upload session for a destination path relative to the server root
+	pub async fn create(
+		&self,
+		path: PathBuf,
+		size: Option<u64>,
+	) -> Result<UploadInfo, UploadError> {
+		self.purge_expired().await;
+
+		tokio::fs::create_dir_all(&self.dir).await?;
+
+		let session = UploadSessionFile {
+			id: Uuid::new_v4(),
+			path,
+			size,
+			created_at: OffsetDateTime::now_utc().unix_timestamp(),
+		};
+
+		tokio::fs::File::create(self.part_path(session.id)).await?;
+		session
+			.save_to_file(&self.meta_path(session.id))
+			.map_err(|err| UploadError::Persist(err.to_string()))?;
+
+		let info = Self::info(&session, 0);
+
+		self.sessions
+			.write()
+			.await
+			.insert(session.id, Arc::new(Mutex::new(session)));
+
+		Ok(info)
+	}
+
+	/// Get the progress of an upload session
+	pub async fn get(&self, id: Uuid) -> Result<UploadInfo, UploadError> {
+		let session = self.session(id).await?;
+		let session = session.lock().await;
+		let received = self.received(id).await?;
+
+		Ok(Self::info(&session, received))
+	}
+
+	/// Append a chunk to an upload. `offset` must equal the number of bytes received so far.
+	/// If the chunk is interrupted, the bytes that made it to disk are kept so the client can
+	/// resume from the new received length. `room` is the most the upload may grow to in
+	/// total, such as the space left in the quota.
+	pub async fn write_chunk<R: AsyncRead + Unpin + Send>(
+		&self,
+		id: Uuid,
+		offset: u64,
+		chunk: R,
+		room: Option<u64>,
+	) -> Result<UploadInfo, UploadError> {
+		let session = self.session(id).await?;
+		let session = session.lock().await;
+
+		let received = self.received(id).await?;
+		if offset != received {
+			return Err(UploadError::OffsetMismatch(received));
+		}
+
+		let file = OpenOptions::new()
+			.append(true)
+			.open(self.part_path(id))
+			.await?;
+		let mut writer = BufWriter::new(file);
+
+		// Read at most one byte past the declared size or the room to detect oversized uploads
+		let max_size = match (session.size, room) {
+			(Some(size), Some(room)) => Some(size.min(room)),
+			(size, room) => size.or(room),
+		};
+		let limit = max_size.map_or(u64::MAX, |max_size| {
+			max_size.saturating_sub(received).saturating_add(1)
+		});
+		let mut chunk = chunk.take(limit);
+
+		let copy_result = tokio::io::copy(&mut chunk, &mut writer).await;
+		writer.flush().await?;
+
+		if let Err(err) = copy_result {
+			tracing::warn!("Upload {} chunk was interrupted: {}", id, err);
+			return Err(UploadError::IoError(err));
+		}
+
+		let received = self.received(id).await?;
+
+		if let Some(size) = session.size {
+			if received > size {
+				writer.get_ref().set_len(size).await?;
+				return Err(UploadError::SizeExceeded(size));
+			}
+		}
+
+		if let Some(room) = room {
+			if received > room {
+				writer.get_ref().set_len(room).await?;
+				return Err(FileManagerError::QuotaExceeded.into());
+			}
+		}
+
+		Ok(Self::info(&session, received))
+	}
+
+	/// Verify an upload and move it into place using the server's file manager
+	pub async fn finalize(
+		&self,
+		id: Uuid,
+		checksum: Option<UploadChecksum>,
+		fs: &dyn FileManager,
+	) -> Result<UploadInfo, UploadError> {
+		let session = self.session(id).await?;
+		let session = session.lock().await;
+
+		let received = self.received(id).await?;
+
+		if let Some(size) = session.size {
+			if received != size {
+				return Err(UploadError::Incomplete { received, size });
+			}
+		}
+
+		let part_path = self.part_path(id);
+
+		if let Some(checksum) = checksum {
+			let hash_path = part_path.clone();
+			let actual = tokio::task::spawn_blocking(move || {
+				compute_file_hash(checksum.algorithm, &hash_path)
+			})
+			.await
+			.map_err(|err| UploadError::IoError(std::io::Error::other(err)))?
+			.map_err(|err| UploadError::IoError(std::io::Error::other(err)))?;
+
+			if !actual.eq_ignore_ascii_case(&checksum.hash) {
+				return Err(UploadError::ChecksumMismatch {
+					expected: checksum.hash,
+					actual,
+				});
+			}
+		}
+
+		fs.import(&part_path, &session.path).await?;
+
+		let info = Self::info(&session, received);
+
+		drop(session);
+		self.remove(id).await;
+
+		Ok(info)
+	}
+
+	/// Cancel an upload session and discard its data
+	pub async fn cancel(&self, id: Uuid) -> Result<(), UploadError> {
+		let session = self.session(id).await?;
+		let _guard = session.lock().await;
+
+		self.remove(id).await;
+
+		Ok(())
+	}
+
+	/// Internal: Get a session by ID
+	async fn session(&self, id: Uuid) -> Result<Arc<Mutex<UploadSessionFile>>, UploadError> {
+		self.sessions
+			.read()
+			.await
+			.get(&id)
+			.cloned()
+			.ok_or(UploadError::NoSuchSession(id))
+	}
+
+	/// Internal: Remove a session and its files
+	async fn remove(&self, id: Uuid) {
+		self.sessions.write().await.remove(&id);
+		Self::remove_session_files(&self.dir, id);
+	}
+
+	/// Internal: Remove sessions that have not received data within the expiry window
+	async fn purge_expired(&self) {
+		let expired: Vec<Uuid> = self
+			.sessions
+			.read()
+			.await
+			.keys()
+			.copied()
+			.filter(|id| Self::is_expired(&self.dir, *id))
+			.collect();
+
+		for id in expired {
+			tracing::info!("Removing expired upload session {}", id);
+			self.remove(id).await;
+		}
+	}
+
+	/// Internal: Number of bytes received for a session
+	async fn received(&self, id: Uuid) -> Result<u64, UploadError> {
+		let metadata = tokio::fs::metadata(self.part_path(id)).await?;
+		Ok(metadata.len())
+	}
+
+	fn info(session: &UploadSessionFile, received: u64) -> UploadInfo {
+		UploadInfo {
+			id: session.id,
+			path: session.path.clone(),
+			size: session.size,
+			received,
+			created_at: session.created_at,
+		}
+	}
+
+	fn part_path(&self, id: Uuid) -> PathBuf {
+		self.dir.join(format!("{id}.part"))
+	}
+
+	fn meta_path(&self, id: Uuid) -> PathBuf {
+		self.dir.join(format!("{id}.toml"))
+	}
+
+	/// Internal: A session is expired once its part file was last written before the expiry window
+	fn is_expired(dir: &Path, id: Uuid) -> bool {
+		let modified = std::fs::metadata(dir.join(format!("{id}.part"))).and_then(|m| m.modified());
+
+		match modified {
+			Ok(modified) => {
+				OffsetDateTime::from(modified) + UPLOAD_SESSION_EXPIRY < OffsetDateTime::now_utc()
+			}
+			Err(_) => true,
+		}
+	}
+
+	fn remove_session_files(dir: &Path, id: Uuid) {
+		let _ = std::fs::remove_file(dir.join(format!("{id}.part")));
+		let _ = std::fs::remove_file(dir.join(format!("{id}.toml")));
+	}
+}
@@ -3,8 +3,11 @@ use std::sync::Arc;
 use crate::{
 	config::{AUTH_TOKEN_LENGTH, REFRESH_TOKEN_LENGTH},
 	db::{
-		models::user::User,
-		repositories::{refresh_token::RefreshTokenRepository, user::UserRepository},
+		models::{app_password::AppPassword, user::User},
+		repositories::{
+			app_password::AppPasswordRepository, refresh_token::RefreshTokenRepository,
+			ssh_key::SshKeyRepository, user::UserRepository,
+		},
 	},
 	models::secrets::Secrets,
 	services::Service,
@@ -14,6 +17,7 @@ use jsonwebtoken::{Algorithm, Validation};
 use password_auth::{verify_password, VerifyError};
 use rand::Rng;
 use serde::{Deserialize, Serialize};
+use ssh_key::{HashAlg, PublicKey};
 use thiserror;
 use tokio::task::spawn_blocking;
 use uuid::Uuid;
@@ -41,6 +45,8 @@ pub struct AuthTokenClaims {
 pub struct AuthService {
 	user_repo: Arc<dyn UserRepository>,
 	token_repo: Arc<dyn RefreshTokenRepository>,
+	ssh_key_repo: Arc<dyn SshKeyRepository>,
+	app_password_repo: Arc<dyn AppPasswordRepository>,
 	secrets: Secrets,
 }
 
@@ -50,11 +56,15 @@ impl AuthService {
 	pub fn new(
 		user_repo: Arc<dyn UserRepository>,
 		token_repo: Arc<dyn RefreshTokenRepository>,
+		ssh_key_repo: Arc<dyn SshKeyRepository>,
+		app_password_repo: Arc<dyn AppPasswordRepository>,
 		secrets: Secrets,
 	) -> Self {
 		Self {
 			user_repo,
 			token_repo,
+			ssh_key_repo,
+			app_password_repo,
 			secrets,
 		}
 	}
@@ -110,11 +120,12 @@ impl AuthService {
 		Ok(())
 	}
 
-	pub async fn authenticate_user(
+	/// Look up a user by username and verify their password
+	pub async fn verify_credentials(
 		&self,
 		username: &str,
 		password: &str,
-	) -> Result<(String, String), AuthServiceError> {
+	) -> Result<User, AuthServiceError> {
 		let user = self.user_repo.get_user_by_username(username).await;
 
 		if user.is_err() {
@@ -125,6 +136,62 @@ impl AuthService {
 
 		self.verify_password(&user, password).await?;
 
+		Ok(user)
+	}
+
+	/// Look up a user by username and check that they registered an SSH key. Whether the
+	/// client holds the private key is verified by the SSH server.
+	pub async fn verify_ssh_key(
+		&self,
+		username: &str,
+		key: &PublicKey,
+	) -> Result<User, AuthServiceError> {
+		let Ok(user) = self.user_repo.get_user_by_username(username).await else {
+			return Err(AuthServiceError::InvalidCredentials);
+		};
+
+		let fingerprint = key.fingerprint(HashAlg::Sha256).to_string();
+
+		match self
+			.ssh_key_repo
+			.get_user_key_by_fingerprint(user.id, &fingerprint)
+			.await
+		{
+			Ok(Some(_)) => Ok(user),
+			Ok(None) => Err(AuthServiceError::InvalidCredentials),
+			Err(err) => Err(AuthServiceError::ServerError(err.to_string())),
+		}
+	}
+
+	/// Look up a user by username and verify an app password of theirs. App passwords never
+	/// grant sudo.
+	pub async fn verify_app_password(
+		&self,
+		username: &str,
+		password: &str,
+	) -> Result<User, AuthServiceError> {
+		let Ok(user) = self.user_repo.get_user_by_username(username).await else {
+			return Err(AuthServiceError::InvalidCredentials);
+		};
+
+		match self
+			.app_password_repo
+			.get_password_by_hash(&AppPassword::hash(password))
+			.await
+		{
+			Ok(Some(app_password)) if app_password.user_id == user.id => Ok(user),
+			Ok(_) => Err(AuthServiceError::InvalidCredentials),
+			Err(err) => Err(AuthServiceError::ServerError(err.to_string())),
+		}
+	}
+
+	pub async fn authenticate_user(
+		&self,
+		username: &str,
+		password: &str,
+	) -> Result<(String, String), AuthServiceError> {
+		let user = self.verify_credentials(username, password).await?;
+
 		let auth_token = self.create_auth_token(user.id.to_string(), false);
 		let ref_token = Self::create_refresh_token();
 
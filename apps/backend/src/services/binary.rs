@@ -117,6 +117,17 @@ impl BinaryService {
 		Ok(binary_path)
 	}
 
+	/// Returns the path to the binary of a given game if it is installed, without downloading it.
+	pub async fn installed_binary(&self, game: &Game) -> Option<PathBuf> {
+		let _lock = self.lockfile_mutex.lock().await;
+		let lockfile = self.load_lockfile().await.ok()?;
+
+		lockfile
+			.binaries
+			.get(game.identifier())
+			.map(|entry| entry.path.clone())
+	}
+
 	/// Returns the path to the binary directory for a given game, if not
 	/// available it will be downloaded.
 	pub async fn ensure_binary(&self, game: &Game) -> Result<PathBuf, String> {
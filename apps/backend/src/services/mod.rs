@@ -2,6 +2,8 @@ pub mod auth;
 pub mod binary;
 pub mod java;
 pub mod server;
+pub mod sftp;
+pub mod trigger;
 pub mod user;
 
 /// Trait for application services.
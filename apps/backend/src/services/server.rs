@@ -1,17 +1,28 @@
 use crate::bin_providers::DownloadDependency;
 use crate::config;
-use crate::config::SERVER_CONFIG_FILE_NAME;
-use crate::models::file_schemas::server_config::ServerConfig;
+use crate::config::{
+	MEMORY_OVERCOMMIT_PERCENT, MEMORY_QUEUE_POLL, MEMORY_QUEUE_STARTS, SERVER_CONFIG_FILE_NAME,
+	SERVER_PORT_RANGE,
+};
+use crate::models::file_schemas::server_config::{
+	FileHistoryConfig, JvmSettings, ResourceLimits, SandboxConfig, ServerConfig, ServerHooks,
+};
+use crate::models::file_schemas::server_properties::{
+	ServerProperties, SERVER_PROPERTIES_FILE_NAME,
+};
 use crate::models::game::Game;
-use crate::models::server::Server;
+use crate::models::memory::{HostMemory, MemoryUsage, ServerMemoryUsage};
+use crate::models::ports::{server_ports, PortError, PortRegistry};
+use crate::models::secrets::SecretCipher;
 use crate::models::server::ServerStateInfo;
+use crate::models::server::{Server, ServerError};
 use crate::services::binary::BinaryService;
 use crate::services::Service;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::path::PathBuf;
 use std::sync::Arc;
 use thiserror::Error;
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
 use tracing::instrument;
 use uuid::Uuid;
 
@@ -23,9 +34,23 @@ pub enum ServerServiceError {
 	DeleteError(String),
 }
 
+/// Outcome of a start request
+pub enum StartOutcome {
+	Started,
+	/// Waiting for other servers to free up memory
+	Queued,
+}
+
 pub struct ServerService {
 	servers: RwLock<HashMap<Uuid, Arc<Server>>>,
 	binary_service: Arc<BinaryService>,
+	ports: Arc<PortRegistry>,
+	cipher: Arc<SecretCipher>,
+	/// Held while checking whether a server fits into memory and starting it, so concurrent
+	/// starts can't overcommit together
+	admission: Mutex<()>,
+	/// Servers waiting for memory, started in order
+	start_queue: Mutex<VecDeque<Uuid>>,
 }
 
 impl Service for ServerService {
@@ -75,7 +100,7 @@ impl Service for ServerService {
 impl ServerService {
 	/// Creates a new `ServerService` instance.
 	#[instrument(name = "ServerService.Startup", skip_all)]
-	pub fn new(binary_service: Arc<BinaryService>) -> Self {
+	pub fn new(binary_service: Arc<BinaryService>, cipher: Arc<SecretCipher>) -> Self {
 		tracing::info!("Loading server instances");
 
 		let path = PathBuf::from(config::SERVERS_DIRECTORY.clone());
@@ -90,6 +115,7 @@ impl ServerService {
 			std::fs::read_dir(&path).expect("Failed to read server instances directory");
 
 		let mut servers = HashMap::<Uuid, Arc<Server>>::new();
+		let ports = Arc::new(PortRegistry::new());
 
 		// Load the server configurations from the server instances directory.
 		for entry in dir_entries {
@@ -119,7 +145,7 @@ impl ServerService {
 				continue;
 			};
 
-			let server = Server::new(uuid);
+			let server = Server::new(uuid, ports.clone(), cipher.clone());
 
 			match server {
 				Ok(server) => {
@@ -135,6 +161,10 @@ impl ServerService {
 		Self {
 			servers: RwLock::new(servers),
 			binary_service,
+			ports,
+			cipher,
+			admission: Mutex::new(()),
+			start_queue: Mutex::new(VecDeque::new()),
 		}
 	}
 
@@ -192,14 +222,41 @@ impl ServerService {
 			name: name.to_string(),
 			game: server_type,
 			args: java_args,
+			jvm: JvmSettings::default(),
+			env: BTreeMap::new(),
+			wrapper: Vec::new(),
+			hooks: ServerHooks::default(),
 			stop_command: "stop".into(), // TODO: Velocity uses "shutdown"
+			history: FileHistoryConfig::default(),
+			disk_quota: None,
+			memory: None,
+			path_rules: Vec::new(),
+			eula: None,
+			limits: ResourceLimits::default(),
+			sandbox: SandboxConfig::default(),
 		};
 
 		server_config
 			.save_to_file(config_path)
 			.map_err(|e| e.to_string())?;
 
-		let server = Server::new(server_id).map_err(|e| e.clone())?;
+		// Servers get distinct ports so they can run side by side
+		match self.allocate_port().await {
+			Ok(port) => {
+				let mut properties = ServerProperties::default();
+				properties.set("server-port", &port.to_string());
+
+				std::fs::write(
+					server_dir.join(SERVER_PROPERTIES_FILE_NAME),
+					properties.to_string(),
+				)
+				.map_err(|e| e.to_string())?;
+			}
+			Err(err) => tracing::warn!("Failed to assign a port to the new server: {}", err),
+		}
+
+		let server = Server::new(server_id, self.ports.clone(), self.cipher.clone())
+			.map_err(|e| e.clone())?;
 		let server_arced = Arc::new(server);
 
 		tracing::info!("Creating new server instance: name='{}'", name);
@@ -209,6 +266,215 @@ impl ServerService {
 		Ok(server_id)
 	}
 
+	/// Internal: Pick a port from the configured range that no other server uses
+	async fn allocate_port(&self) -> Result<u16, PortError> {
+		let servers_guard = self.servers.read().await;
+
+		let taken: HashSet<u16> = servers_guard
+			.keys()
+			.flat_map(|server_id| server_ports(&config::server_dir(*server_id)))
+			.map(|binding| binding.port)
+			.collect();
+
+		self.ports.allocate(SERVER_PORT_RANGE, &taken)
+	}
+
+	/// Start a server if the host has enough memory left for it. Depending on the configuration,
+	/// starts that would overcommit are queued until other servers stop, or rejected.
+	#[instrument(name = "ServerService.StartServer", skip_all, fields(server_id = %server.id()))]
+	pub async fn start(
+		self: &Arc<Self>,
+		server: &Arc<Server>,
+	) -> Result<StartOutcome, ServerError> {
+		let mut queue = self.start_queue.lock().await;
+
+		if queue.contains(&server.id()) {
+			return Ok(StartOutcome::Queued);
+		}
+
+		let admission = self.admission.lock().await;
+
+		// Servers that are already waiting go first
+		let admitted = match self.check_memory(server, &queue).await {
+			Ok(()) => queue.is_empty(),
+			Err(ServerError::InsufficientMemory { .. }) if MEMORY_QUEUE_STARTS => {
+				if let Some(err) = self.exceeds_budget(server).await {
+					return Err(err);
+				}
+
+				false
+			}
+			Err(err) => return Err(err),
+		};
+
+		if admitted {
+			// The starting server counts towards the memory in use, so other starts can be
+			// admitted while its pre-start hooks run
+			server.reserve_start().await?;
+			drop(admission);
+			drop(queue);
+
+			server.start(self.binary_service.clone()).await?;
+			return Ok(StartOutcome::Started);
+		}
+
+		tracing::info!("Queueing server start until enough memory is free");
+
+		queue.push_back(server.id());
+
+		if queue.len() == 1 {
+			let service = self.clone();
+			tokio::spawn(async move { service.process_start_queue().await });
+		}
+
+		Ok(StartOutcome::Queued)
+	}
+
+	/// Remove a server from the start queue. Returns whether it was queued.
+	pub async fn cancel_start(&self, server_id: Uuid) -> bool {
+		let mut queue = self.start_queue.lock().await;
+		let queued = queue.len();
+
+		queue.retain(|id| *id != server_id);
+		queue.len() < queued
+	}
+
+	/// Get the host's memory and what the servers are expected to use. Returns None if the host's
+	/// memory can't be read.
+	pub async fn memory_usage(&self) -> Option<MemoryUsage> {
+		let queue = self.start_queue.lock().await;
+		self.collect_memory_usage(&queue).await
+	}
+
+	/// Internal: Get the memory usage while the start queue is locked by the caller
+	async fn collect_memory_usage(&self, queue: &VecDeque<Uuid>) -> Option<MemoryUsage> {
+		let host = HostMemory::read()?;
+		let servers = self
+			.servers
+			.read()
+			.await
+			.values()
+			.cloned()
+			.collect::<Vec<_>>();
+
+		let mut usage = Vec::with_capacity(servers.len());
+
+		for server in servers {
+			let (memory, state) = server.memory_info(host.total).await;
+
+			usage.push(ServerMemoryUsage {
+				id: server.id(),
+				memory,
+				queued: queue.contains(&server.id()),
+				state,
+			});
+		}
+
+		let allocated = usage
+			.iter()
+			.filter(|server| server.state != ServerStateInfo::Stopped)
+			.map(|server| server.memory)
+			.sum();
+
+		Some(MemoryUsage {
+			host,
+			budget: memory_budget(&host),
+			allocated,
+			servers: usage,
+		})
+	}
+
+	/// Internal: Check that a server fits into the memory left by the running servers
+	async fn check_memory(
+		&self,
+		server: &Server,
+		queue: &VecDeque<Uuid>,
+	) -> Result<(), ServerError> {
+		let Some(usage) = self.collect_memory_usage(queue).await else {
+			// Without knowing the host's memory, nothing can be checked
+			return Ok(());
+		};
+
+		let Some(own) = usage.servers.iter().find(|s| s.id == server.id()) else {
+			return Ok(());
+		};
+
+		// A running server is refused later on, without counting it twice here
+		if own.state != ServerStateInfo::Stopped {
+			return Ok(());
+		}
+
+		let available = usage.budget.saturating_sub(usage.allocated);
+
+		if own.memory > available {
+			return Err(ServerError::InsufficientMemory {
+				required: own.memory,
+				available,
+			});
+		}
+
+		Ok(())
+	}
+
+	/// Internal: Check whether a server needs more memory than all servers may use together. It
+	/// would wait for memory forever, so it is refused instead of queued.
+	async fn exceeds_budget(&self, server: &Server) -> Option<ServerError> {
+		let host = HostMemory::read()?;
+		let budget = memory_budget(&host);
+		let (required, _) = server.memory_info(host.total).await;
+
+		(required > budget).then_some(ServerError::InsufficientMemory {
+			required,
+			available: budget,
+		})
+	}
+
+	/// Internal: Start queued servers in order as memory becomes free, until the queue is empty
+	async fn process_start_queue(self: Arc<Self>) {
+		loop {
+			tokio::time::sleep(MEMORY_QUEUE_POLL).await;
+
+			let mut queue = self.start_queue.lock().await;
+			let admission = self.admission.lock().await;
+
+			let Some(server_id) = queue.front().copied() else {
+				return;
+			};
+
+			let Ok(server) = self.get_server(server_id).await else {
+				queue.pop_front();
+				continue;
+			};
+
+			// The first server in line waits until it fits, unless its memory was raised beyond
+			// what it could ever get
+			if self.check_memory(&server, &queue).await.is_err() {
+				if let Some(err) = self.exceeds_budget(&server).await {
+					tracing::error!("Failed to start queued server {}: {}", server_id, err);
+					queue.pop_front();
+				}
+
+				continue;
+			}
+
+			queue.pop_front();
+			let reserved = server.reserve_start().await;
+			drop(admission);
+			drop(queue);
+
+			tracing::info!("Starting queued server {}", server_id);
+
+			let started = match reserved {
+				Ok(()) => server.start(self.binary_service.clone()).await,
+				Err(err) => Err(err),
+			};
+
+			if let Err(err) = started {
+				tracing::error!("Failed to start queued server {}: {}", server_id, err);
+			}
+		}
+	}
+
 	/// Deletes a server and removes its files
 	#[instrument(name = "ServerService.DeleteServer", skip(self))]
 	pub async fn delete(&self, server_id: Uuid) -> Result<(), ServerServiceError> {
@@ -224,6 +490,8 @@ impl ServerService {
 				.map_err(|err| ServerServiceError::DeleteError(err.to_string()))?;
 		}
 
+		self.cancel_start(server_id).await;
+
 		let mut servers_guard = self.servers.write().await;
 		servers_guard
 			.remove(&server_id)
@@ -234,8 +502,20 @@ impl ServerService {
 			ServerServiceError::DeleteError(format!("Failed to delete server files: {e}"))
 		})?;
 
+		let meta_dir = config::server_meta_dir(server_id);
+		if meta_dir.exists() {
+			std::fs::remove_dir_all(&meta_dir).map_err(|e| {
+				ServerServiceError::DeleteError(format!("Failed to delete server metadata: {e}"))
+			})?;
+		}
+
 		tracing::info!("Server {} deleted successfully", server_id);
 
 		Ok(())
 	}
 }
+
+/// Internal: Memory in bytes running servers may use in total
+fn memory_budget(host: &HostMemory) -> u64 {
+	host.total / 100 * MEMORY_OVERCOMMIT_PERCENT
+}
@@ -0,0 +1,559 @@
+//! SFTP access to the files of every server through its `FileManager`, served by the embedded
+//! SSH server to users who log in with their password or a registered SSH key
+
+use crate::config::SFTP_MAX_READ_LENGTH;
+use crate::db::models::user::User;
+use crate::models::file_manager::types::{FSEntry, FileManagerError};
+use crate::models::file_manager::{normalize_relative, FileReader, FileWriter};
+use crate::models::server::Server;
+use crate::models::ssh::connection::{self, SshHandler};
+use crate::services::auth::AuthService;
+use crate::services::server::ServerService;
+use async_trait::async_trait;
+use russh_sftp::protocol::{
+	Attrs, Data, File, FileAttributes, Handle, Name, OpenFlags, Status, StatusCode, Version,
+};
+use ssh_key::{PrivateKey, PublicKey};
+use std::collections::HashMap;
+use std::io::{ErrorKind, SeekFrom};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, DuplexStream};
+use tokio::net::TcpListener;
+use uuid::Uuid;
+
+/// Unix file type bits reported in file attributes
+const MODE_DIR: u32 = 0o040_000;
+const MODE_FILE: u32 = 0o100_000;
+
+pub struct SftpService {
+	server_service: Arc<ServerService>,
+	auth_service: Arc<AuthService>,
+	host_key: Arc<PrivateKey>,
+}
+
+impl SftpService {
+	pub fn new(
+		server_service: Arc<ServerService>,
+		auth_service: Arc<AuthService>,
+		host_key: PrivateKey,
+	) -> Self {
+		Self {
+			server_service,
+			auth_service,
+			host_key: Arc::new(host_key),
+		}
+	}
+
+	/// Accept SSH connections, serving each in its own task
+	pub async fn listen(self: Arc<Self>, addr: SocketAddr) -> std::io::Result<()> {
+		let listener = TcpListener::bind(addr).await?;
+
+		loop {
+			let (stream, peer) = match listener.accept().await {
+				Ok(connection) => connection,
+				Err(err) => {
+					tracing::warn!("Failed to accept SFTP connection: {}", err);
+					continue;
+				}
+			};
+
+			let service = self.clone();
+			tokio::spawn(async move {
+				let host_key = service.host_key.clone();
+
+				if let Err(err) = connection::serve(stream, host_key, service.as_ref()).await {
+					tracing::info!("SFTP connection from {} closed: {}", peer, err);
+				}
+			});
+		}
+	}
+}
+
+#[async_trait]
+impl SshHandler for SftpService {
+	type Identity = User;
+
+	async fn auth_password(&self, username: &str, password: &str) -> Option<User> {
+		self.auth_service
+			.verify_credentials(username, password)
+			.await
+			.ok()
+	}
+
+	async fn auth_public_key(&self, username: &str, key: &PublicKey) -> Option<User> {
+		self.auth_service.verify_ssh_key(username, key).await.ok()
+	}
+
+	fn subsystem(&self, user: &User, name: &str) -> Option<DuplexStream> {
+		if name != "sftp" {
+			return None;
+		}
+
+		tracing::info!("Starting SFTP session for {}", user.username);
+
+		let (stream, session_stream) = tokio::io::duplex(SFTP_MAX_READ_LENGTH);
+		let session = SftpSession::new(self.server_service.clone());
+		tokio::spawn(russh_sftp::server::run(session_stream, session));
+
+		Some(stream)
+	}
+}
+
+/// A path in the SFTP tree. The root lists the servers by ID, and each server's directory is
+/// the root of its file manager.
+enum SftpPath {
+	Root,
+	Server(Arc<Server>, PathBuf),
+}
+
+enum OpenHandle {
+	Read {
+		path: String,
+		reader: FileReader,
+	},
+	Write {
+		path: String,
+		writer: FileWriter,
+		written: u64,
+	},
+	Dir {
+		/// Entries not yet sent to the client
+		entries: Option<Vec<File>>,
+	},
+}
+
+/// The SFTP session of an authenticated user
+struct SftpSession {
+	server_service: Arc<ServerService>,
+	handles: HashMap<String, OpenHandle>,
+	next_handle: u64,
+}
+
+impl SftpSession {
+	fn new(server_service: Arc<ServerService>) -> Self {
+		Self {
+			server_service,
+			handles: HashMap::new(),
+			next_handle: 0,
+		}
+	}
+
+	/// Internal: Keep an open file or directory, returning the handle the client refers to it by
+	fn add_handle(&mut self, handle: OpenHandle) -> String {
+		let id = self.next_handle.to_string();
+		self.next_handle += 1;
+		self.handles.insert(id.clone(), handle);
+		id
+	}
+
+	/// Internal: Resolve a client path into a server and a path relative to its root
+	async fn resolve(&self, path: &str) -> Result<SftpPath, StatusCode> {
+		let path = normalize_relative(Path::new(path));
+		let mut components = path.components();
+
+		let Some(server_id) = components.next() else {
+			return Ok(SftpPath::Root);
+		};
+
+		let server_id = server_id
+			.as_os_str()
+			.to_str()
+			.and_then(|id| Uuid::parse_str(id).ok())
+			.ok_or(StatusCode::NoSuchFile)?;
+
+		let server = self
+			.server_service
+			.get_server(server_id)
+			.await
+			.map_err(|_| StatusCode::NoSuchFile)?;
+
+		Ok(SftpPath::Server(server, components.as_path().to_path_buf()))
+	}
+
+	/// Internal: Resolve a client path that must be inside a server directory
+	async fn resolve_in_server(&self, path: &str) -> Result<(Arc<Server>, PathBuf), StatusCode> {
+		match self.resolve(path).await? {
+			SftpPath::Root => Err(StatusCode::PermissionDenied),
+			SftpPath::Server(server, path) => Ok((server, path)),
+		}
+	}
+
+	/// Internal: Get the attributes of the file or directory at a client path
+	async fn attributes(&self, path: &str) -> Result<FileAttributes, StatusCode> {
+		match self.resolve(path).await? {
+			SftpPath::Root => Ok(Self::root_attributes()),
+			SftpPath::Server(server, path) => {
+				let entry = server.get_fs().stat(&path).await.map_err(status_code)?;
+				Ok(Self::entry_attributes(&entry))
+			}
+		}
+	}
+
+	/// Internal: Attributes of the root and the server directories in it
+	fn root_attributes() -> FileAttributes {
+		FileAttributes {
+			permissions: Some(MODE_DIR | 0o755),
+			..FileAttributes::default()
+		}
+	}
+
+	/// Internal: Attributes of an entry of a file manager
+	fn entry_attributes(entry: &FSEntry) -> FileAttributes {
+		let (file_type, size, permissions) = match entry {
+			FSEntry::File(file) => (MODE_FILE, Some(file.size), file.permissions),
+			FSEntry::Dir(dir) => (MODE_DIR, None, dir.permissions),
+		};
+
+		let mtime = entry.modified().and_then(|time| u32::try_from(time).ok());
+
+		FileAttributes {
+			size,
+			permissions: Some(file_type | permissions.unwrap_or(0o644)),
+			atime: mtime,
+			mtime,
+			..FileAttributes::default()
+		}
+	}
+
+	/// Internal: Status reporting success of a request
+	fn ok_status(id: u32) -> Status {
+		Status {
+			id,
+			status_code: StatusCode::Ok,
+			error_message: "Ok".to_string(),
+			language_tag: "en-US".to_string(),
+		}
+	}
+}
+
+/// Internal: Map a file manager error to the closest SFTP status
+fn status_code(error: FileManagerError) -> StatusCode {
+	match error {
+		FileManagerError::NotFound => StatusCode::NoSuchFile,
+		FileManagerError::NoPermission | FileManagerError::ReadOnly => StatusCode::PermissionDenied,
+		FileManagerError::Unsupported => StatusCode::OpUnsupported,
+		FileManagerError::IoError(err) => {
+			tracing::error!("SFTP I/O error: {}", err);
+			StatusCode::Failure
+		}
+		FileManagerError::AlreadyExists
+		| FileManagerError::QuotaExceeded
+		| FileManagerError::UnknownType
+		| FileManagerError::EncodingError
+		| FileManagerError::InvalidPattern(_) => StatusCode::Failure,
+	}
+}
+
+impl russh_sftp::server::Handler for SftpSession {
+	type Error = StatusCode;
+
+	fn unimplemented(&self) -> Self::Error {
+		StatusCode::OpUnsupported
+	}
+
+	async fn init(
+		&mut self,
+		_version: u32,
+		_extensions: HashMap<String, String>,
+	) -> Result<Version, Self::Error> {
+		Ok(Version::new())
+	}
+
+	async fn realpath(&mut self, id: u32, path: String) -> Result<Name, Self::Error> {
+		let path = Path::new("/").join(normalize_relative(Path::new(&path)));
+
+		Ok(Name {
+			id,
+			files: vec![File::dummy(path.to_string_lossy())],
+		})
+	}
+
+	async fn stat(&mut self, id: u32, path: String) -> Result<Attrs, Self::Error> {
+		let attrs = self.attributes(&path).await?;
+		Ok(Attrs { id, attrs })
+	}
+
+	async fn lstat(&mut self, id: u32, path: String) -> Result<Attrs, Self::Error> {
+		// Symlinks are always followed by the file manager
+		self.stat(id, path).await
+	}
+
+	async fn fstat(&mut self, id: u32, handle: String) -> Result<Attrs, Self::Error> {
+		let path = match self.handles.get(&handle) {
+			Some(OpenHandle::Read { path, .. } | OpenHandle::Write { path, .. }) => path.clone(),
+			Some(OpenHandle::Dir { .. }) => return Err(StatusCode::OpUnsupported),
+			None => return Err(StatusCode::Failure),
+		};
+
+		self.stat(id, path).await
+	}
+
+	async fn setstat(
+		&mut self,
+		id: u32,
+		_path: String,
+		_attrs: FileAttributes,
+	) -> Result<Status, Self::Error> {
+		// Ownership, permissions and timestamps are managed by the panel. Accept and ignore
+		// changes so clients that preserve timestamps after an upload don't report failures.
+		Ok(Self::ok_status(id))
+	}
+
+	async fn fsetstat(
+		&mut self,
+		id: u32,
+		_handle: String,
+		_attrs: FileAttributes,
+	) -> Result<Status, Self::Error> {
+		Ok(Self::ok_status(id))
+	}
+
+	async fn opendir(&mut self, id: u32, path: String) -> Result<Handle, Self::Error> {
+		let entries = match self.resolve(&path).await? {
+			SftpPath::Root => self
+				.server_service
+				.list_server_ids()
+				.await
+				.into_iter()
+				.map(|server_id| File::new(server_id.to_string(), Self::root_attributes()))
+				.collect(),
+			SftpPath::Server(server, path) => server
+				.get_fs()
+				.list_dir(&path)
+				.await
+				.map_err(status_code)?
+				.iter()
+				.map(|entry| File::new(entry.name(), Self::entry_attributes(entry)))
+				.collect(),
+		};
+
+		let handle = self.add_handle(OpenHandle::Dir {
+			entries: Some(entries),
+		});
+
+		Ok(Handle { id, handle })
+	}
+
+	async fn readdir(&mut self, id: u32, handle: String) -> Result<Name, Self::Error> {
+		let Some(OpenHandle::Dir { entries }) = self.handles.get_mut(&handle) else {
+			return Err(StatusCode::Failure);
+		};
+
+		match entries.take() {
+			Some(files) => Ok(Name { id, files }),
+			None => Err(StatusCode::Eof),
+		}
+	}
+
+	async fn open(
+		&mut self,
+		id: u32,
+		filename: String,
+		pflags: OpenFlags,
+		_attrs: FileAttributes,
+	) -> Result<Handle, Self::Error> {
+		let (server, path) = self.resolve_in_server(&filename).await?;
+		let fs = server.get_fs();
+
+		if !pflags.contains(OpenFlags::WRITE) {
+			let reader = fs.read_file(&path).await.map_err(status_code)?;
+			let handle = self.add_handle(OpenHandle::Read {
+				path: filename,
+				reader,
+			});
+
+			return Ok(Handle { id, handle });
+		}
+
+		// Writes replace the whole file once the handle is closed, so appending or writing into
+		// an existing file without truncating it can't be supported
+		if pflags.contains(OpenFlags::APPEND) {
+			return Err(StatusCode::OpUnsupported);
+		}
+
+		match fs.stat(&path).await {
+			Ok(FSEntry::Dir(_)) => return Err(StatusCode::Failure),
+			Ok(FSEntry::File(_)) if pflags.contains(OpenFlags::EXCLUDE) => {
+				return Err(StatusCode::Failure)
+			}
+			Ok(FSEntry::File(_)) if !pflags.contains(OpenFlags::TRUNCATE) => {
+				return Err(StatusCode::OpUnsupported)
+			}
+			Ok(FSEntry::File(_)) => {}
+			Err(FileManagerError::NotFound) if pflags.contains(OpenFlags::CREATE) => {
+				fs.create_file(&path).await.map_err(status_code)?;
+			}
+			Err(err) => return Err(status_code(err)),
+		}
+
+		let writer = fs.write_file(&path).await.map_err(status_code)?;
+		let handle = self.add_handle(OpenHandle::Write {
+			path: filename,
+			writer,
+			written: 0,
+		});
+
+		Ok(Handle { id, handle })
+	}
+
+	async fn read(
+		&mut self,
+		id: u32,
+		handle: String,
+		offset: u64,
+		len: u32,
+	) -> Result<Data, Self::Error> {
+		let Some(OpenHandle::Read { reader, .. }) = self.handles.get_mut(&handle) else {
+			return Err(StatusCode::Failure);
+		};
+
+		reader
+			.seek(SeekFrom::Start(offset))
+			.await
+			.map_err(|_| StatusCode::Failure)?;
+
+		let len = usize::try_from(len)
+			.unwrap_or(SFTP_MAX_READ_LENGTH)
+			.min(SFTP_MAX_READ_LENGTH);
+		let mut data = vec![0; len];
+
+		let read = reader
+			.read(&mut data)
+			.await
+			.map_err(|_| StatusCode::Failure)?;
+
+		if read == 0 {
+			return Err(StatusCode::Eof);
+		}
+
+		data.truncate(read);
+
+		Ok(Data { id, data })
+	}
+
+	async fn write(
+		&mut self,
+		id: u32,
+		handle: String,
+		offset: u64,
+		data: Vec<u8>,
+	) -> Result<Status, Self::Error> {
+		let Some(OpenHandle::Write {
+			writer, written, ..
+		}) = self.handles.get_mut(&handle)
+		else {
+			return Err(StatusCode::Failure);
+		};
+
+		// The staged file is written as a stream, so writes must arrive in order
+		if offset != *written {
+			return Err(StatusCode::OpUnsupported);
+		}
+
+		writer.write_all(&data).await.map_err(|err| {
+			if err.kind() != ErrorKind::StorageFull {
+				tracing::error!("SFTP write error: {}", err);
+			}
+			StatusCode::Failure
+		})?;
+
+		*written += data.len() as u64;
+
+		Ok(Self::ok_status(id))
+	}
+
+	async fn close(&mut self, id: u32, handle: String) -> Result<Status, Self::Error> {
+		match self.handles.remove(&handle) {
+			Some(OpenHandle::Write { writer, .. }) => {
+				writer.commit().await.map_err(status_code)?;
+			}
+			Some(OpenHandle::Read { .. } | OpenHandle::Dir { .. }) => {}
+			None => return Err(StatusCode::Failure),
+		}
+
+		Ok(Self::ok_status(id))
+	}
+
+	async fn remove(&mut self, id: u32, filename: String) -> Result<Status, Self::Error> {
+		let (server, path) = self.resolve_in_server(&filename).await?;
+		let fs = server.get_fs();
+
+		if let FSEntry::Dir(_) = fs.stat(&path).await.map_err(status_code)? {
+			return Err(StatusCode::Failure);
+		}
+
+		// Deleted files can be recovered from the trash, like deletions through the web panel
+		server
+			.get_trash()
+			.put(fs.as_ref(), &path)
+			.await
+			.map_err(status_code)?;
+
+		Ok(Self::ok_status(id))
+	}
+
+	async fn mkdir(
+		&mut self,
+		id: u32,
+		path: String,
+		_attrs: FileAttributes,
+	) -> Result<Status, Self::Error> {
+		let (server, path) = self.resolve_in_server(&path).await?;
+
+		server
+			.get_fs()
+			.create_dir(&path)
+			.await
+			.map_err(status_code)?;
+
+		Ok(Self::ok_status(id))
+	}
+
+	async fn rmdir(&mut self, id: u32, path: String) -> Result<Status, Self::Error> {
+		let (server, path) = self.resolve_in_server(&path).await?;
+		let fs = server.get_fs();
+
+		if let FSEntry::File(_) = fs.stat(&path).await.map_err(status_code)? {
+			return Err(StatusCode::Failure);
+		}
+
+		if !fs.list_dir(&path).await.map_err(status_code)?.is_empty() {
+			return Err(StatusCode::Failure);
+		}
+
+		fs.delete(&path).await.map_err(status_code)?;
+
+		Ok(Self::ok_status(id))
+	}
+
+	async fn rename(
+		&mut self,
+		id: u32,
+		oldpath: String,
+		newpath: String,
+	) -> Result<Status, Self::Error> {
+		let (server, old_path) = self.resolve_in_server(&oldpath).await?;
+		let (new_server, new_path) = self.resolve_in_server(&newpath).await?;
+
+		if server.id() != new_server.id() {
+			return Err(StatusCode::OpUnsupported);
+		}
+
+		let fs = server.get_fs();
+
+		// SFTP v3 renames must not replace an existing entry
+		match fs.stat(&new_path).await {
+			Ok(_) => return Err(StatusCode::Failure),
+			Err(FileManagerError::NotFound) => {}
+			Err(err) => return Err(status_code(err)),
+		}
+
+		fs.relocate(&old_path, &new_path)
+			.await
+			.map_err(status_code)?;
+
+		Ok(Self::ok_status(id))
+	}
+}
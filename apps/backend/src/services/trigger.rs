@@ -0,0 +1,579 @@
+use crate::config::{
+	CLIENT_USER_AGENT, SERVER_EVENTS_LIMIT, TRIGGER_NOTIFY_TIMEOUT, TRIGGER_REGEX_SIZE_LIMIT,
+	TRIGGER_RESTART_POLL,
+};
+use crate::db::models::console_trigger::{
+	ConsoleTrigger, ServerEvent, TriggerAction, TriggerStream,
+};
+use crate::db::repositories::console_trigger::TriggerRepository;
+use crate::models::server::{ConsoleLine, ConsoleStreamType, ServerError, ServerStateInfo};
+use crate::services::server::ServerService;
+use crate::services::Service;
+use regex::{Captures, Regex, RegexBuilder};
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Instant;
+use thiserror::Error;
+use time::OffsetDateTime;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{Mutex, RwLock};
+use tracing::{instrument, Instrument};
+use uuid::Uuid;
+
+#[derive(Error, Debug)]
+pub enum TriggerServiceError {
+	#[error("No such trigger: {0}")]
+	NoSuchTrigger(Uuid),
+	#[error("Invalid trigger: {0}")]
+	InvalidTrigger(String),
+	#[error("Internal server error: {0}")]
+	ServerError(String),
+}
+
+impl From<sqlx::Error> for TriggerServiceError {
+	fn from(err: sqlx::Error) -> Self {
+		Self::ServerError(err.to_string())
+	}
+}
+
+/// Fields of a trigger that can be set
+pub struct TriggerSettings {
+	pub name: String,
+	pub pattern: String,
+	pub stream: TriggerStream,
+	pub cooldown: u32,
+	pub action: TriggerAction,
+	pub enabled: bool,
+}
+
+/// Internal: An enabled trigger with its pattern compiled
+struct CompiledTrigger {
+	trigger: ConsoleTrigger,
+	regex: Regex,
+	/// When the trigger last fired, for its cooldown
+	last_fired: std::sync::Mutex<Option<Instant>>,
+}
+
+impl CompiledTrigger {
+	/// Internal: Whether the trigger listens to a console stream
+	fn matches_stream(&self, stream: ConsoleStreamType) -> bool {
+		match (self.trigger.stream, stream) {
+			// Hook output isn't server output
+			(_, ConsoleStreamType::Hook) => false,
+			(TriggerStream::Any, _)
+			| (TriggerStream::Stdout, ConsoleStreamType::Stdout)
+			| (TriggerStream::Stderr, ConsoleStreamType::Stderr) => true,
+			_ => false,
+		}
+	}
+
+	/// Internal: Start the cooldown if it isn't running. Returns whether the trigger may fire.
+	fn try_fire(&self) -> bool {
+		let mut last_fired = self
+			.last_fired
+			.lock()
+			.unwrap_or_else(std::sync::PoisonError::into_inner);
+
+		let cooling_down = last_fired
+			.is_some_and(|time| time.elapsed().as_secs() < u64::from(self.trigger.cooldown));
+
+		if cooling_down {
+			return false;
+		}
+
+		*last_fired = Some(Instant::now());
+		true
+	}
+}
+
+/// Runs the actions of console triggers when lines of a server's output match them
+pub struct TriggerService {
+	trigger_repo: Arc<dyn TriggerRepository>,
+	server_service: Arc<ServerService>,
+	/// Enabled triggers by server
+	triggers: RwLock<HashMap<Uuid, Vec<Arc<CompiledTrigger>>>>,
+	/// Servers whose console output is being matched
+	watched: Mutex<HashSet<Uuid>>,
+}
+
+impl Service for TriggerService {}
+
+impl TriggerService {
+	pub fn new(
+		trigger_repo: Arc<dyn TriggerRepository>,
+		server_service: Arc<ServerService>,
+	) -> Self {
+		Self {
+			trigger_repo,
+			server_service,
+			triggers: RwLock::new(HashMap::new()),
+			watched: Mutex::new(HashSet::new()),
+		}
+	}
+
+	/// Load the triggers of all servers and start matching their output
+	#[instrument(name = "TriggerService.Init", skip_all)]
+	pub async fn init(self: &Arc<Self>) -> Result<(), TriggerServiceError> {
+		let triggers = self.trigger_repo.list_triggers().await?;
+
+		let server_ids: HashSet<Uuid> = triggers.iter().map(|trigger| trigger.server_id).collect();
+
+		for server_id in server_ids {
+			self.reload(server_id).await?;
+		}
+
+		Ok(())
+	}
+
+	/// List the triggers of a server
+	pub async fn list(&self, server_id: Uuid) -> Result<Vec<ConsoleTrigger>, TriggerServiceError> {
+		Ok(self.trigger_repo.list_server_triggers(server_id).await?)
+	}
+
+	/// Create a trigger for a server
+	pub async fn create(
+		self: &Arc<Self>,
+		server_id: Uuid,
+		settings: TriggerSettings,
+	) -> Result<ConsoleTrigger, TriggerServiceError> {
+		validate(&settings)?;
+
+		let trigger = ConsoleTrigger {
+			id: Uuid::new_v4(),
+			server_id,
+			name: settings.name,
+			pattern: settings.pattern,
+			stream: settings.stream,
+			cooldown: settings.cooldown,
+			action: sqlx::types::Json(settings.action),
+			enabled: settings.enabled,
+			created_at: OffsetDateTime::now_utc().unix_timestamp(),
+		};
+
+		self.trigger_repo.create_trigger(&trigger).await?;
+		self.reload(server_id).await?;
+
+		Ok(trigger)
+	}
+
+	/// Replace the settings of a trigger
+	pub async fn update(
+		self: &Arc<Self>,
+		server_id: Uuid,
+		trigger_id: Uuid,
+		settings: TriggerSettings,
+	) -> Result<ConsoleTrigger, TriggerServiceError> {
+		validate(&settings)?;
+
+		let mut trigger = self.find(server_id, trigger_id).await?;
+
+		trigger.name = settings.name;
+		trigger.pattern = settings.pattern;
+		trigger.stream = settings.stream;
+		trigger.cooldown = settings.cooldown;
+		trigger.action = sqlx::types::Json(settings.action);
+		trigger.enabled = settings.enabled;
+
+		self.trigger_repo.update_trigger(&trigger).await?;
+		self.reload(server_id).await?;
+
+		Ok(trigger)
+	}
+
+	/// Delete a trigger
+	pub async fn delete(
+		self: &Arc<Self>,
+		server_id: Uuid,
+		trigger_id: Uuid,
+	) -> Result<(), TriggerServiceError> {
+		self.find(server_id, trigger_id).await?;
+		self.trigger_repo.delete_trigger(trigger_id).await?;
+		self.reload(server_id).await
+	}
+
+	/// Delete the triggers and events of a deleted server
+	pub async fn delete_server(&self, server_id: Uuid) -> Result<(), TriggerServiceError> {
+		self.trigger_repo.delete_server_triggers(server_id).await?;
+		self.triggers.write().await.remove(&server_id);
+		Ok(())
+	}
+
+	/// List the most recent events of a server, newest first
+	pub async fn list_events(
+		&self,
+		server_id: Uuid,
+	) -> Result<Vec<ServerEvent>, TriggerServiceError> {
+		Ok(self
+			.trigger_repo
+			.list_events(server_id, SERVER_EVENTS_LIMIT)
+			.await?)
+	}
+
+	/// Internal: Get a trigger of a server
+	async fn find(
+		&self,
+		server_id: Uuid,
+		trigger_id: Uuid,
+	) -> Result<ConsoleTrigger, TriggerServiceError> {
+		self.trigger_repo
+			.list_server_triggers(server_id)
+			.await?
+			.into_iter()
+			.find(|trigger| trigger.id == trigger_id)
+			.ok_or(TriggerServiceError::NoSuchTrigger(trigger_id))
+	}
+
+	/// Internal: Compile the enabled triggers of a server and watch its console if it has any.
+	/// Cooldowns of unchanged triggers carry over.
+	async fn reload(self: &Arc<Self>, server_id: Uuid) -> Result<(), TriggerServiceError> {
+		let triggers = self.trigger_repo.list_server_triggers(server_id).await?;
+		let mut guard = self.triggers.write().await;
+		let previous = guard.remove(&server_id).unwrap_or_default();
+
+		let compiled: Vec<Arc<CompiledTrigger>> = triggers
+			.into_iter()
+			.filter(|trigger| trigger.enabled)
+			.filter_map(|trigger| {
+				let regex = match compile(&trigger.pattern) {
+					Ok(regex) => regex,
+					Err(err) => {
+						tracing::warn!("Skipping trigger {}: {}", trigger.id, err);
+						return None;
+					}
+				};
+
+				let last_fired = previous
+					.iter()
+					.find(|old| {
+						old.trigger.id == trigger.id && old.trigger.pattern == trigger.pattern
+					})
+					.and_then(|old| {
+						*old.last_fired
+							.lock()
+							.unwrap_or_else(std::sync::PoisonError::into_inner)
+					});
+
+				Some(Arc::new(CompiledTrigger {
+					trigger,
+					regex,
+					last_fired: std::sync::Mutex::new(last_fired),
+				}))
+			})
+			.collect();
+
+		if compiled.is_empty() {
+			return Ok(());
+		}
+
+		guard.insert(server_id, compiled);
+		drop(guard);
+
+		self.watch(server_id).await;
+		Ok(())
+	}
+
+	/// Internal: Match the console output of a server against its triggers, unless that happens
+	/// already. Stops once the server is gone.
+	async fn watch(self: &Arc<Self>, server_id: Uuid) {
+		let Ok(server) = self.server_service.get_server(server_id).await else {
+			return;
+		};
+
+		if !self.watched.lock().await.insert(server_id) {
+			return;
+		}
+
+		// Don't keep the server alive, so the channel closes when it is deleted
+		let mut console_rx = server.subscribe_console();
+		drop(server);
+
+		let service = self.clone();
+		let watcher = async move {
+			loop {
+				match console_rx.recv().await {
+					Ok(line) => service.handle_line(server_id, &line).await,
+					Err(RecvError::Lagged(skipped)) => {
+						tracing::warn!("Skipped matching {} console lines", skipped);
+					}
+					Err(RecvError::Closed) => break,
+				}
+			}
+
+			service.watched.lock().await.remove(&server_id);
+		};
+
+		tokio::spawn(watcher.instrument(
+			tracing::info_span!(parent: None, "TriggerWatcher", server_id = %server_id),
+		));
+	}
+
+	/// Internal: Fire the triggers a console line matches
+	async fn handle_line(self: &Arc<Self>, server_id: Uuid, line: &ConsoleLine) {
+		let triggers = self
+			.triggers
+			.read()
+			.await
+			.get(&server_id)
+			.cloned()
+			.unwrap_or_default();
+
+		for trigger in triggers {
+			if !trigger.matches_stream(line.stream) {
+				continue;
+			}
+
+			let Some(captures) = trigger.regex.captures(line.plain()) else {
+				continue;
+			};
+
+			if !trigger.try_fire() {
+				continue;
+			}
+
+			tracing::info!("Console trigger {} fired", trigger.trigger.name);
+
+			let action = expand_action(&trigger.trigger.action, &captures);
+			let service = self.clone();
+			let line = line.plain().to_string();
+
+			// Actions may take a while, e.g. restarts, and must not hold up matching
+			tokio::spawn(
+				async move {
+					if let Err(err) = service.run_action(&trigger.trigger, action, line).await {
+						tracing::warn!("Console trigger {} failed: {}", trigger.trigger.name, err);
+					}
+				}
+				.in_current_span(),
+			);
+		}
+	}
+
+	/// Internal: Run the action of a trigger
+	async fn run_action(
+		&self,
+		trigger: &ConsoleTrigger,
+		action: TriggerAction,
+		line: String,
+	) -> Result<(), TriggerServiceError> {
+		let server = self
+			.server_service
+			.get_server(trigger.server_id)
+			.await
+			.map_err(|err| TriggerServiceError::ServerError(err.to_string()))?;
+
+		match action {
+			TriggerAction::SendCommand { command } => server
+				.send_command(&command)
+				.await
+				.map_err(|err| TriggerServiceError::ServerError(err.to_string())),
+			TriggerAction::Notify { url, message } => {
+				let body = json!({
+					"server_id": trigger.server_id,
+					"trigger_id": trigger.id,
+					"trigger": trigger.name,
+					"message": message,
+					"line": line,
+				});
+
+				// A host name may resolve to a private address even though the URL passed validation
+				let url = validate_notify_url(&url).map_err(TriggerServiceError::InvalidTrigger)?;
+				let client = notify_client(&url).await?;
+
+				client
+					.post(url)
+					.timeout(TRIGGER_NOTIFY_TIMEOUT)
+					.json(&body)
+					.send()
+					.await
+					.and_then(reqwest::Response::error_for_status)
+					.map_err(|err| TriggerServiceError::ServerError(err.to_string()))?;
+
+				Ok(())
+			}
+			TriggerAction::Restart => {
+				// The server may already be on its way down, or still starting and killed once it
+				// runs. It only counts as stopped once its post-stop hooks are done. Those are
+				// bounded by their own timeouts, so there is no fixed timeout for the whole wait.
+				let mut killed = false;
+
+				loop {
+					match server.get_server_state().await {
+						Ok(ServerStateInfo::Stopped) => break,
+						Ok(ServerStateInfo::Running) if !killed => match server.kill().await {
+							Ok(()) | Err(ServerError::NotRunning) => killed = true,
+							Err(err) => {
+								return Err(TriggerServiceError::ServerError(err.to_string()))
+							}
+						},
+						_ => {}
+					}
+
+					tokio::time::sleep(TRIGGER_RESTART_POLL).await;
+				}
+
+				server.mark_crashed().await;
+
+				self.server_service
+					.start(&server)
+					.await
+					.map_err(|err| TriggerServiceError::ServerError(err.to_string()))?;
+
+				Ok(())
+			}
+			TriggerAction::RecordEvent { message } => {
+				let event = ServerEvent {
+					id: Uuid::new_v4(),
+					server_id: trigger.server_id,
+					trigger_id: Some(trigger.id),
+					message,
+					created_at: OffsetDateTime::now_utc().unix_timestamp(),
+				};
+
+				Ok(self.trigger_repo.add_event(&event).await?)
+			}
+		}
+	}
+}
+
+/// Internal: Compile a trigger pattern, limiting its size
+fn compile(pattern: &str) -> Result<Regex, regex::Error> {
+	RegexBuilder::new(pattern)
+		.size_limit(TRIGGER_REGEX_SIZE_LIMIT)
+		.build()
+}
+
+/// Internal: Check the settings of a trigger
+fn validate(settings: &TriggerSettings) -> Result<(), TriggerServiceError> {
+	if settings.name.trim().is_empty() {
+		return Err(TriggerServiceError::InvalidTrigger(
+			"Name must not be empty".to_string(),
+		));
+	}
+
+	compile(&settings.pattern)
+		.map_err(|err| TriggerServiceError::InvalidTrigger(format!("Invalid pattern: {err}")))?;
+
+	match &settings.action {
+		TriggerAction::SendCommand { command } if command.trim().is_empty() => Err(
+			TriggerServiceError::InvalidTrigger("Command must not be empty".to_string()),
+		),
+		TriggerAction::Notify { url, .. } => validate_notify_url(url)
+			.map(|_| ())
+			.map_err(TriggerServiceError::InvalidTrigger),
+		_ => Ok(()),
+	}
+}
+
+/// Internal: Parse a notification URL. It must be HTTP(S) and must not name a loopback,
+/// link-local or private host, so triggers can't reach services on the panel's network.
+fn validate_notify_url(url: &str) -> Result<reqwest::Url, String> {
+	let url = reqwest::Url::parse(url)
+		.ok()
+		.filter(|url| matches!(url.scheme(), "http" | "https"))
+		.ok_or_else(|| "Notification URL must be an HTTP(S) URL".to_string())?;
+
+	let host = notify_host(&url).ok_or_else(|| "Notification URL must have a host".to_string())?;
+
+	let public = host.parse::<IpAddr>().map_or_else(
+		|_| {
+			let name = host.trim_end_matches('.').to_ascii_lowercase();
+			name != "localhost" && !name.ends_with(".localhost")
+		},
+		is_public_ip,
+	);
+
+	if !public {
+		return Err("Notification URL must not point to a local or private host".to_string());
+	}
+
+	Ok(url)
+}
+
+/// Internal: Build a client for a notification URL. Its host must only resolve to public
+/// addresses, and the client connects to exactly those, so a second lookup can't return a
+/// different address. Proxies and redirects could reach other hosts, so neither is used.
+async fn notify_client(url: &reqwest::Url) -> Result<reqwest::Client, TriggerServiceError> {
+	let host = notify_host(url).unwrap_or_default();
+	let port = url.port_or_known_default().unwrap_or(80);
+
+	let addresses: Vec<_> = tokio::net::lookup_host((host, port))
+		.await
+		.map_err(|err| {
+			TriggerServiceError::ServerError(format!("Failed to resolve {host}: {err}"))
+		})?
+		.collect();
+
+	if addresses.iter().any(|address| !is_public_ip(address.ip())) {
+		return Err(TriggerServiceError::InvalidTrigger(format!(
+			"Notification host {host} resolves to a local or private address"
+		)));
+	}
+
+	reqwest::Client::builder()
+		.user_agent(CLIENT_USER_AGENT)
+		.redirect(reqwest::redirect::Policy::none())
+		.no_proxy()
+		.resolve_to_addrs(host, &addresses)
+		.build()
+		.map_err(|err| TriggerServiceError::ServerError(err.to_string()))
+}
+
+/// Internal: Get the host of a URL, without the brackets around IPv6 addresses
+fn notify_host(url: &reqwest::Url) -> Option<&str> {
+	url.host_str()
+		.map(|host| host.trim_start_matches('[').trim_end_matches(']'))
+}
+
+/// Internal: Whether an address is reachable on the internet, rather than the loopback,
+/// link-local, private, shared or an otherwise reserved address
+fn is_public_ip(ip: IpAddr) -> bool {
+	match ip {
+		IpAddr::V4(ip) => {
+			let [first, second, ..] = ip.octets();
+			// 100.64.0.0/10 is shared by carrier-grade NATs
+			let shared = first == 100 && (second & 0xc0) == 64;
+
+			!(ip.is_private()
+				|| ip.is_loopback()
+				|| ip.is_link_local()
+				|| ip.is_unspecified()
+				|| ip.is_broadcast()
+				|| ip.is_documentation()
+				|| shared)
+		}
+		IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+			Some(ip) => is_public_ip(IpAddr::V4(ip)),
+			None => {
+				!(ip.is_loopback()
+					|| ip.is_unspecified()
+					|| ip.is_unique_local()
+					|| ip.is_unicast_link_local())
+			}
+		},
+	}
+}
+
+/// Internal: Replace references to captures in the texts of an action
+fn expand_action(action: &TriggerAction, captures: &Captures) -> TriggerAction {
+	let expand = |template: &str| {
+		let mut expanded = String::new();
+		captures.expand(template, &mut expanded);
+		expanded
+	};
+
+	match action {
+		TriggerAction::SendCommand { command } => TriggerAction::SendCommand {
+			command: expand(command),
+		},
+		TriggerAction::Notify { url, message } => TriggerAction::Notify {
+			url: url.clone(),
+			message: expand(message),
+		},
+		TriggerAction::Restart => TriggerAction::Restart,
+		TriggerAction::RecordEvent { message } => TriggerAction::RecordEvent {
+			message: expand(message),
+		},
+	}
+}
@@ -1,30 +1,55 @@
 use std::sync::Arc;
 
 use crate::{
-	db::{models::user::User, repositories::user::UserRepository},
+	db::{
+		models::{app_password::AppPassword, ssh_key::SshKey, user::User},
+		repositories::{
+			app_password::AppPasswordRepository, ssh_key::SshKeyRepository, user::UserRepository,
+		},
+	},
 	services::Service,
 };
 
+use base64::{engine::general_purpose, Engine};
+use rand::Rng;
+use ssh_key::{HashAlg, PublicKey};
 use thiserror::Error;
+use time::OffsetDateTime;
 use tokio::task::spawn_blocking;
+use uuid::Uuid;
 
 #[derive(Error, Debug)]
 pub enum UserServiceError {
 	#[error("Username already taken")]
 	UsernameTaken,
+	#[error("Invalid SSH key: {0}")]
+	InvalidSshKey(String),
+	#[error("SSH key already registered")]
+	SshKeyExists,
 	#[error("Internal server error: {0}")]
 	ServerError(String),
 }
 
+#[allow(clippy::struct_field_names)]
 pub struct UserService {
 	user_repo: Arc<dyn UserRepository>,
+	ssh_key_repo: Arc<dyn SshKeyRepository>,
+	app_password_repo: Arc<dyn AppPasswordRepository>,
 }
 
 impl Service for UserService {}
 
 impl UserService {
-	pub fn new(user_repo: Arc<dyn UserRepository>) -> Self {
-		Self { user_repo }
+	pub fn new(
+		user_repo: Arc<dyn UserRepository>,
+		ssh_key_repo: Arc<dyn SshKeyRepository>,
+		app_password_repo: Arc<dyn AppPasswordRepository>,
+	) -> Self {
+		Self {
+			user_repo,
+			ssh_key_repo,
+			app_password_repo,
+		}
 	}
 
 	/// Retrieve a user by their ID.
@@ -104,4 +129,116 @@ impl UserService {
 
 		Ok(())
 	}
+
+	/// List the SSH keys a user registered.
+	pub async fn list_ssh_keys(&self, user: &User) -> Result<Vec<SshKey>, UserServiceError> {
+		self.ssh_key_repo
+			.list_user_keys(user.id)
+			.await
+			.map_err(|err| UserServiceError::ServerError(err.to_string()))
+	}
+
+	/// Register an SSH key in OpenSSH format for a user.
+	pub async fn add_ssh_key(
+		&self,
+		user: &User,
+		name: &str,
+		public_key: &str,
+	) -> Result<SshKey, UserServiceError> {
+		let mut key = PublicKey::from_openssh(public_key.trim())
+			.map_err(|err| UserServiceError::InvalidSshKey(err.to_string()))?;
+		key.set_comment("");
+
+		let fingerprint = key.fingerprint(HashAlg::Sha256).to_string();
+
+		if self
+			.ssh_key_repo
+			.get_user_key_by_fingerprint(user.id, &fingerprint)
+			.await
+			.map_err(|err| UserServiceError::ServerError(err.to_string()))?
+			.is_some()
+		{
+			return Err(UserServiceError::SshKeyExists);
+		}
+
+		let ssh_key = SshKey {
+			id: Uuid::new_v4(),
+			user_id: user.id,
+			name: name.to_string(),
+			public_key: key
+				.to_openssh()
+				.map_err(|err| UserServiceError::InvalidSshKey(err.to_string()))?,
+			fingerprint,
+			created_at: OffsetDateTime::now_utc().unix_timestamp(),
+		};
+
+		self.ssh_key_repo
+			.create_key(&ssh_key)
+			.await
+			.map_err(|err| UserServiceError::ServerError(err.to_string()))?;
+
+		Ok(ssh_key)
+	}
+
+	/// Remove an SSH key of a user. Returns whether the key existed.
+	pub async fn delete_ssh_key(
+		&self,
+		user: &User,
+		key_id: Uuid,
+	) -> Result<bool, UserServiceError> {
+		self.ssh_key_repo
+			.delete_user_key(user.id, key_id)
+			.await
+			.map_err(|err| UserServiceError::ServerError(err.to_string()))
+	}
+
+	/// List the app passwords of a user.
+	pub async fn list_app_passwords(
+		&self,
+		user: &User,
+	) -> Result<Vec<AppPassword>, UserServiceError> {
+		self.app_password_repo
+			.list_user_passwords(user.id)
+			.await
+			.map_err(|err| UserServiceError::ServerError(err.to_string()))
+	}
+
+	/// Create an app password for a user. The password itself is only returned here, only its
+	/// hash is stored.
+	pub async fn create_app_password(
+		&self,
+		user: &User,
+		name: &str,
+	) -> Result<(AppPassword, String), UserServiceError> {
+		let mut bytes = [0u8; 32];
+		rand::rng().fill(&mut bytes);
+		let password = general_purpose::URL_SAFE_NO_PAD.encode(bytes);
+
+		let app_password = AppPassword {
+			id: Uuid::new_v4(),
+			user_id: user.id,
+			name: name.to_string(),
+			password_hash: AppPassword::hash(&password),
+			created_at: OffsetDateTime::now_utc().unix_timestamp(),
+		};
+
+		self.app_password_repo
+			.create_password(&app_password)
+			.await
+			.map_err(|err| UserServiceError::ServerError(err.to_string()))?;
+
+		Ok((app_password, password))
+	}
+
+	/// Revoke an app password of a user. Returns whether the password existed.
+	pub async fn delete_app_password(
+		&self,
+		user: &User,
+		password_id: Uuid,
+	) -> Result<bool, UserServiceError> {
+		self.app_password_repo
+			.delete_user_password(user.id, password_id)
+			.await
+			.map_err(|err| UserServiceError::ServerError(err.to_string()))
+	}
 }